@@ -40,6 +40,22 @@ fn voxel_data_extract_material_index(voxel_data: u32) -> u32 {
     return voxel_data & 255u;
 }
 
+// 光照 16-23位 低4位是方块光 高4位是天空光 取两者中较亮的
+fn voxel_data_extract_light(voxel_data: u32) -> f32 {
+    let light = voxel_data >> 16u & 255u;
+    return f32(max(light & 15u, light >> 4u));
+}
+
+// 第26位 半透明的方块 保留贴图的透明度
+fn voxel_data_is_translucent(voxel_data: u32) -> bool {
+    return (voxel_data >> 26u & 1u) == 1u;
+}
+
+// 环境光遮蔽 24-25位 0是没有遮挡 3是最暗 每一级亮度减少0.15
+fn voxel_data_extract_occlusion(voxel_data: u32) -> f32 {
+    return 1.0 - 0.15 * f32(voxel_data >> 24u & 3u);
+}
+
 
 
 
@@ -66,6 +82,7 @@ struct VertexOutput {
     @location(1) voxel_data: u32,
     @location(2) world_position: vec3<f32>,
     @location(3) uv:vec2<f32>,
+    @location(4) occlusion: f32,
 };
 
 @vertex
@@ -78,6 +95,8 @@ fn vertex(vertex: Vertex) -> VertexOutput {
     out.voxel_data = vertex.voxel_data;
     out.world_position = world_position.xyz;
     out.uv = vertex.uv;
+    // 每个顶点的遮蔽不一样 在面上插值
+    out.occlusion = voxel_data_extract_occlusion(vertex.voxel_data);
     return out;
 }
 
@@ -93,6 +112,7 @@ struct FragmentInput {
     @location(2) world_position: vec3<f32>,
     // #import bevy_pbr::mesh_vertex_output
     @location(3) uv:vec2<f32>,
+    @location(4) occlusion: f32,
 };
 
 @fragment
@@ -113,7 +133,15 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     // pbr_input.material.reflectance = 0.7;
 
     pbr_input.flags |= MESH_FLAGS_SHADOW_RECEIVER_BIT;
-    pbr_input.material.base_color = textureSample(textures[layer], nearest_sampler, in.uv);
+    let base_color = textureSample(textures[layer], nearest_sampler, in.uv);
+    let translucent = voxel_data_is_translucent(in.voxel_data);
+    // 装饰方块和镂空方块贴图中透明的部分 半透明方块完全透明的部分
+    if ((base_color.a < 0.5 && !translucent) || base_color.a < 0.01) {
+        discard;
+    }
+    // 每暗一级亮度乘以0.8
+    let brightness = pow(0.8, 15.0 - voxel_data_extract_light(in.voxel_data)) * in.occlusion;
+    pbr_input.material.base_color = vec4<f32>(base_color.rgb * brightness, base_color.a);
 
     pbr_input.frag_coord = in.frag_coord;
     pbr_input.world_position =  vec4<f32>(in.world_position, 1.0);
@@ -124,7 +152,11 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
     pbr_input.N = normalize(mfn::mesh_normal_local_to_world(in.voxel_normal));
     pbr_input.V = fns::calculate_view(vec4<f32>(in.world_position, 1.0), pbr_input.is_orthographic);
     
-    return tone_mapping(fns::pbr(pbr_input),view.color_grading);
+    var color = fns::pbr(pbr_input);
+    if (translucent) {
+        color.a = base_color.a;
+    }
+    return tone_mapping(color, view.color_grading);
 }
 
 
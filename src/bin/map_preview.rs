@@ -0,0 +1,45 @@
+// 地图预览工具 不需要启动游戏 直接输出地形的预览图片
+use std::path::PathBuf;
+
+use clap::Parser;
+use just_join::{
+    client::voxels::voxel_materail_config::MaterailConfiguration,
+    tools::map_preview::{MapPreview, VoxelPalette},
+    voxel_world::map_database::MAP_SEED,
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "map_preview", about = "render map preview images")]
+struct PreviewArgs {
+    /// 中心点 x 坐标
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    x: f32,
+    /// 中心点 z 坐标
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    z: f32,
+    /// 预览半径(方块)
+    #[arg(long, default_value_t = 256.0)]
+    radius: f32,
+    /// 地图种子
+    #[arg(long, default_value_t = MAP_SEED, allow_negative_numbers = true)]
+    seed: i32,
+    /// 输出目录
+    #[arg(long, default_value = "preview")]
+    out: PathBuf,
+}
+
+fn main() {
+    let args = PreviewArgs::parse();
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let preview = MapPreview::sample(args.seed, args.x, args.z, args.radius, threads);
+
+    let config = MaterailConfiguration::load();
+    let palette = VoxelPalette::from_material_config(&config, &PathBuf::from("assets"));
+
+    match preview.save_all(&args.out, &palette) {
+        Ok(_) => println!("预览图片已输出到 {}", args.out.display()),
+        Err(err) => println!("输出预览图片失败{:?}", err),
+    }
+}
@@ -1,7 +1,14 @@
-use std::{net::UdpSocket, time::SystemTime};
+use std::{
+    net::UdpSocket,
+    time::{Duration, SystemTime},
+};
 
-use bevy::prelude::{
-    App, Camera3dBundle, Commands, PointLightBundle, Res, ResMut, Startup, Transform, Update, Vec3,
+use bevy::{
+    asset::{AssetPlugin, ChangeWatcher},
+    prelude::{
+        App, Camera3dBundle, Commands, PointLightBundle, Res, ResMut, Startup, Transform, Update,
+        Vec3,
+    },
 };
 use bevy_rapier3d::prelude::{NoUserData, RapierPhysicsPlugin};
 use bevy_renet::{
@@ -12,20 +19,38 @@ use bevy_renet::{
     transport::NetcodeServerPlugin,
     RenetServerPlugin,
 };
+use clap::{Parser, Subcommand};
 use just_join::{
     common::ServerClipSpheresPlugin,
     connection_config,
     server::{
-        async_chunk::ChunkDataPlugin, chunk::ServerChunkPlugin,
-        cross_through_check::CossTroughCheckPlugin, deal_message_system,
-        object_filing::ObjectFilingPlugin, player::ServerLobby, server_connect_system,
-        sp_physics::SpPhysicsPlugin, staff_rule_sync::ServerStaffRulePlugin, sync_body_and_head,
+        async_chunk::ChunkDataPlugin,
+        block_update::BlockUpdatePlugin,
+        chunk::ServerChunkPlugin,
+        content_sync::ServerContentSyncPlugin,
+        cross_through_check::CossTroughCheckPlugin,
+        deal_message_system,
+        dimension::ServerDimensionPlugin,
+        falling_block::FallingBlockPlugin,
+        fluid::FluidPlugin,
+        leaf_decay::LeafDecayPlugin,
+        object_filing::ObjectFilingPlugin,
+        player::ServerLobby,
+        pregen::pregenerate_world,
+        random_tick::{RandomTickConfig, RandomTickPlugin, DEFAULT_RANDOM_TICK_SPEED},
+        server_connect_system,
+        sp_physics::SpPhysicsPlugin,
+        staff_rule_sync::ServerStaffRulePlugin,
+        sync_body_and_head,
         terrain_physics::TerrainPhysicsPlugin,
     },
     sky::ServerSkyPlugins,
     staff::ServerStaffInfoPlugin,
-    voxel_world::{biomes::OtherTreePlugin, voxel_mesh::VoxelMeshPlugin},
-    PROTOCOL_ID,
+    voxel_world::{
+        biomes::OtherTreePlugin, dimension::find_dimension_by_name, map_database::MapDataBase,
+        voxel_mesh::VoxelMeshPlugin,
+    },
+    PROTOCOL_ID, WORD_PATH,
 };
 use renet_visualizer::RenetServerVisualizer;
 use seldom_state::StateMachinePlugin;
@@ -36,7 +61,7 @@ use smooth_bevy_cameras::{
 
 #[cfg(feature = "server_ui")]
 use {
-    bevy::DefaultPlugins,
+    bevy::{app::PluginGroup, DefaultPlugins},
     bevy_egui::{EguiContexts, EguiPlugin},
     bevy_rapier3d::render::RapierDebugRenderPlugin,
     smooth_bevy_cameras::controllers::fps::FpsCameraPlugin,
@@ -44,11 +69,42 @@ use {
 
 #[cfg(feature = "headless")]
 use bevy::{
-    asset::AssetPlugin,
     prelude::{AddAsset, Mesh},
     MinimalPlugins,
 };
 
+#[derive(Debug, Parser)]
+#[command(name = "server", about = "just join server")]
+struct ServerArgs {
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
+    /// 每个区块每次随机刻选择的方块数量 为0时关闭
+    #[arg(long, default_value_t = DEFAULT_RANDOM_TICK_SPEED)]
+    random_tick_speed: u32,
+    /// 可以使用管理员命令的用户名 可以指定多次
+    #[arg(long)]
+    admin: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+enum ServerCommand {
+    /// 离线预生成地图 不会打开网络端口
+    Pregen {
+        /// 中心点 x 坐标
+        #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+        x: f32,
+        /// 中心点 z 坐标
+        #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+        z: f32,
+        /// 生成半径(方块)
+        #[arg(long, default_value_t = 256.0)]
+        radius: f32,
+        /// 要生成的维度的名字
+        #[arg(long, default_value = "overworld")]
+        dimension: String,
+    },
+}
+
 fn new_renet_server() -> (RenetServer, NetcodeServerTransport) {
     let server = RenetServer::new(connection_config());
 
@@ -92,11 +148,32 @@ fn setup(mut commands: Commands) {
 }
 
 fn main() {
+    let args = ServerArgs::parse();
+    if let Some(ServerCommand::Pregen {
+        x,
+        z,
+        radius,
+        dimension,
+    }) = args.command
+    {
+        let Some(config) = find_dimension_by_name(&dimension) else {
+            println!("没有找到维度{}", dimension);
+            return;
+        };
+        let mut db = MapDataBase::new(WORD_PATH);
+        pregenerate_world(&mut db, config.dimension(), Vec3::new(x, 0.0, z), radius);
+        return;
+    }
+
     let mut app = App::new();
 
     #[cfg(feature = "server_ui")]
     {
-        app.add_plugins(DefaultPlugins);
+        // 监听模型文件的修改
+        app.add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+            ..Default::default()
+        }));
         app.add_plugins(RapierDebugRenderPlugin::default());
         app.add_plugins(EguiPlugin);
         app.add_plugins(FpsCameraPlugin::default());
@@ -105,7 +182,11 @@ fn main() {
     #[cfg(feature = "headless")]
     {
         app.add_plugins(MinimalPlugins);
-        app.add_plugins(AssetPlugin::default());
+        // 监听模型文件的修改
+        app.add_plugins(AssetPlugin {
+            watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+            ..Default::default()
+        });
         app.add_asset::<Mesh>();
     }
 
@@ -125,17 +206,34 @@ fn main() {
         ServerSkyPlugins,
         ObjectFilingPlugin,
         ServerStaffRulePlugin,
+        ServerContentSyncPlugin,
         CossTroughCheckPlugin,
         OtherTreePlugin,
         VoxelMeshPlugin,
         SpPhysicsPlugin,
     ));
+    // 方块更新以及各种模拟
+    app.add_plugins((
+        BlockUpdatePlugin,
+        FluidPlugin,
+        FallingBlockPlugin,
+        RandomTickPlugin,
+        LeafDecayPlugin,
+        ServerDimensionPlugin,
+    ));
+    app.insert_resource(RandomTickConfig {
+        blocks_per_chunk: args.random_tick_speed,
+        ..Default::default()
+    });
 
     let (server, transport) = new_renet_server();
     app.insert_resource(server);
     app.insert_resource(transport);
     app.insert_resource(RenetServerVisualizer::<200>::default());
-    app.insert_resource(ServerLobby::default());
+    app.insert_resource(ServerLobby {
+        admins: args.admin.into_iter().collect(),
+        ..Default::default()
+    });
 
     app.add_systems(Startup, setup);
     app.add_systems(Update, update_visulizer_system);
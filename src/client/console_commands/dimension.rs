@@ -0,0 +1,27 @@
+use bevy::prelude::ResMut;
+use bevy_console::ConsoleCommand;
+use bevy_renet::renet::RenetClient;
+use clap::Parser;
+
+use crate::client::message_def::{user_command::UserCommandMessage, ClientChannel};
+
+#[derive(Parser, ConsoleCommand)]
+#[command(name = "dimension", about = "move to another dimension (admin only)")]
+pub struct DimensionCommand {
+    // 维度的名字 和 dimensions.ron 中的一致
+    name: String,
+}
+
+pub fn change_dimension(
+    mut dimension_command: ConsoleCommand<DimensionCommand>,
+    mut client: ResMut<RenetClient>,
+) {
+    if let Some(Ok(DimensionCommand { name })) = dimension_command.take() {
+        let message =
+            bincode::serialize(&UserCommandMessage::ChangeDimension { name: name.clone() })
+                .unwrap();
+        client.send_message(ClientChannel::Command, message);
+        dimension_command.reply(format!("Request to enter dimension {}", name));
+        dimension_command.ok();
+    }
+}
@@ -6,8 +6,7 @@ use crate::{
     client::mesh_display::MeshManager,
     common::ClipSpheres,
     voxel_world::{
-        chunk::{get_chunk_key_i3_by_vec3, ChunkKey},
-        chunk_map::ChunkMap,
+        chunk::ChunkKey, chunk_map::ChunkMap, pos::ChunkPos, world_height::WORLD_HEIGHT,
     },
 };
 
@@ -24,23 +23,30 @@ pub fn check_mesh_state(
     mesh_manager: Res<MeshManager>,
     chunk_map: Res<ChunkMap>,
 ) {
-    let mut ive3 = get_chunk_key_i3_by_vec3(clip_spheres.new_sphere.center);
+    let mut ive3 = ChunkPos::from_vec3(clip_spheres.new_sphere.center).0;
     ive3.y = 0;
-    let chunk_key = ChunkKey(ive3);
+    let chunk_key = ChunkKey::new(ive3);
     println!(
         "Ready for mesh: {}",
         chunk_map.chunk_for_mesh_ready(chunk_key)
     );
     println!("Check On ChunkKey {:?}", chunk_key);
-    if let Some(entity) = mesh_manager.entities.get(&chunk_key) {
-        println!("Has entitiy {:?}", entity);
+    if mesh_manager.columns.contains(&chunk_key) {
+        println!("Has column mesh");
     } else {
-        println!("Not has entitiy");
+        println!("Not has column mesh");
     }
-    if let Some(entity) = mesh_manager.water_entities.get(&chunk_key) {
-        println!("Has water entitiy {:?}", entity);
-    } else {
-        println!("Not has water entitiy");
+    // 每个区块的mesh 空的区块没有
+    for chunk_y in WORLD_HEIGHT.chunk_ys() {
+        let mut section_key = chunk_key;
+        section_key.0.y = chunk_y;
+        println!(
+            "Section {} entity {:?} water entity {:?} translucent entity {:?}",
+            chunk_y,
+            mesh_manager.entities.get(&section_key),
+            mesh_manager.water_entities.get(&section_key),
+            mesh_manager.translucent_entities.get(&section_key)
+        );
     }
     if mesh_manager.fast_key.contains(&chunk_key) {
         println!("Has fast Key");
@@ -3,10 +3,14 @@ use bevy_console::{
     AddConsoleCommand, ConsoleCommandEntered, ConsoleOpen, ConsolePlugin, ConsoleSet,
 };
 
-use self::mesh_state::{check_mesh_state, MeshStateCommand};
+use self::{
+    dimension::{change_dimension, DimensionCommand},
+    mesh_state::{check_mesh_state, MeshStateCommand},
+};
 
 use super::player::controller::ControllerFlag;
 
+pub mod dimension;
 pub mod mesh_state;
 
 pub struct ConsoleCommandPlugins;
@@ -16,7 +20,8 @@ impl Plugin for ConsoleCommandPlugins {
         app.add_plugins(ConsolePlugin)
             .add_systems(PreUpdate, sync_flags)
             .add_systems(Update, raw_commands.in_set(ConsoleSet::Commands))
-            .add_console_command::<MeshStateCommand, _>(check_mesh_state);
+            .add_console_command::<MeshStateCommand, _>(check_mesh_state)
+            .add_console_command::<DimensionCommand, _>(change_dimension);
     }
 }
 
@@ -0,0 +1,42 @@
+// 客户端所在的维度
+// 服务器通知切换维度后 清空本地的区块 mesh LOD 和特殊mesh 之后按照新维度重新请求区块
+
+use bevy::prelude::{
+    in_state, on_event, Event, IntoSystemConfigs, OnExit, Plugin, ResMut, Resource, Update,
+};
+
+use crate::voxel_world::dimension::DimensionId;
+
+use super::{
+    lod_display::lod_setdown, mesh_display::mesh_chunk_map_setdown, sp_mesh_display::unset_all,
+    state_manager::GameState,
+};
+
+#[derive(Debug, Clone, Copy, Default, Resource)]
+pub struct CurrentDimension(pub DimensionId);
+
+#[derive(Debug, Clone, Event)]
+pub struct DimensionChangeEvent {
+    pub dimension: DimensionId,
+    pub name: String,
+}
+
+pub struct ClientDimensionPlugin;
+
+impl Plugin for ClientDimensionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<CurrentDimension>();
+        app.add_event::<DimensionChangeEvent>();
+        app.add_systems(
+            Update,
+            (mesh_chunk_map_setdown, lod_setdown, unset_all)
+                .run_if(on_event::<DimensionChangeEvent>())
+                .run_if(in_state(GameState::Game)),
+        );
+        app.add_systems(OnExit(GameState::Game), reset_dimension);
+    }
+}
+
+fn reset_dimension(mut current: ResMut<CurrentDimension>) {
+    *current = CurrentDimension::default();
+}
@@ -4,7 +4,7 @@ use ahash::HashSet;
 use bevy::{
     prelude::{
         in_state, Color, Commands, Component, Entity, Gizmos, IntoSystemConfigs,
-        MaterialMeshBundle, Plugin, Query, Res, ResMut, Resource, Transform, Update, Vec3,
+        MaterialMeshBundle, Plugin, Query, Res, ResMut, Resource, Transform, Update, Vec3, Without,
     },
     time::Time,
     utils::HashMap,
@@ -30,11 +30,16 @@ use super::{
 pub struct FilledObjectPool {
     // 服务端 entity 和 客户端 entity 对应表
     pub entities_map: HashMap<Entity, Entity>,
+    // 下落的方块 服务端 entity 和 客户端 entity 对应表
+    pub falling_map: HashMap<Entity, Entity>,
 }
 
 #[derive(Debug, Clone, Component)]
 pub struct FilledObjectCommpent;
 
+#[derive(Debug, Clone, Component)]
+pub struct FallingBlockCommpent;
+
 pub struct ClientFilledObjectnPlugin;
 
 impl Plugin for ClientFilledObjectnPlugin {
@@ -64,6 +69,7 @@ fn rotate_filled_objects(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn sync_filled_objects(
     mut commands: Commands,
     mut filled_object_pool: ResMut<FilledObjectPool>,
@@ -72,7 +78,11 @@ fn sync_filled_objects(
     materials: Res<MaterialStorge>,
     material_config: Res<MaterailConfiguration>,
     // mut mesh_assets: ResMut<Assets<Mesh>>,
-    mut query: Query<(Entity, &FilledObjectCommpent, &mut Transform)>,
+    mut query: Query<
+        (Entity, &FilledObjectCommpent, &mut Transform),
+        Without<FallingBlockCommpent>,
+    >,
+    mut falling_query: Query<(&FallingBlockCommpent, &mut Transform)>,
     mut sprite_params: Sprite3dParams,
 ) {
     while let Some(message) = client.receive_message(ServerChannel::FilledObjectMessage) {
@@ -165,6 +175,46 @@ fn sync_filled_objects(
                     }
                 }
             }
+            FilledObjectMessage::SyncFallingBlock(blocks) => {
+                let mut new_set: HashSet<Entity> = HashSet::default();
+                for (server_entity, voxel, pos) in blocks.iter() {
+                    new_set.insert(server_entity.clone());
+                    let translation = Vec3::new(pos[0], pos[1], pos[2]);
+                    if let Some(client_entity) = filled_object_pool.falling_map.get(server_entity) {
+                        if let Ok((_, mut trf)) = falling_query.get_mut(client_entity.clone()) {
+                            trf.translation = translation;
+                        }
+                    } else if let Some(render_mesh) =
+                        gen_one_volex_mesh(voxel.clone(), material_config.clone())
+                    {
+                        // 和地形一样大小的方块
+                        let mesh_handle = sprite_params.meshes.add(render_mesh);
+                        let client_entity = commands
+                            .spawn(MaterialMeshBundle {
+                                transform: Transform::from_translation(translation),
+                                mesh: mesh_handle,
+                                material: materials.0.clone(),
+                                ..Default::default()
+                            })
+                            .insert(FallingBlockCommpent)
+                            .id();
+                        filled_object_pool
+                            .falling_map
+                            .insert(server_entity.clone(), client_entity);
+                    }
+                }
+                let delete_keys: Vec<Entity> = filled_object_pool
+                    .falling_map
+                    .keys()
+                    .filter(|key| !new_set.contains(*key))
+                    .cloned()
+                    .collect();
+                for key in delete_keys.iter() {
+                    if let Some(client_entity) = filled_object_pool.falling_map.remove(key) {
+                        commands.entity(client_entity).despawn();
+                    }
+                }
+            }
         }
     }
 }
@@ -176,5 +226,9 @@ pub fn setdown_filled_object(
     for (_, entity) in filled_object_pool.entities_map.clone() {
         commands.entity(entity).despawn();
     }
+    for (_, entity) in filled_object_pool.falling_map.clone() {
+        commands.entity(entity).despawn();
+    }
     filled_object_pool.entities_map = HashMap::new();
+    filled_object_pool.falling_map = HashMap::new();
 }
@@ -0,0 +1,231 @@
+// 远处地形的LOD mesh
+// 完整精度的视野外面 按照距离向服务器请求降采样的列 用它生成低精度的mesh
+// 视野内的列在完整精度的mesh生成之前继续显示LOD 之后删除
+// 倍数改变时 新的mesh生成之后再替换旧的 不会出现空洞
+
+use std::time::{Duration, Instant};
+
+use bevy::{
+    prelude::{
+        in_state, Assets, Commands, Entity, IVec3, IntoSystemConfigs, MaterialMeshBundle, Mesh,
+        Plugin, Res, ResMut, Resource, Transform, Update, Vec3,
+    },
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::HashMap,
+};
+use bevy_renet::renet::RenetClient;
+use ndshape::Shape;
+
+use crate::{
+    common::ClipSpheres,
+    server::message_def::chunk_result::ChunkResult,
+    voxel_world::{
+        chunk::ChunkKey,
+        compress::uncompress,
+        lod::{lod_factor, lod_shape, max_lod_distance},
+        pos::ChunkPos,
+        voxel::Voxel,
+        world_height::WORLD_HEIGHT,
+    },
+    CHUNK_SIZE, VIEW_RADIUS,
+};
+
+use super::{
+    mesh_display::MeshManager,
+    mesh_priority::{drain_finished_tasks, sort_by_priority, MeshViewer},
+    message_def::{chunk_query::ChunkQuery, ClientChannel},
+    state_manager::GameState,
+    voxels::{
+        mesh::gen_mesh_lod, mesh_material::MaterialStorge,
+        voxel_materail_config::MaterailConfiguration,
+    },
+};
+
+// 每帧最多发送的LOD请求
+pub const LOD_QUERY_PER_FRAME: usize = 4;
+// 每帧最多生成的LOD mesh
+pub const LOD_MESH_PER_FRAME: usize = 2;
+// 请求之后这么久没有收到就重新请求
+pub const LOD_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default, Resource)]
+pub struct LodManager {
+    // 已经显示的列 倍数和实体 空的列没有实体
+    pub columns: HashMap<ChunkKey, (u32, Option<Entity>)>,
+    // 已经请求还没有生成mesh的列
+    pub requested: HashMap<ChunkKey, (u32, Instant)>,
+    // 收到的服务器数据 等待生成mesh
+    pub received: Vec<(ChunkKey, ChunkResult)>,
+}
+
+impl LodManager {
+    fn remove_column(&mut self, commands: &mut Commands, chunk_key: ChunkKey) {
+        if let Some((_, Some(entity))) = self.columns.remove(&chunk_key) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct LodTasks {
+    pub tasks: Vec<(ChunkKey, Task<(u32, Option<Mesh>)>)>,
+}
+
+pub struct ClientLodPlugin;
+
+impl Plugin for ClientLodPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<LodManager>();
+        app.init_resource::<LodTasks>();
+        app.add_systems(
+            Update,
+            (lod_query_system, lod_mesh_system)
+                .chain()
+                .run_if(in_state(GameState::Game))
+                .run_if(bevy_renet::transport::client_connected()),
+        );
+    }
+}
+
+// 视野周围每一列应该使用的倍数 完整精度的列是 None
+fn lod_columns(clip_spheres: &ClipSpheres) -> Vec<(ChunkKey, Option<u32>)> {
+    let mut center = ChunkPos::from_vec3(clip_spheres.new_sphere.center).0;
+    center.y = 0;
+    let full_distance = VIEW_RADIUS as i32 / CHUNK_SIZE;
+    let max_distance = max_lod_distance();
+    let mut columns = Vec::new();
+    for x in -max_distance..=max_distance {
+        for z in -max_distance..=max_distance {
+            let chunk_distance = x.abs().max(z.abs());
+            columns.push((
+                ChunkKey::new(center + IVec3::new(x, 0, z)),
+                lod_factor(chunk_distance, full_distance),
+            ));
+        }
+    }
+    columns
+}
+
+/**
+ * 请求需要的LOD列 删除不再需要的
+ * 完整精度的mesh已经生成或者离开了LOD范围的列删除
+ */
+pub fn lod_query_system(
+    mut commands: Commands,
+    clip_spheres: Res<ClipSpheres>,
+    viewer: Res<MeshViewer>,
+    mesh_manager: Res<MeshManager>,
+    mut lod_manager: ResMut<LodManager>,
+    mut client: ResMut<RenetClient>,
+) {
+    let wanted: HashMap<ChunkKey, Option<u32>> = lod_columns(&clip_spheres).into_iter().collect();
+    let removed: Vec<ChunkKey> = lod_manager
+        .columns
+        .keys()
+        .filter(|key| match wanted.get(*key) {
+            Some(Some(_)) => false,
+            Some(None) => mesh_manager.columns.contains(*key),
+            None => true,
+        })
+        .cloned()
+        .collect();
+    for key in removed {
+        lod_manager.remove_column(&mut commands, key);
+    }
+    lod_manager.requested.retain(|key, (factor, time)| {
+        wanted.get(key) == Some(&Some(*factor)) && time.elapsed() < LOD_QUERY_TIMEOUT
+    });
+
+    let mut keys: Vec<ChunkKey> = wanted
+        .iter()
+        .filter(|(key, factor)| match factor {
+            Some(factor) => {
+                lod_manager.columns.get(*key).map(|(f, _)| f) != Some(factor)
+                    && !lod_manager.requested.contains_key(*key)
+            }
+            None => false,
+        })
+        .map(|(key, _)| *key)
+        .collect();
+    sort_by_priority(&viewer, &mut keys);
+    for key in keys.into_iter().take(LOD_QUERY_PER_FRAME) {
+        if let Some(Some(factor)) = wanted.get(&key) {
+            let message = bincode::serialize(&ChunkQuery::GetLod(key, *factor)).unwrap();
+            client.send_message(ClientChannel::ChunkQuery, message);
+            lod_manager.requested.insert(key, (*factor, Instant::now()));
+        }
+    }
+}
+
+// 解压收到的数据并生成mesh 生成好了的替换旧的LOD
+pub fn lod_mesh_system(
+    mut commands: Commands,
+    viewer: Res<MeshViewer>,
+    material_config: Res<MaterailConfiguration>,
+    materials: Res<MaterialStorge>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut lod_manager: ResMut<LodManager>,
+    mut lod_tasks: ResMut<LodTasks>,
+) {
+    let pool = AsyncComputeTaskPool::get();
+    for (key, chunk_result) in std::mem::take(&mut lod_manager.received) {
+        let config = material_config.clone();
+        let task = match chunk_result {
+            ChunkResult::LodColumn { factor, data, .. } => pool.spawn(async move {
+                let voxels = uncompress(&data.0, data.1);
+                (factor, gen_mesh_lod(&voxels, factor, config))
+            }),
+            ChunkResult::LodSame { factor, voxel, .. } => pool.spawn(async move {
+                let voxels = vec![voxel; lod_shape(factor).size() as usize];
+                (factor, gen_mesh_lod(&voxels, factor, config))
+            }),
+            _ => continue,
+        };
+        lod_tasks.tasks.push((key, task));
+    }
+
+    let requested = &lod_manager.requested;
+    let finished = drain_finished_tasks(&viewer, &mut lod_tasks.tasks, LOD_MESH_PER_FRAME, |key| {
+        requested.contains_key(&key)
+    });
+    for (key, (factor, mesh)) in finished {
+        // 已经请求了别的倍数
+        if lod_manager.requested.get(&key).map(|(f, _)| *f) != Some(factor) {
+            continue;
+        }
+        lod_manager.requested.remove(&key);
+        lod_manager.remove_column(&mut commands, key);
+        let entity = mesh.map(|mesh| {
+            let min_block = ChunkPos::from(key).min_block().0;
+            let scale = factor as f32;
+            commands
+                .spawn(MaterialMeshBundle {
+                    transform: Transform::from_translation(Vec3::new(
+                        min_block.x as f32 - scale,
+                        WORLD_HEIGHT.min_y() as f32 - scale,
+                        min_block.z as f32 - scale,
+                    ))
+                    .with_scale(Vec3::splat(scale)),
+                    mesh: mesh_assets.add(mesh),
+                    material: materials.0.clone(),
+                    ..Default::default()
+                })
+                .id()
+        });
+        lod_manager.columns.insert(key, (factor, entity));
+    }
+}
+
+pub fn lod_setdown(
+    mut commands: Commands,
+    mut lod_manager: ResMut<LodManager>,
+    mut lod_tasks: ResMut<LodTasks>,
+) {
+    lod_tasks.tasks.clear();
+    for (_, (_, entity)) in lod_manager.columns.drain() {
+        if let Some(entity) = entity {
+            commands.entity(entity).despawn();
+        }
+    }
+    *lod_manager = LodManager::default();
+}
@@ -5,9 +5,9 @@ use std::{
 
 use bevy::{
     prelude::{
-        AlphaMode, AssetServer, Assets, Color, Commands, Component, Entity, Handle, IVec3,
-        IntoSystemConfigs, Last, MaterialMeshBundle, MaterialPlugin, Mesh, Plugin, PreUpdate, Res,
-        ResMut, Resource, StandardMaterial, Startup, Transform, Update, Vec3,
+        AlphaMode, AssetServer, Assets, Color, Commands, Component, Entity, EventReader, Handle,
+        IVec3, IntoSystemConfigs, Last, MaterialMeshBundle, MaterialPlugin, Mesh, Plugin,
+        PreUpdate, Res, ResMut, Resource, StandardMaterial, Startup, Transform, Update, Vec3,
     },
     tasks::{AsyncComputeTaskPool, Task},
     time::{Time, Timer, TimerMode},
@@ -19,7 +19,10 @@ use ndshape::{ConstShape, ConstShape3u32};
 
 use crate::{
     common::ClipSpheres,
-    server::message_def::{chunk_result::ChunkResult, ServerChannel},
+    server::{
+        block_update::{offset_block_pos, NEIGHBOR_OFFSETS},
+        message_def::{chunk_result::ChunkResult, ServerChannel},
+    },
     tools::get_all_v_chunk,
     voxel_world::{
         chunk::{
@@ -28,49 +31,109 @@ use crate::{
         },
         chunk_map::ChunkMap,
         compress::uncompress,
+        dimension::DimensionId,
+        light::{block_light, compute_column_light, emit_level, sky_light, ColumnLight},
+        pos::ChunkPos,
         voxel::Voxel,
+        world_height::WORLD_HEIGHT,
     },
-    CHUNK_SIZE, CHUNK_SIZE_U32, MATERIAL_RON, VIEW_RADIUS,
+    CHUNK_SIZE_U32, VIEW_RADIUS,
 };
 
 use super::{
+    dimension::CurrentDimension,
+    lod_display::LodManager,
+    mesh_priority::{
+        chunk_priority, column_in_view, drain_finished_tasks, poll_task, sort_by_priority,
+        update_mesh_viewer, MeshViewer, CHUNK_DATA_PER_FRAME, CHUNK_QUERY_PER_FRAME,
+        MESH_RESULT_PER_FRAME, MESH_TASK_PER_FRAME,
+    },
     message_def::{chunk_query::ChunkQuery, ClientChannel},
     ray_cast::MyRaycastSet,
     voxels::{
-        mesh::{gen_mesh, gen_mesh_water, pick_water},
-        mesh_material::{BindlessMaterial, MaterialStorge},
-        voxel_materail_config::MaterailConfiguration,
+        mesh::{gen_mesh, gen_mesh_decoration, gen_mesh_translucent, gen_mesh_water, pick_water},
+        mesh_material::{
+            load_textures, set_texture_count, texture_count, BindlessMaterial, MaterialStorge,
+            TranslucentMaterial,
+        },
+        voxel_materail_config::{MaterailConfiguration, MaterialSyncEvent},
     },
 };
 
+// mesh 和实体都按区块(16x16x16)保存 空的区块没有
+// columns fast_key data_status 按列保存 key 的 y 是 0
 #[derive(Debug, Clone, Resource, Default)]
 pub struct MeshManager {
     pub mesh_storge: HashMap<ChunkKey, Handle<Mesh>>,
     pub water_mesh_storge: HashMap<ChunkKey, Handle<Mesh>>,
+    pub translucent_mesh_storge: HashMap<ChunkKey, Handle<Mesh>>,
+    pub decoration_mesh_storge: HashMap<ChunkKey, Handle<Mesh>>,
     pub entities: HashMap<ChunkKey, Entity>,
     pub water_entities: HashMap<ChunkKey, Entity>,
+    pub translucent_entities: HashMap<ChunkKey, Entity>,
+    pub decoration_entities: HashMap<ChunkKey, Entity>,
+    // 已经生成了mesh的列
+    pub columns: HashSet<ChunkKey>,
     pub fast_key: HashSet<ChunkKey>,
     pub data_status: HashMap<ChunkKey, (bool, Instant)>,
 }
 
 #[derive(Resource)]
 pub struct MeshTasks {
-    pub tasks: Vec<Task<(Vec<Voxel>, ChunkKey)>>,
+    // 列 以及列的光照 列中不是空的区块和它们的邻居
+    pub tasks: Vec<(ChunkKey, Task<(ColumnLight, Vec<(ChunkKey, Vec<Voxel>)>)>)>,
 }
 
+// 解压区块数据的任务
 #[derive(Resource)]
 pub struct ChunkSyncTask {
-    pub tasks: Vec<Task<(ChunkKey, Vec<Voxel>)>>,
+    pub tasks: Vec<(ChunkKey, Task<Vec<Voxel>>)>,
 }
 
 #[derive(Resource)]
 pub struct ChunkUpdateTask {
-    pub tasks: Vec<Task<ChunkKey>>,
+    // 需要刷新的列 光照在后台重新计算 同一列的任务按照顺序处理
+    pub tasks: Vec<(ChunkKey, Task<ColumnUpdate>)>,
+}
+
+pub struct ColumnUpdate {
+    // 列中方块改变了的区块的 y
+    pub sections: HashSet<i32>,
+    pub light: ColumnLight,
+    // 只因为旁边列的光照改变而刷新的列 不再影响它旁边的列
+    pub spread: bool,
+}
+
+// 在后台重新计算一列的光照 区域的数据在这里复制 之后的修改不会影响这个任务
+fn spawn_column_update(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    sections: HashSet<i32>,
+    spread: bool,
+) -> (ChunkKey, Task<ColumnUpdate>) {
+    let region = chunk_map.get_light_region(chunk_key);
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        ColumnUpdate {
+            sections,
+            light: compute_column_light(chunk_key, &region),
+            spread,
+        }
+    });
+    (chunk_key, task)
 }
+
+// 水的材质 所有水的mesh共用一个
+#[derive(Resource)]
+pub struct WaterMaterial(pub Handle<StandardMaterial>);
 pub struct ClientMeshPlugin;
 
 impl Plugin for ClientMeshPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
+        // 贴图数组的大小要在创建渲染管线之前确定
+        let config = MaterailConfiguration::load();
+        set_texture_count(config.files.len());
+        app.insert_resource(config);
+        app.add_event::<MaterialSyncEvent>();
         app.add_plugins(MaterialPlugin::<BindlessMaterial>::default());
         app.insert_resource(ChunkMap::new());
         app.insert_resource(MeshManager::default());
@@ -78,6 +141,7 @@ impl Plugin for ClientMeshPlugin {
         app.insert_resource(generate_offset_resource(VIEW_RADIUS));
         app.insert_resource(ChunkSyncTask { tasks: Vec::new() });
         app.insert_resource(ChunkUpdateTask { tasks: Vec::new() });
+        app.insert_resource(MeshViewer::default());
         app.insert_resource(CycleCheckTimer(Timer::new(
             bevy::utils::Duration::from_millis(1000 * 2),
             TimerMode::Repeating,
@@ -87,12 +151,23 @@ impl Plugin for ClientMeshPlugin {
         // mesh_加载和更新相关
         app.add_systems(
             PreUpdate,
-            (gen_mesh_system, async_chunk_result, cycle_check_mesh)
+            (
+                update_mesh_viewer,
+                gen_mesh_system,
+                async_chunk_result,
+                cycle_check_mesh,
+            )
+                .chain()
                 .run_if(bevy_renet::transport::client_connected()),
         );
         app.add_systems(
             Update,
-            (update_mesh_system, save_chunk_result, update_chunk_mesh),
+            (
+                update_mesh_system,
+                save_chunk_result,
+                update_chunk_mesh,
+                reload_block_textures,
+            ),
         );
         app.add_systems(Last, deleter_mesh_system);
     }
@@ -101,44 +176,252 @@ impl Plugin for ClientMeshPlugin {
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    materials: ResMut<Assets<BindlessMaterial>>,
+    config: Res<MaterailConfiguration>,
+    mut materials: ResMut<Assets<BindlessMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let storge =
+        MaterialStorge::init_with_files(asset_server, materials.as_mut(), config.files.clone());
+    commands.insert_resource(storge.translucent(materials.as_mut()));
+    commands.insert_resource(storge);
+    commands.insert_resource(WaterMaterial(standard_materials.add(StandardMaterial {
+        base_color: Color::rgba(10. / 255., 18. / 255., 246. / 255., 0.6),
+        alpha_mode: AlphaMode::Blend,
+        ..Default::default()
+    })));
+}
+
+// 区块全是空气 没有数据也当作空的
+fn section_is_empty(chunk_map: &ChunkMap, section_key: ChunkKey) -> bool {
+    chunk_map
+        .get(section_key)
+        .map_or(true, |voxels| voxels.iter().all(|v| *v == Voxel::EMPTY))
+}
+
+// 一列中需要生成mesh的区块和它们带邻居的数据 空的区块直接跳过
+fn column_sections(chunk_map: &ChunkMap, column_key: ChunkKey) -> Vec<(ChunkKey, Vec<Voxel>)> {
+    WORLD_HEIGHT
+        .chunk_ys()
+        .map(|chunk_y| {
+            ChunkKey(
+                IVec3::new(column_key.0.x, chunk_y, column_key.0.z),
+                column_key.1,
+            )
+        })
+        .filter(|section_key| !section_is_empty(chunk_map, *section_key))
+        .map(|section_key| (section_key, chunk_map.get_neighbors(section_key)))
+        .collect()
+}
+
+// 区块mesh的位置 数据每个方向都多了一层邻居
+fn section_transform(section_key: ChunkKey) -> Transform {
+    Transform::from_translation((ChunkPos::from(section_key).min_block().0 - IVec3::ONE).as_vec3())
+}
+
+// 替换区块的一种mesh 还没有实体时生成 mesh是空的时候删除
+fn set_section_mesh(
+    commands: &mut Commands,
+    mesh_assets: &mut Assets<Mesh>,
+    storge: &mut HashMap<ChunkKey, Handle<Mesh>>,
+    entities: &mut HashMap<ChunkKey, Entity>,
+    section_key: ChunkKey,
+    mesh: Option<Mesh>,
+    spawn: impl FnOnce(&mut Commands, Handle<Mesh>) -> Entity,
+) {
+    let Some(mesh) = mesh else {
+        storge.remove(&section_key);
+        if let Some(entity) = entities.remove(&section_key) {
+            commands.entity(entity).despawn();
+        }
+        return;
+    };
+    if let (Some(handle), Some(entity)) = (storge.get(&section_key), entities.get(&section_key)) {
+        if let Some(old_mesh) = mesh_assets.get_mut(handle) {
+            // 更新AABB
+            if let Some(aabb) = mesh.compute_aabb() {
+                commands.entity(*entity).insert(aabb);
+            }
+            *old_mesh = mesh;
+            return;
+        }
+    }
+    let handle = mesh_assets.add(mesh);
+    storge.insert(section_key, handle.clone());
+    if let Some(entity) = entities.insert(section_key, spawn(commands, handle)) {
+        commands.entity(entity).despawn();
+    }
+}
+
+/**
+ * 生成一个区块的地形 装饰方块和水的mesh
+ * 已经有的mesh直接替换 新出现的生成实体 没有了的删除
+ */
+#[allow(clippy::too_many_arguments)]
+fn apply_section_meshes(
+    commands: &mut Commands,
+    mesh_manager: &mut MeshManager,
+    mesh_assets: &mut Assets<Mesh>,
+    materials: &MaterialStorge,
+    water_material: &WaterMaterial,
+    translucent_material: &TranslucentMaterial,
+    material_config: &MaterailConfiguration,
+    section_key: ChunkKey,
+    voxels: Vec<Voxel>,
+    light: &[u8],
+) {
+    let transform = section_transform(section_key);
+    set_section_mesh(
+        commands,
+        mesh_assets,
+        &mut mesh_manager.mesh_storge,
+        &mut mesh_manager.entities,
+        section_key,
+        gen_mesh(voxels.to_owned(), light, material_config.clone()),
+        |commands, mesh| {
+            commands
+                .spawn((
+                    MaterialMeshBundle {
+                        transform,
+                        mesh,
+                        material: materials.0.clone(),
+                        ..Default::default()
+                    },
+                    TerrainMesh(HitMeshType::Common),
+                    RaycastMesh::<MyRaycastSet>::default(), // Make this mesh ray cast-able
+                ))
+                .id()
+        },
+    );
+    set_section_mesh(
+        commands,
+        mesh_assets,
+        &mut mesh_manager.decoration_mesh_storge,
+        &mut mesh_manager.decoration_entities,
+        section_key,
+        gen_mesh_decoration(&voxels, light, material_config.clone()),
+        |commands, mesh| {
+            commands
+                .spawn((
+                    MaterialMeshBundle {
+                        transform,
+                        mesh,
+                        material: materials.0.clone(),
+                        ..Default::default()
+                    },
+                    TerrainMesh(HitMeshType::Decoration),
+                    RaycastMesh::<MyRaycastSet>::default(),
+                ))
+                .id()
+        },
+    );
+    set_section_mesh(
+        commands,
+        mesh_assets,
+        &mut mesh_manager.translucent_mesh_storge,
+        &mut mesh_manager.translucent_entities,
+        section_key,
+        gen_mesh_translucent(voxels.to_owned(), light, material_config.clone()),
+        |commands, mesh| {
+            commands
+                .spawn((
+                    MaterialMeshBundle {
+                        transform,
+                        mesh,
+                        material: translucent_material.0.clone(),
+                        ..Default::default()
+                    },
+                    TerrainMesh(HitMeshType::Common),
+                    RaycastMesh::<MyRaycastSet>::default(),
+                ))
+                .id()
+        },
+    );
+    set_section_mesh(
+        commands,
+        mesh_assets,
+        &mut mesh_manager.water_mesh_storge,
+        &mut mesh_manager.water_entities,
+        section_key,
+        gen_mesh_water(pick_water(voxels), material_config.clone()),
+        |commands, mesh| {
+            commands
+                .spawn(MaterialMeshBundle {
+                    transform,
+                    mesh,
+                    material: water_material.0.clone(),
+                    ..Default::default()
+                })
+                .insert(WaterMesh)
+                .id()
+        },
+    );
+}
+
+// 删除一个区块的全部mesh
+fn remove_section_meshes(
+    commands: &mut Commands,
+    mesh_manager: &mut MeshManager,
+    section_key: ChunkKey,
 ) {
-    // 初始化数据
-    let config = MaterailConfiguration::new()
-        .read_file(String::from(MATERIAL_RON))
-        .unwrap();
-    commands.insert_resource(config.clone());
-    commands.insert_resource(MaterialStorge::init_with_files(
-        asset_server,
-        materials,
-        config.files,
-    ));
+    mesh_manager.mesh_storge.remove(&section_key);
+    mesh_manager.water_mesh_storge.remove(&section_key);
+    mesh_manager.translucent_mesh_storge.remove(&section_key);
+    mesh_manager.decoration_mesh_storge.remove(&section_key);
+    for entities in [
+        &mut mesh_manager.entities,
+        &mut mesh_manager.water_entities,
+        &mut mesh_manager.translucent_entities,
+        &mut mesh_manager.decoration_entities,
+    ] {
+        if let Some(entity) = entities.remove(&section_key) {
+            commands.entity(entity).despawn();
+        }
+    }
 }
 
+/**
+ * 按照离相机的远近请求区块和开始计算mesh
+ * 每帧的数量有限 剩下的下一帧继续
+ */
 pub fn gen_mesh_system(
     chunk_map: Res<ChunkMap>,
     mut mesh_manager: ResMut<MeshManager>,
     clip_spheres: Res<ClipSpheres>,
     neighbour_offest: Res<NeighbourOffset>,
+    viewer: Res<MeshViewer>,
     mut mesh_task: ResMut<MeshTasks>,
     mut client: ResMut<RenetClient>,
 ) {
     let pool = AsyncComputeTaskPool::get();
-    for key in
-        find_chunk_keys_array_by_sphere_y_0(clip_spheres.new_sphere, neighbour_offest.0.clone())
-            .drain(..)
-    {
-        if !mesh_manager.entities.contains_key(&key) && !mesh_manager.fast_key.contains(&key) {
+    let mut keys =
+        find_chunk_keys_array_by_sphere_y_0(clip_spheres.new_sphere, neighbour_offest.0.clone());
+    sort_by_priority(&viewer, &mut keys);
+    let mut query_count = 0;
+    let mut task_count = 0;
+    for key in keys.drain(..) {
+        if query_count >= CHUNK_QUERY_PER_FRAME && task_count >= MESH_TASK_PER_FRAME {
+            break;
+        }
+        if !mesh_manager.columns.contains(&key) && !mesh_manager.fast_key.contains(&key) {
             // FIXME: 这要给数据加上 一个有效时间放置server端丢命令
             if let Some(_state) = mesh_manager.data_status.get(&key) {
-                if chunk_map.chunk_for_mesh_ready(key) {
+                if task_count < MESH_TASK_PER_FRAME && chunk_map.chunk_for_mesh_ready(key) {
+                    task_count += 1;
                     mesh_manager.fast_key.insert(key);
                     mesh_manager.data_status.insert(key, (true, Instant::now()));
-                    let volexs: Vec<Voxel> = chunk_map.get_with_neighbor_full_y(key);
-                    let task = pool.spawn(async move { (volexs.clone(), key) });
-                    mesh_task.tasks.push(task);
+                    let sections = column_sections(&chunk_map, key);
+                    let region: Vec<Voxel> = chunk_map.get_light_region(key);
+                    let task = pool.spawn(async move {
+                        let light = compute_column_light(key, &region);
+                        (light, sections)
+                    });
+                    mesh_task.tasks.push((key, task));
                 }
             } else if !chunk_map.chunk_for_mesh_ready(key) {
+                if query_count >= CHUNK_QUERY_PER_FRAME {
+                    continue;
+                }
+                query_count += 1;
                 let message = bincode::serialize(&ChunkQuery::GetFullY(key)).unwrap();
                 client.send_message(ClientChannel::ChunkQuery, message);
                 mesh_manager
@@ -153,176 +436,389 @@ pub fn gen_mesh_system(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn async_chunk_result(
     mesh_manager: Res<MeshManager>,
+    viewer: Res<MeshViewer>,
+    current_dimension: Res<CurrentDimension>,
     mut client: ResMut<RenetClient>,
     mut chunk_sync_task: ResMut<ChunkSyncTask>,
     mut chunk_map: ResMut<ChunkMap>,
     mut chunk_update_task: ResMut<ChunkUpdateTask>,
+    mut lod_manager: ResMut<LodManager>,
 ) {
     let pool = AsyncComputeTaskPool::get();
-    let mut key_set: HashSet<(usize, ChunkKey)> = HashSet::new();
+    let mut dirty: HashMap<ChunkKey, (usize, HashSet<i32>)> = HashMap::new();
+    // 需要解压的区块 按照远近排序后再开始解压
+    let mut new_chunks: Vec<(ChunkKey, ChunkResult)> = Vec::new();
     while let Some(message) = client.receive_message(ServerChannel::ChunkResult) {
-        let chunk_result: ChunkResult = bincode::deserialize(&message).unwrap();
+        let mut chunk_result: ChunkResult = bincode::deserialize(&message).unwrap();
+        let key = chunk_result.chunk_key_mut();
+        // 切换维度之前请求的区块 直接丢弃
+        if key.1 != current_dimension.0 {
+            continue;
+        }
+        // 客户端只有一个维度的数据 本地都使用主世界的 ChunkKey
+        *key = key.with_dimension(DimensionId::OVERWORLD);
         match chunk_result {
             ChunkResult::UpdateChunkData { key, data } => {
                 let voxel = uncompress(&data.0, data.1);
                 chunk_map.write_chunk(key.clone(), voxel);
-                key_set.insert((1, key.to_y_zore()));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(1, 0, 0))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(-1, 0, 0))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(0, 0, 1))));
-                key_set.insert((0, key.to_y_zore().add_ivec3(IVec3::new(0, 0, -1))));
-            }
-            ChunkResult::ChunkData { key, data } => {
-                let task = pool.spawn(async move { (key, uncompress(&data.0, data.1)) });
-                chunk_sync_task.tasks.push(task);
+                // 整个区块都变了 周围一圈区块的边界也要刷新 包括棱和角上的
+                for dx in -1..=1 {
+                    for dz in -1..=1 {
+                        let priority = if dx == 0 && dz == 0 { 1 } else { 0 };
+                        for chunk_y in key.0.y - 1..=key.0.y + 1 {
+                            mark_dirty(
+                                &mut dirty,
+                                key.add_ivec3(IVec3::new(dx, 0, dz)),
+                                priority,
+                                Some(chunk_y),
+                            );
+                        }
+                    }
+                }
             }
-            ChunkResult::ChunkSame((key, voxel)) => {
-                let task = pool.spawn(async move { (key, get_all_v_chunk(voxel)) });
-                chunk_sync_task.tasks.push(task);
+            ChunkResult::ChunkData { key, .. } | ChunkResult::ChunkSame((key, _)) => {
+                new_chunks.push((key, chunk_result));
             }
             ChunkResult::ChunkUpdateOne {
                 chunk_key,
                 pos,
                 voxel_type,
             } => {
-                // 1. 判断 更新 chunkmap的数据
-                if let Some(voxel) = chunk_map.map_data.get_mut(&chunk_key) {
-                    type SampleShape =
-                        ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-                    let index = SampleShape::linearize(pos) as usize;
-                    voxel[index] = voxel_type;
-                    let mut clone_chunk_key = chunk_key;
-                    clone_chunk_key.0.y = 0;
-                    key_set.insert((1, clone_chunk_key));
-                    // 2. 刷新mesh的task 注意是刷新的task
-                    if pos[0] == 0 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.x -= 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[0] == CHUNK_SIZE_U32 - 1 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.x += 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[2] == 0 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.z -= 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
-                    if pos[2] == CHUNK_SIZE_U32 - 1 {
-                        let mut new_chunk_key_i3 = clone_chunk_key.0;
-                        new_chunk_key_i3.z += 1;
-                        key_set.insert((0, ChunkKey(new_chunk_key_i3)));
-                    }
+                update_one_voxel(chunk_map.as_mut(), &mut dirty, chunk_key, pos, voxel_type);
+            }
+            ChunkResult::ChunkUpdateBatch { chunk_key, changes } => {
+                for (pos, voxel_type) in changes {
+                    update_one_voxel(chunk_map.as_mut(), &mut dirty, chunk_key, pos, voxel_type);
                 }
             }
+            ChunkResult::LodColumn { key, .. } | ChunkResult::LodSame { key, .. } => {
+                lod_manager.received.push((key, chunk_result));
+            }
         }
     }
+    new_chunks
+        .sort_by(|a, b| chunk_priority(&viewer, a.0).total_cmp(&chunk_priority(&viewer, b.0)));
+    for (key, chunk_result) in new_chunks {
+        let task = match chunk_result {
+            ChunkResult::ChunkData { data, .. } => {
+                pool.spawn(async move { uncompress(&data.0, data.1) })
+            }
+            ChunkResult::ChunkSame((_, voxel)) => pool.spawn(async move { get_all_v_chunk(voxel) }),
+            _ => continue,
+        };
+        chunk_sync_task.tasks.push((key, task));
+    }
     // 这里解决处理顺序
-    let mut sorted_vec: Vec<(usize, ChunkKey)> = key_set.into_iter().collect();
-    sorted_vec.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().reverse());
-
-    for (_, key) in sorted_vec.iter() {
-        let chunk_key: ChunkKey = *key;
-        if mesh_manager.entities.get(key).is_some() {
-            let task = pool.spawn(async move { chunk_key });
-            chunk_update_task.tasks.push(task);
+    let mut sorted_vec: Vec<(ChunkKey, (usize, HashSet<i32>))> = dirty.into_iter().collect();
+    sorted_vec.sort_by(|a, b| a.1 .0.cmp(&b.1 .0).reverse());
+
+    for (chunk_key, (_, sections)) in sorted_vec {
+        if mesh_manager.columns.contains(&chunk_key) {
+            chunk_update_task.tasks.push(spawn_column_update(
+                chunk_map.as_ref(),
+                chunk_key,
+                sections,
+                true,
+            ));
+        }
+    }
+}
+
+// 记录需要刷新mesh的列 section_y 是列中方块改变了的区块
+fn mark_dirty(
+    dirty: &mut HashMap<ChunkKey, (usize, HashSet<i32>)>,
+    chunk_key: ChunkKey,
+    priority: usize,
+    section_y: Option<i32>,
+) {
+    let (old_priority, sections) = dirty
+        .entry(chunk_key.to_y_zore())
+        .or_insert((priority, HashSet::new()));
+    *old_priority = (*old_priority).max(priority);
+    sections.extend(section_y);
+}
+
+// 更新一个体素的数据 并记录需要刷新mesh的区块
+fn update_one_voxel(
+    chunk_map: &mut ChunkMap,
+    dirty: &mut HashMap<ChunkKey, (usize, HashSet<i32>)>,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    voxel_type: Voxel,
+) {
+    // 光照可能改变的距离 旁边的光照和新旧方块的亮度中最大的
+    let mut reach = 1;
+    for offset in NEIGHBOR_OFFSETS {
+        let (key, p) = offset_block_pos(chunk_key, pos, offset);
+        if let Some(light) = chunk_map.get_light(key, p) {
+            reach = reach.max(sky_light(light)).max(block_light(light));
+        }
+    }
+    // 1. 判断 更新 chunkmap的数据
+    if let Some(voxel) = chunk_map.map_data.get_mut(&chunk_key) {
+        type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+        let index = SampleShape::linearize(pos) as usize;
+        reach = reach
+            .max(emit_level(voxel[index]))
+            .max(emit_level(voxel_type));
+        voxel[index] = voxel_type;
+        // 2. 刷新mesh的task 注意是刷新的task
+        // 区块的mesh数据带着一圈邻居 边界上的方块会影响旁边区块的mesh
+        // 棱和角上的区块也会受影响 它们的环境光遮蔽会改变
+        let near = |p: u32| {
+            let mut offsets = vec![0];
+            if p == 0 {
+                offsets.push(-1);
+            }
+            if p == CHUNK_SIZE_U32 - 1 {
+                offsets.push(1);
+            }
+            offsets
+        };
+        for dx in near(pos[0]) {
+            for dy in near(pos[1]) {
+                for dz in near(pos[2]) {
+                    let priority = if dx == 0 && dz == 0 { 1 } else { 0 };
+                    mark_dirty(
+                        dirty,
+                        chunk_key.add_ivec3(IVec3::new(dx, 0, dz)),
+                        priority,
+                        Some(chunk_key.0.y + dy),
+                    );
+                }
+            }
+        }
+        // 光照能照到的列也要重新计算光照
+        let distance = |p: u32, d: i32| match d {
+            -1 => p + 1,
+            1 => CHUNK_SIZE_U32 - p,
+            _ => 0,
+        };
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                if dx == 0 && dz == 0 {
+                    continue;
+                }
+                let steps = distance(pos[0], dx) + distance(pos[2], dz);
+                if steps <= reach as u32 {
+                    mark_dirty(dirty, chunk_key.add_ivec3(IVec3::new(dx, 0, dz)), 0, None);
+                }
+            }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn update_chunk_mesh(
     mut commands: Commands,
     mut chunk_update_task: ResMut<ChunkUpdateTask>,
     material_config: Res<MaterailConfiguration>,
-    chunk_map: Res<ChunkMap>,
+    materials: Res<MaterialStorge>,
+    water_material: Res<WaterMaterial>,
+    translucent_material: Res<TranslucentMaterial>,
+    mut chunk_map: ResMut<ChunkMap>,
     mut mesh_manager: ResMut<MeshManager>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
 ) {
     let l = chunk_update_task.tasks.len().min(16);
-    for ele in chunk_update_task.tasks.drain(..l) {
-        if let Some(chunk_key) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            update_mesh(
-                &mut commands,
+    // 1. 保存后台算好的光照 找出光照改变了的区块
+    let mut lights: HashMap<ChunkKey, ColumnLight> = HashMap::new();
+    let mut dirty: HashMap<ChunkKey, HashSet<i32>> = HashMap::new();
+    let mut neighbors: HashMap<ChunkKey, HashSet<i32>> = HashMap::new();
+    let mut pending = Vec::new();
+    // 还有没完成的任务的列 后面的任务要等它完成 不然旧的光照会覆盖新的
+    let mut waiting: HashSet<ChunkKey> = HashSet::new();
+    for (chunk_key, mut task) in chunk_update_task.tasks.drain(..l) {
+        if waiting.contains(&chunk_key) {
+            pending.push((chunk_key, task));
+            continue;
+        }
+        // 没有完成的任务留到下一帧 不能丢掉方块的修改
+        let Some(update) = poll_task(&mut task) else {
+            waiting.insert(chunk_key);
+            pending.push((chunk_key, task));
+            continue;
+        };
+        let mut changed: HashMap<ChunkKey, HashSet<i32>> = HashMap::new();
+        changed
+            .entry(chunk_key)
+            .or_default()
+            .extend(update.sections);
+        save_column_light(
+            chunk_map.as_mut(),
+            &update.light,
+            &mut changed,
+            update.spread,
+        );
+        for (key, sections) in changed {
+            if key == chunk_key {
+                dirty.entry(key).or_default().extend(sections);
+            } else {
+                neighbors.entry(key).or_default().extend(sections);
+            }
+        }
+        lights.insert(chunk_key, update.light);
+    }
+    // 放在剩下的任务前面 保持原来的顺序
+    pending.extend(chunk_update_task.tasks.drain(..));
+    chunk_update_task.tasks = pending;
+    // 2. 只因为旁边列的光照改变而刷新的列 也在后台计算光照
+    for (chunk_key, sections) in neighbors {
+        if lights.contains_key(&chunk_key) {
+            dirty.entry(chunk_key).or_default().extend(sections);
+        } else if mesh_manager.columns.contains(&chunk_key) {
+            chunk_update_task.tasks.push(spawn_column_update(
                 chunk_map.as_ref(),
                 chunk_key,
-                material_config.clone(),
-                mesh_manager.as_mut(),
-                mesh_assets.as_mut(),
-            )
+                sections,
+                false,
+            ));
         }
     }
+    // 3. 只刷新需要改变的区块
+    for (chunk_key, sections) in dirty {
+        if !mesh_manager.columns.contains(&chunk_key) {
+            continue;
+        }
+        let Some(light) = lights.get(&chunk_key) else {
+            continue;
+        };
+        update_mesh(
+            &mut commands,
+            chunk_map.as_ref(),
+            chunk_key,
+            sections,
+            light,
+            material_config.as_ref(),
+            mesh_manager.as_mut(),
+            mesh_assets.as_mut(),
+            materials.as_ref(),
+            water_material.as_ref(),
+            translucent_material.as_ref(),
+        );
+    }
 }
 
+/**
+ * 保存一列新的光照 并记录光照改变了的区块
+ * 改变的位置在区块的边界上时 旁边区块的mesh也会用到这个光照
+ * spread 为 false 时只记录这一列中的区块
+ */
+fn save_column_light(
+    chunk_map: &mut ChunkMap,
+    light: &ColumnLight,
+    dirty: &mut HashMap<ChunkKey, HashSet<i32>>,
+    spread: bool,
+) {
+    type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    let last = CHUNK_SIZE_U32 - 1;
+    for (key, data) in light.chunks.iter() {
+        let Some(old) = chunk_map.light_data.insert(*key, data.clone()) else {
+            dirty.entry(key.to_y_zore()).or_default().insert(key.0.y);
+            continue;
+        };
+        for (index, _) in old
+            .iter()
+            .zip(data.iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+        {
+            let [x, y, z] = DataShape::delinearize(index as u32);
+            dirty.entry(key.to_y_zore()).or_default().insert(key.0.y);
+            let mut touch = |offset: IVec3| {
+                let neighbor = key.add_ivec3(offset);
+                if !spread && neighbor.to_y_zore() != key.to_y_zore() {
+                    return;
+                }
+                dirty
+                    .entry(neighbor.to_y_zore())
+                    .or_default()
+                    .insert(neighbor.0.y);
+            };
+            if x == 0 {
+                touch(IVec3::NEG_X);
+            }
+            if x == last {
+                touch(IVec3::X);
+            }
+            if y == 0 {
+                touch(IVec3::NEG_Y);
+            }
+            if y == last {
+                touch(IVec3::Y);
+            }
+            if z == 0 {
+                touch(IVec3::NEG_Z);
+            }
+            if z == last {
+                touch(IVec3::Z);
+            }
+        }
+    }
+}
+
+/**
+ * 刷新一列中的一些区块 sections 是区块的 y
+ * 变成空的区块删除mesh
+ */
+#[allow(clippy::too_many_arguments)]
 pub fn update_mesh(
     commands: &mut Commands,
     chunk_map: &ChunkMap,
     chunk_key_y0: ChunkKey,
-    material_config: MaterailConfiguration,
+    sections: HashSet<i32>,
+    light: &ColumnLight,
+    material_config: &MaterailConfiguration,
     mesh_manager: &mut MeshManager,
     mesh_assets: &mut Assets<Mesh>,
+    materials: &MaterialStorge,
+    water_material: &WaterMaterial,
+    translucent_material: &TranslucentMaterial,
 ) {
-    let volexs: Vec<Voxel> = chunk_map.get_with_neighbor_full_y(chunk_key_y0);
-    match gen_mesh(volexs.to_owned(), material_config.clone()) {
-        Some(render_mesh) => {
-            if let Some(mesh_handle) = mesh_manager.mesh_storge.get(&chunk_key_y0) {
-                if let Some(mesh) = mesh_assets.get_mut(mesh_handle) {
-                    // 更新AABB
-                    if let Some(entity) = mesh_manager.entities.get(&chunk_key_y0) {
-                        if let Some(aabb) = render_mesh.compute_aabb() {
-                            commands.entity(*entity).insert(aabb);
-                        }
-                    }
-                    *mesh = render_mesh;
-                }
-            }
-            // 没有生成mesh就不管反正后面要生成
-        }
-        None => {
-            mesh_manager.mesh_storge.remove(&chunk_key_y0);
-            if let Some(entity) = mesh_manager.entities.remove(&chunk_key_y0) {
-                commands.entity(entity).despawn();
-            }
-        }
-    };
-    match gen_mesh_water(pick_water(volexs), material_config) {
-        Some(water_mesh) => {
-            if let Some(mesh_handle) = mesh_manager.water_mesh_storge.get(&chunk_key_y0) {
-                if let Some(mesh) = mesh_assets.get_mut(mesh_handle) {
-                    if let Some(entity) = mesh_manager.water_entities.get(&chunk_key_y0) {
-                        if let Some(aabb) = water_mesh.compute_aabb() {
-                            commands.entity(*entity).insert(aabb);
-                        }
-                    }
-                    *mesh = water_mesh;
-                }
-            }
-        }
-        None => {
-            mesh_manager.water_mesh_storge.remove(&chunk_key_y0);
-            if let Some(entity) = mesh_manager.water_entities.remove(&chunk_key_y0) {
-                commands.entity(entity).despawn();
-            }
+    for chunk_y in sections {
+        let Some(padded) = light.section_padded(chunk_y) else {
+            continue;
+        };
+        let mut section_key = chunk_key_y0;
+        section_key.0.y = chunk_y;
+        if section_is_empty(chunk_map, section_key) {
+            remove_section_meshes(commands, mesh_manager, section_key);
+            continue;
         }
+        apply_section_meshes(
+            commands,
+            mesh_manager,
+            mesh_assets,
+            materials,
+            water_material,
+            translucent_material,
+            material_config,
+            section_key,
+            chunk_map.get_neighbors(section_key),
+            padded,
+        );
     }
 }
 
+// 保存解压好的区块 近的先保存 离开视野的取消
 pub fn save_chunk_result(
     mut chunk_sync_task: ResMut<ChunkSyncTask>,
     mut chunk_map: ResMut<ChunkMap>,
+    viewer: Res<MeshViewer>,
+    clip_spheres: Res<ClipSpheres>,
 ) {
-    let l = chunk_sync_task.tasks.len().min(16);
-    for ele in chunk_sync_task.tasks.drain(..l) {
-        if let Some((chunk_key, data)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            chunk_map.write_chunk(chunk_key, data);
-        }
+    // 视野边上的列生成mesh时要用到外面一列的数据
+    let finished = drain_finished_tasks(
+        &viewer,
+        &mut chunk_sync_task.tasks,
+        CHUNK_DATA_PER_FRAME,
+        |chunk_key| column_in_view(&clip_spheres.new_sphere, chunk_key, 1),
+    );
+    for (chunk_key, data) in finished {
+        chunk_map.write_chunk(chunk_key, data);
     }
 }
 
@@ -334,96 +830,57 @@ pub struct TerrainMesh(pub HitMeshType);
 pub enum HitMeshType {
     Common,
     Sp(Vec3),
+    // 装饰方块 命中的就是方块本身
+    Decoration,
 }
 
 #[derive(Debug, Component)]
 pub struct WaterMesh;
 
+#[allow(clippy::too_many_arguments)]
 pub fn update_mesh_system(
     mut commands: Commands,
     mut mesh_manager: ResMut<MeshManager>,
     mut mesh_assets: ResMut<Assets<Mesh>>,
     mut mesh_task: ResMut<MeshTasks>,
     materials: Res<MaterialStorge>,
+    water_material: Res<WaterMaterial>,
+    translucent_material: Res<TranslucentMaterial>,
     material_config: Res<MaterailConfiguration>,
-    mut materials_assets: ResMut<Assets<StandardMaterial>>,
+    mut chunk_map: ResMut<ChunkMap>,
+    viewer: Res<MeshViewer>,
 ) {
-    let l: usize = mesh_task.tasks.len().min(3);
-    for ele in mesh_task.tasks.drain(..l) {
-        if let Some((voxels, chunk_key)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            if mesh_manager.entities.contains_key(&chunk_key) {
-                return;
-            } else {
-                if let Some(render_mesh) = gen_mesh(voxels.to_owned(), material_config.clone()) {
-                    let mesh_handle = mesh_assets.add(render_mesh);
-                    mesh_manager
-                        .mesh_storge
-                        .insert(chunk_key, mesh_handle.clone());
-                    mesh_manager.entities.insert(
-                        chunk_key,
-                        commands
-                            .spawn((
-                                MaterialMeshBundle {
-                                    transform: Transform::from_xyz(
-                                        (chunk_key.0.x * CHUNK_SIZE) as f32
-                                            - CHUNK_SIZE as f32 / 2.0
-                                            - 1.0,
-                                        -128.0 + CHUNK_SIZE as f32 / 2.0,
-                                        (chunk_key.0.z * CHUNK_SIZE) as f32
-                                            - CHUNK_SIZE as f32 / 2.0
-                                            - 1.0,
-                                    ),
-                                    mesh: mesh_handle.clone(),
-                                    material: materials.0.clone(),
-                                    ..Default::default()
-                                },
-                                TerrainMesh(HitMeshType::Common),
-                                RaycastMesh::<MyRaycastSet>::default(), // Make this mesh ray cast-able
-                            ))
-                            .id(),
-                    );
-                };
-                if let Some(water_mesh) =
-                    gen_mesh_water(pick_water(voxels.clone()), material_config.clone())
-                {
-                    let water_mesh_handle = mesh_assets.add(water_mesh);
-                    mesh_manager
-                        .water_mesh_storge
-                        .insert(chunk_key, water_mesh_handle.clone());
-                    mesh_manager.water_entities.insert(
-                        chunk_key,
-                        commands
-                            .spawn(MaterialMeshBundle {
-                                transform: Transform::from_xyz(
-                                    (chunk_key.0.x * CHUNK_SIZE) as f32
-                                        - CHUNK_SIZE as f32 / 2.0
-                                        - 1.0,
-                                    -128.0 + CHUNK_SIZE as f32 / 2.0,
-                                    (chunk_key.0.z * CHUNK_SIZE) as f32
-                                        - CHUNK_SIZE as f32 / 2.0
-                                        - 1.0,
-                                ),
-                                mesh: water_mesh_handle,
-                                material: materials_assets.add(StandardMaterial {
-                                    base_color: Color::rgba(
-                                        10. / 255.,
-                                        18. / 255.,
-                                        246. / 255.,
-                                        0.6,
-                                    ),
-                                    alpha_mode: AlphaMode::Blend,
-                                    ..Default::default()
-                                }),
-                                ..Default::default()
-                            })
-                            .insert(WaterMesh)
-                            .id(),
-                    );
-                }
-            }
+    // 离开视野的列 deleter_mesh_system 已经去掉了 fast_key 这里取消它们的任务
+    let finished = drain_finished_tasks(
+        &viewer,
+        &mut mesh_task.tasks,
+        MESH_RESULT_PER_FRAME,
+        |chunk_key| mesh_manager.fast_key.contains(&chunk_key),
+    );
+    for (chunk_key, (light, sections)) in finished {
+        // 已经生成过了
+        if mesh_manager.columns.contains(&chunk_key) {
+            continue;
         }
+        chunk_map.light_data.extend(light.chunks.iter().cloned());
+        for (section_key, voxels) in sections {
+            let Some(padded) = light.section_padded(section_key.0.y) else {
+                continue;
+            };
+            apply_section_meshes(
+                &mut commands,
+                mesh_manager.as_mut(),
+                mesh_assets.as_mut(),
+                materials.as_ref(),
+                water_material.as_ref(),
+                translucent_material.as_ref(),
+                material_config.as_ref(),
+                section_key,
+                voxels,
+                padded,
+            );
+        }
+        mesh_manager.columns.insert(chunk_key);
     }
 }
 
@@ -449,14 +906,13 @@ pub fn deleter_mesh_system(
     }
 
     for chunk_key in chunks_to_remove.into_iter() {
-        if let Some(entity) = mesh_manager.entities.remove(&chunk_key) {
-            mesh_manager.fast_key.remove(&chunk_key);
-            commands.entity(entity).despawn();
-        }
-        if let Some(entity) = mesh_manager.water_entities.remove(&chunk_key) {
-            mesh_manager.fast_key.remove(&chunk_key);
-            commands.entity(entity).despawn();
+        for chunk_y in WORLD_HEIGHT.chunk_ys() {
+            let mut section_key = chunk_key;
+            section_key.0.y = chunk_y;
+            remove_section_meshes(&mut commands, mesh_manager.as_mut(), section_key);
         }
+        mesh_manager.columns.remove(&chunk_key);
+        mesh_manager.fast_key.remove(&chunk_key);
         mesh_manager.data_status.remove(&chunk_key);
     }
 }
@@ -495,7 +951,7 @@ fn cycle_check_mesh(
                 client.send_message(ClientChannel::ChunkQuery, message);
             }
             if duration.as_millis() > 5 * 1000
-                && !mesh_manager.entities.contains_key(key)
+                && !mesh_manager.columns.contains(key)
                 && mesh_manager.fast_key.contains(key)
             {
                 println!("数据修复2");
@@ -505,6 +961,62 @@ fn cycle_check_mesh(
     }
 }
 
+/**
+ * 服务器推送了新的贴图列表之后 替换贴图并刷新已经生成的mesh
+ * 本地缺少贴图文件或者贴图数量超过了渲染管线创建时的大小 保留原来的贴图
+ * 掉落物和远处的 LOD 重新生成时才会使用新的贴图
+ */
+#[allow(clippy::too_many_arguments)]
+fn reload_block_textures(
+    mut events: EventReader<MaterialSyncEvent>,
+    asset_server: Res<AssetServer>,
+    mut material_config: ResMut<MaterailConfiguration>,
+    mut materials: ResMut<Assets<BindlessMaterial>>,
+    material_storge: Res<MaterialStorge>,
+    translucent_material: Res<TranslucentMaterial>,
+    mesh_manager: Res<MeshManager>,
+    chunk_map: Res<ChunkMap>,
+    mut chunk_update_task: ResMut<ChunkUpdateTask>,
+) {
+    // 只需要最新的一次
+    let Some(MaterialSyncEvent(config)) = events.iter().last() else {
+        return;
+    };
+    let config = config.clone();
+    let errors = config.validate(std::path::Path::new("assets"));
+    if !errors.is_empty() {
+        for error in errors {
+            println!("贴图配置错误: {}", error);
+        }
+        return;
+    }
+    if config.files.len() > texture_count() {
+        println!(
+            "贴图数量从{}增加到了{} 需要重启客户端才能生效",
+            texture_count(),
+            config.files.len()
+        );
+        return;
+    }
+    let textures = load_textures(&asset_server, &config.files);
+    for handle in [&material_storge.0, &translucent_material.0] {
+        if let Some(material) = materials.get_mut(handle) {
+            material.set_textures(textures.clone());
+        }
+    }
+    println!("重新加载了{}张贴图", config.files.len());
+    *material_config = config;
+    // 贴图的索引可能变了 已经生成的列全部刷新
+    for chunk_key in mesh_manager.columns.iter().copied() {
+        chunk_update_task.tasks.push(spawn_column_update(
+            chunk_map.as_ref(),
+            chunk_key,
+            WORLD_HEIGHT.chunk_ys().collect(),
+            false,
+        ));
+    }
+}
+
 pub fn mesh_chunk_map_setdown(
     mut commands: Commands,
     mut mesh_manager: ResMut<MeshManager>,
@@ -515,11 +1027,80 @@ pub fn mesh_chunk_map_setdown(
     chunk_update_task.tasks.drain(..);
     chunk_sync_task.tasks.drain(..);
     chunk_map.map_data.clear();
+    chunk_map.light_data.clear();
     for (_, entity) in mesh_manager.entities.clone() {
         commands.entity(entity).despawn();
     }
     for (_, entity) in mesh_manager.water_entities.clone() {
         commands.entity(entity).despawn();
     }
+    for (_, entity) in mesh_manager.translucent_entities.clone() {
+        commands.entity(entity).despawn();
+    }
+    for (_, entity) in mesh_manager.decoration_entities.clone() {
+        commands.entity(entity).despawn();
+    }
     *mesh_manager.as_mut() = MeshManager::default();
 }
+
+#[test]
+fn test_set_section_mesh() {
+    use bevy::{
+        app::App,
+        asset::{AddAsset, AssetPlugin},
+        ecs::system::CommandQueue,
+        prelude::{shape, Mut},
+    };
+
+    let mut app = App::new();
+    app.add_plugins(AssetPlugin::default()).add_asset::<Mesh>();
+    let key = ChunkKey(IVec3::ZERO, DimensionId(0));
+    let mut storge: HashMap<ChunkKey, Handle<Mesh>> = HashMap::new();
+    let mut entities: HashMap<ChunkKey, Entity> = HashMap::new();
+    let mesh = || Some(Mesh::from(shape::Cube::new(1.0)));
+    app.world
+        .resource_scope(|world, mut mesh_assets: Mut<Assets<Mesh>>| {
+            let mut queue = CommandQueue::default();
+            // 第一次出现的mesh 比如放下了这个区块中的第一棵草 要生成实体
+            let mut commands = Commands::new(&mut queue, world);
+            set_section_mesh(
+                &mut commands,
+                &mut mesh_assets,
+                &mut storge,
+                &mut entities,
+                key,
+                mesh(),
+                |commands, mesh| commands.spawn(mesh).id(),
+            );
+            assert!(storge.contains_key(&key));
+            let entity = entities[&key];
+            // 已经有实体时只替换mesh
+            set_section_mesh(
+                &mut commands,
+                &mut mesh_assets,
+                &mut storge,
+                &mut entities,
+                key,
+                mesh(),
+                |_, _| panic!("不应该重新生成实体"),
+            );
+            assert_eq!(entities[&key], entity);
+            queue.apply(world);
+            assert!(world.get_entity(entity).is_some());
+
+            // 没有了的mesh删除实体
+            let mut commands = Commands::new(&mut queue, world);
+            set_section_mesh(
+                &mut commands,
+                &mut mesh_assets,
+                &mut storge,
+                &mut entities,
+                key,
+                None,
+                |_, _| panic!("不应该生成实体"),
+            );
+            queue.apply(world);
+            assert!(storge.is_empty() && entities.is_empty());
+            assert!(world.get_entity(entity).is_none());
+        });
+}
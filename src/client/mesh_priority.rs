@@ -0,0 +1,169 @@
+// 区块加载和生成mesh的顺序
+// 离相机越近的列越先请求 解压和生成mesh 在视线前方的列再提前一些
+// 每一帧只处理一定数量的列 已经离开视野的列的任务直接取消
+
+use bevy::{
+    prelude::{GlobalTransform, Query, ResMut, Resource, Vec3, With},
+    tasks::Task,
+};
+
+use crate::{
+    common::Sphere3,
+    voxel_world::{chunk::ChunkKey, pos::ChunkPos},
+    CHUNK_SIZE,
+};
+
+use super::player::controller::CameraTag;
+
+// 每帧最多发送的区块请求
+pub const CHUNK_QUERY_PER_FRAME: usize = 8;
+// 每帧最多保存的解压好的区块
+pub const CHUNK_DATA_PER_FRAME: usize = 16;
+// 每帧最多开始计算的列
+pub const MESH_TASK_PER_FRAME: usize = 4;
+// 每帧最多生成mesh的列
+pub const MESH_RESULT_PER_FRAME: usize = 3;
+// 视线方向的影响 正前方的列距离缩短这个比例 正后方的列增加这个比例
+pub const VIEW_DIRECTION_WEIGHT: f32 = 0.3;
+
+// 相机的位置和朝向
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct MeshViewer {
+    pub position: Vec3,
+    pub forward: Vec3,
+}
+
+impl Default for MeshViewer {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            forward: Vec3::NEG_Z,
+        }
+    }
+}
+
+pub fn update_mesh_viewer(
+    query: Query<&GlobalTransform, With<CameraTag>>,
+    mut viewer: ResMut<MeshViewer>,
+) {
+    if let Ok(trf) = query.get_single() {
+        viewer.position = trf.translation();
+        viewer.forward = trf.forward();
+    }
+}
+
+/**
+ * 列的优先级 越小越先处理
+ * 只看水平方向 相机所在的列总是最先处理
+ */
+pub fn chunk_priority(viewer: &MeshViewer, chunk_key: ChunkKey) -> f32 {
+    let center =
+        ChunkPos::from(chunk_key).min_block().0.as_vec3() + Vec3::splat(CHUNK_SIZE as f32 / 2.0);
+    let mut offset = center - viewer.position;
+    offset.y = 0.0;
+    let mut forward = viewer.forward;
+    forward.y = 0.0;
+    let distance = offset.length();
+    let facing = match (offset.try_normalize(), forward.try_normalize()) {
+        (Some(offset), Some(forward)) => offset.dot(forward),
+        _ => 0.0,
+    };
+    distance * (1.0 - VIEW_DIRECTION_WEIGHT * facing)
+}
+
+pub fn sort_by_priority(viewer: &MeshViewer, keys: &mut [ChunkKey]) {
+    keys.sort_by(|a, b| chunk_priority(viewer, *a).total_cmp(&chunk_priority(viewer, *b)));
+}
+
+// 列是否还在视野中 margin 是视野外面多保留的列数
+pub fn column_in_view(sphere: &Sphere3, chunk_key: ChunkKey, margin: i32) -> bool {
+    let center = ChunkPos::from_vec3(sphere.center).0;
+    let chunk_distance = sphere.radius as i32 / CHUNK_SIZE + margin;
+    (chunk_key.0.x - center.x).abs() <= chunk_distance
+        && (chunk_key.0.z - center.z).abs() <= chunk_distance
+}
+
+pub fn poll_task<T>(task: &mut Task<T>) -> Option<T> {
+    futures_lite::future::block_on(futures_lite::future::poll_once(task))
+}
+
+/**
+ * 按照优先级处理完成了的任务 最多处理 budget 个
+ * 离开视野的任务直接丢掉(取消) 没有完成的任务留到下一帧
+ */
+pub fn drain_finished_tasks<T>(
+    viewer: &MeshViewer,
+    tasks: &mut Vec<(ChunkKey, Task<T>)>,
+    budget: usize,
+    mut keep: impl FnMut(ChunkKey) -> bool,
+) -> Vec<(ChunkKey, T)> {
+    tasks.retain(|(chunk_key, _)| keep(*chunk_key));
+    tasks.sort_by(|a, b| chunk_priority(viewer, a.0).total_cmp(&chunk_priority(viewer, b.0)));
+    let mut finished = Vec::new();
+    let mut pending = Vec::with_capacity(tasks.len());
+    for (chunk_key, mut task) in tasks.drain(..) {
+        if finished.len() >= budget {
+            pending.push((chunk_key, task));
+            continue;
+        }
+        match poll_task(&mut task) {
+            Some(result) => finished.push((chunk_key, result)),
+            None => pending.push((chunk_key, task)),
+        }
+    }
+    *tasks = pending;
+    finished
+}
+
+#[test]
+fn test_chunk_priority() {
+    use bevy::prelude::IVec3;
+    let viewer = MeshViewer {
+        position: Vec3::new(0.0, 40.0, 0.0),
+        forward: Vec3::new(0.0, -0.5, -1.0),
+    };
+    let key = |x, z| ChunkKey::new(IVec3::new(x, 0, z));
+    let mut keys = vec![key(0, 3), key(4, 0), key(0, -3), key(0, 0), key(1, 0)];
+    sort_by_priority(&viewer, &mut keys);
+    // 脚下的先处理 然后是近的 同样远时前方的先处理
+    assert_eq!(
+        keys,
+        vec![key(0, 0), key(1, 0), key(0, -3), key(0, 3), key(4, 0)]
+    );
+    // 往下看时不影响
+    let down = MeshViewer {
+        forward: Vec3::NEG_Y,
+        ..viewer
+    };
+    assert_eq!(
+        chunk_priority(&down, key(0, -3)),
+        chunk_priority(&down, key(0, 3))
+    );
+}
+
+#[test]
+fn test_column_in_view() {
+    use crate::voxel_world::dimension::DimensionId;
+    use bevy::prelude::IVec3;
+    let sphere = Sphere3 {
+        center: Vec3::new(20.0, 0.0, -20.0),
+        radius: 64.0,
+        dimension: DimensionId::OVERWORLD,
+    };
+    // 中心在列 (1, -1) 视野是4列
+    assert!(column_in_view(
+        &sphere,
+        ChunkKey::new(IVec3::new(5, 0, -5)),
+        0
+    ));
+    assert!(!column_in_view(
+        &sphere,
+        ChunkKey::new(IVec3::new(6, 0, 0)),
+        0
+    ));
+    assert!(column_in_view(
+        &sphere,
+        ChunkKey::new(IVec3::new(6, 0, 0)),
+        1
+    ));
+}
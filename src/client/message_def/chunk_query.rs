@@ -1,18 +1,18 @@
-use bevy::prelude::{Component, Vec3};
+use bevy::prelude::Component;
 use serde::{Deserialize, Serialize};
 
-use crate::voxel_world::{chunk::ChunkKey, voxel::Voxel};
+use crate::voxel_world::{chunk::ChunkKey, pos::BlockPos, voxel::Voxel};
 
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ChunkQuery {
     // 获取全部的ChunkKey 的数据
     GetFullY(ChunkKey),
-    // 更新某块数据
+    // 获取一列降采样之后的数据 用于远处的LOD 第二个值是倍数
+    GetLod(ChunkKey, u32),
+    // 更新某块数据 服务器根据玩家所在的维度找到区块
     Change {
-        chunk_key: ChunkKey,
-        pos: [u32; 3],
+        block: BlockPos,
         voxel_type: Voxel,
-        center: Vec3,
         active_index: Option<usize>,
     },
 }
@@ -9,4 +9,8 @@ pub enum UserCommandMessage {
         staff_id: usize,
         forward: Vec3,
     },
+    // 管理员命令 进入另一个维度
+    ChangeDimension {
+        name: String,
+    },
 }
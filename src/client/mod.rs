@@ -1,5 +1,5 @@
 use bevy::prelude::{
-    Assets, Commands, DespawnRecursiveExt, Entity, Mesh, Quat, Query, Res, ResMut,
+    Assets, Commands, DespawnRecursiveExt, Entity, EventWriter, Mesh, Quat, Query, Res, ResMut,
     StandardMaterial, Transform, Without,
 };
 use bevy_renet::renet::{transport::NetcodeClientTransport, RenetClient};
@@ -12,18 +12,24 @@ use crate::{
         },
         player::Player,
     },
+    staff::StaffSyncEvent,
 };
 
+use self::dimension::{CurrentDimension, DimensionChangeEvent};
 use self::player::{
     client_create_player,
     controller::{HeadTag, YawTag},
     ClientLobby,
 };
+use self::voxels::voxel_materail_config::MaterialSyncEvent;
 
 pub mod console_commands;
 pub mod debug;
+pub mod dimension;
 pub mod filled_object;
+pub mod lod_display;
 pub mod mesh_display;
+pub mod mesh_priority;
 pub mod message_def;
 pub mod player;
 pub mod ray_cast;
@@ -34,6 +40,7 @@ pub mod voxels;
 pub mod sp_mesh_display;
 
 // 同步创建或者删除角色
+#[allow(clippy::too_many_arguments)]
 pub fn client_sync_players(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -41,6 +48,10 @@ pub fn client_sync_players(
     mut client: ResMut<RenetClient>,
     transport: Res<NetcodeClientTransport>,
     mut lobby: ResMut<ClientLobby>,
+    mut current_dimension: ResMut<CurrentDimension>,
+    mut dimension_events: EventWriter<DimensionChangeEvent>,
+    mut staff_sync_events: EventWriter<StaffSyncEvent>,
+    mut material_sync_events: EventWriter<MaterialSyncEvent>,
 ) {
     let client_id = transport.client_id();
     while let Some(message) = client.receive_message(ServerChannel::ServerMessages) {
@@ -90,6 +101,27 @@ pub fn client_sync_players(
                     commands.entity(client_entity).despawn_recursive();
                 }
             }
+            ServerMessages::DimensionChange {
+                dimension,
+                name,
+                translation,
+            } => {
+                println!("进入维度{}|{:?} 位置{:?}", name, dimension, translation);
+                current_dimension.0 = dimension;
+                dimension_events.send(DimensionChangeEvent { dimension, name });
+            }
+            ServerMessages::StaffSync { configs } => {
+                staff_sync_events.send(StaffSyncEvent::Staff(configs));
+            }
+            ServerMessages::StaffRulesSync { rules } => {
+                staff_sync_events.send(StaffSyncEvent::Rules(rules));
+            }
+            ServerMessages::BlockDropsSync { drops } => {
+                staff_sync_events.send(StaffSyncEvent::Drops(drops));
+            }
+            ServerMessages::MaterialSync { config } => {
+                material_sync_events.send(MaterialSyncEvent(config));
+            }
         }
     }
 }
@@ -1,9 +1,10 @@
 use bevy::{
     prelude::{
-        in_state, warn, Event, EventReader, EventWriter, IVec3, Input, IntoSystemConfigs, KeyCode,
-        MouseButton, Plugin, Query, Res, ResMut, Resource, Transform, Update, Vec3,
+        in_state, warn, Event, EventReader, EventWriter, Input, IntoSystemConfigs, KeyCode,
+        MouseButton, Plugin, Query, Res, ResMut, Resource, Transform, Update,
     },
     time::{Time, Timer, TimerMode},
+    utils::Duration,
 };
 use bevy_renet::renet::RenetClient;
 
@@ -15,28 +16,27 @@ use crate::{
         ui::tool_bar::ToolBar,
     },
     server::player::Player,
-    tools::{vec3_to_chunk_key_any_xyz, zone::check_player_put_object_available},
-    voxel_world::{chunk::ChunkKey, chunk_map::ChunkMap, voxel::Voxel},
+    tools::zone::check_player_put_object_available,
+    voxel_world::{chunk_map::ChunkMap, dimension::DimensionId, pos::BlockPos, voxel::Voxel},
 };
 
 use super::controller::ControllerFlag;
 
+// 破坏方块最少需要的时间
+pub const MIN_BREAK_SECONDS: f32 = 0.1;
+
 // 破坏方块的计时器
 
 #[derive(Debug, Resource, Clone)]
 pub struct AttackTimer {
     pub pressed: bool,
     pub timer: Option<Timer>,
-    pub chunk_key: ChunkKey,
-    pub xyz: [u32; 3],
-    pub center: Vec3,
+    pub block: BlockPos,
 }
 
 #[derive(Debug, Event)]
 pub struct BrokeCubeEvent {
-    pub chunk_key: ChunkKey,
-    pub xyz: [u32; 3],
-    pub center: Vec3,
+    pub block: BlockPos,
 }
 
 // 处理时间相关
@@ -50,9 +50,7 @@ pub fn deal_attack_time(
         if timer.finished() {
             // 这处理完毕了 要删除物体了！
             broke_cube_event.send(BrokeCubeEvent {
-                chunk_key: attack_timer.chunk_key,
-                xyz: attack_timer.xyz,
-                center: attack_timer.center,
+                block: attack_timer.block,
             });
             attack_timer.timer = None;
         }
@@ -65,10 +63,8 @@ pub fn deal_broken_cube_event(
 ) {
     for event in broke_cube_event.iter() {
         let message = bincode::serialize(&ChunkQuery::Change {
-            chunk_key: event.chunk_key,
-            pos: event.xyz,
+            block: event.block,
             voxel_type: Voxel::EMPTY,
-            center: event.center,
             active_index: None,
         })
         .unwrap();
@@ -76,6 +72,18 @@ pub fn deal_broken_cube_event(
     }
 }
 
+// 破坏方块需要的时间 由方块的硬度决定 无法破坏的方块没有计时器
+fn break_timer(voxel: Voxel) -> Option<Timer> {
+    let block = voxel.property()?;
+    if !block.breakable {
+        return None;
+    }
+    Some(Timer::new(
+        Duration::from_secs_f32(block.hardness.max(MIN_BREAK_SECONDS)),
+        TimerMode::Once,
+    ))
+}
+
 //鼠标操作
 pub fn mouse_button_system(
     mouse_button_input: Res<Input<MouseButton>>,
@@ -98,16 +106,14 @@ pub fn mouse_button_system(
         && keyboard_input.pressed(KeyCode::ShiftLeft)
     {
         if let Some(pos) = choose_cube.center {
-            let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
-            if let Some(voxel_type) = chunk_map.get_block(chunk_key, xyz) {
+            let block = BlockPos::from_vec3(pos);
+            if let Some(voxel_type) = chunk_map.get_block_at(DimensionId::OVERWORLD, block) {
                 // FIXME: 这里要判断是否每种情况都可以去旋转!
                 let new_voxel = voxel_type.next_direction();
                 println!("这里发送了旋转方块的指令{:?}", new_voxel);
                 let message = bincode::serialize(&ChunkQuery::Change {
-                    chunk_key,
-                    pos: xyz,
+                    block,
                     voxel_type: new_voxel,
-                    center: pos,
                     active_index: None,
                 })
                 .unwrap();
@@ -121,33 +127,14 @@ pub fn mouse_button_system(
         // println!("4:{}", controller_flag.flag);
         // 破坏方块
         if let Some(pos) = choose_cube.center {
-            let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
-            // 判断计时器是否存在
-            let test_chunk_key = attack_timer.chunk_key;
-            let test_xyz = attack_timer.xyz;
-            if attack_timer.timer.is_some() {
-                // FIXME: 理论上不会走到这个分支
-                if test_chunk_key == chunk_key && test_xyz == xyz {
-                    // 和原来位置一样不处理
-                } else {
-                    // FIXME: 后续根据体素块 和 当前物体来判断 新的 timer
-                    attack_timer.timer = Some(Timer::new(
-                        bevy::utils::Duration::from_millis(1000 * 2),
-                        TimerMode::Once,
-                    ));
-                    attack_timer.chunk_key = chunk_key;
-                    attack_timer.xyz = xyz;
-                    attack_timer.center = pos;
-                }
-            } else {
-                attack_timer.chunk_key = chunk_key;
-                attack_timer.xyz = xyz;
-                attack_timer.center = pos;
-                // FIXME: 后续根据体素块 和 当前物体来判断
-                attack_timer.timer = Some(Timer::new(
-                    bevy::utils::Duration::from_millis(1000 * 2),
-                    TimerMode::Once,
-                ));
+            let block = BlockPos::from_vec3(pos);
+            // 判断计时器是否存在 和原来位置一样不处理
+            if attack_timer.timer.is_none() || attack_timer.block != block {
+                attack_timer.block = block;
+                // FIXME: 后续还要根据当前的工具来判断
+                attack_timer.timer = chunk_map
+                    .get_block_at(DimensionId::OVERWORLD, block)
+                    .and_then(break_timer);
             }
         } else {
             // 清空计时器
@@ -170,12 +157,9 @@ pub fn mouse_button_system(
                 // 判断当前这里是否和 其他的player的位置冲突
                 if check_player_put_object_available(pos.clone(), &player_query) {
                     // 还要发送当前生效的 tool_bar的 index
-                    let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos);
                     let message = bincode::serialize(&ChunkQuery::Change {
-                        chunk_key,
-                        pos: xyz,
+                        block: BlockPos::from_vec3(pos),
                         voxel_type,
-                        center: pos,
                         active_index: Some(tool_bar_data.active_index),
                     })
                     .unwrap();
@@ -198,9 +182,7 @@ impl Plugin for MouseControlPlugin {
         app.insert_resource(AttackTimer {
             pressed: false,
             timer: None,
-            chunk_key: ChunkKey(IVec3::ONE),
-            xyz: [0, 0, 0],
-            center: Vec3::ZERO,
+            block: BlockPos::default(),
         });
         app.add_systems(
             Update,
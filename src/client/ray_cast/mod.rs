@@ -12,7 +12,7 @@ use bevy_mod_raycast::{
     DefaultRaycastingPlugin, Ray3d,
 };
 
-use crate::{CLIENT_DEBUG, TOUCH_RADIUS};
+use crate::{voxel_world::pos::BlockPos, CLIENT_DEBUG, TOUCH_RADIUS};
 
 use self::choose_cube::{ChooseCube, HelpCube};
 
@@ -23,19 +23,9 @@ use super::{
 
 pub mod choose_cube;
 
-fn get_pos_chunk_center(vec3: Vec3, normal: Vec3) -> Vec3 {
-    // 应该是命中点所在的面的中点
-    let mid_pos = Vec3::new(
-        calc_point(vec3.x, normal.x),
-        calc_point(vec3.y, normal.y),
-        calc_point(vec3.z, normal.z),
-    );
-    mid_pos - (normal * 0.5)
-}
-
-fn calc_point(x: f32, normal: f32) -> f32 {
-    // 这里 使用四舍五入进行计算
-    ((x * 1000.0).round() / 1000.0).floor() + 0.5 * (if normal == 0.0 { 1.0 } else { 0.0 })
+// 命中点在方块的面上 沿着法线往里走半格就是被命中的方块
+fn get_hit_block(hit_point: Vec3, normal: Vec3) -> BlockPos {
+    BlockPos::from_vec3(hit_point - normal * 0.5)
 }
 
 #[derive(Reflect)]
@@ -164,8 +154,8 @@ pub fn touth_mesh_ray_cast(
             let center_point: Vec3;
             match mesh_data.0 {
                 super::mesh_display::HitMeshType::Common => {
-                    center_point = get_pos_chunk_center(hit_point, normal);
-                    let out_center_point = get_pos_chunk_center(hit_point, -normal);
+                    center_point = get_hit_block(hit_point, normal).center();
+                    let out_center_point = get_hit_block(hit_point, -normal).center();
                     gizmos.sphere(out_center_point, Quat::IDENTITY, 0.5, Color::GREEN);
                     choose_cube.out_center = Some(out_center_point);
                 }
@@ -173,6 +163,11 @@ pub fn touth_mesh_ray_cast(
                     center_point = center;
                     choose_cube.out_center = None;
                 }
+                super::mesh_display::HitMeshType::Decoration => {
+                    // 交叉面片在方块内部 命中点所在的格子就是方块
+                    center_point = BlockPos::from_vec3(hit_point).center();
+                    choose_cube.out_center = None;
+                }
             }
 
             gizmos.sphere(center_point, Quat::IDENTITY, 0.5, Color::DARK_GRAY);
@@ -13,18 +13,17 @@ use bevy::{
     tasks::{AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
-use ndshape::{ConstShape, ConstShape3u32};
 
 use crate::{
     common::ClipSpheres,
-    tools::chunk_key_any_xyz_to_vec3,
     voxel_world::{
         chunk::{find_chunk_keys_array_by_sphere, generate_offset_array, ChunkKey},
         chunk_map::ChunkMap,
-        voxel::{Voxel, VoxelDirection},
-        voxel_mesh::{VoxelMeshStorge, VOXEL_MESH_MAP},
+        pos::LocalPos,
+        voxel::Voxel,
+        voxel_mesh::{model_rotation, VoxelMeshStorge, VOX_SCALE},
     },
-    CHUNK_SIZE_U32, SP_MESH_DISTANCE,
+    SP_MESH_DISTANCE,
 };
 
 use super::{
@@ -89,7 +88,7 @@ fn sp_mesh_tasks_update(
                 }
             }
             for i in 0..voxels.len() {
-                if VOXEL_MESH_MAP.contains_key(&voxels[i].id) {
+                if voxels[i].has_model() {
                     if let Some(index_voxels_entity_map) =
                         sp_mesh_manager.entities.get_mut(&chunk_key)
                     {
@@ -100,7 +99,7 @@ fn sp_mesh_tasks_update(
                                 commands.entity(*entity).insert(get_sp_tfr(
                                     chunk_key.clone(),
                                     i as u32,
-                                    voxels[i].direction,
+                                    voxels[i],
                                 ));
                             }
                             // 否则什么也不处理
@@ -159,9 +158,9 @@ fn deal_sp_mesh_tasks(
             if let Some(meta) = voxel_mesh_storge.data.get(&v.id) {
                 let entity = commands
                     .spawn(PbrBundle {
-                        transform: get_sp_tfr(chunk_key.clone(), index as u32, v.direction),
-                        // FIXME:这里 暂时取第一个 后面要根据生成器取置换
-                        mesh: meta.vox_list[0].clone(),
+                        transform: get_sp_tfr(chunk_key.clone(), index as u32, v),
+                        // 模型文件修改后 handle 对应的mesh会自动更新
+                        mesh: meta.mesh.clone(),
                         material: stdmats.add(Color::rgb(1., 1., 1.).into()),
                         ..Default::default()
                     })
@@ -221,7 +220,7 @@ fn despawn_sp_mesh(
     }
 }
 
-fn unset_all(
+pub fn unset_all(
     mut commands: Commands,
     mut sp_mesh_tasks: ResMut<SpMeshTasks>,
     mut sp_mesh_manager: ResMut<SpMeshManager>,
@@ -239,18 +238,18 @@ fn unset_all(
     }
 }
 
-// 通过球体生成要删除的数据
-pub type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-
 fn get_pos(chunk_key: ChunkKey, index: u32) -> Vec3 {
-    let xyz = SampleShape::delinearize(index);
-    chunk_key_any_xyz_to_vec3(chunk_key, xyz)
+    chunk_key.block(LocalPos::from_index(index)).center()
 }
 
-pub fn get_sp_tfr(chunk_key: ChunkKey, index: u32, direction: VoxelDirection) -> Transform {
+// 特殊模型的位置 朝向按照方块配置中的 rotation
+pub fn get_sp_tfr(chunk_key: ChunkKey, index: u32, voxel: Voxel) -> Transform {
     Transform {
         translation: get_pos(chunk_key, index),
-        rotation: direction.to_quat(),
-        scale: Vec3::new(1. / 32., 1. / 32., 1. / 32.),
+        rotation: voxel
+            .model()
+            .map(|model| model_rotation(model, voxel.direction))
+            .unwrap_or_else(|| voxel.direction.to_quat()),
+        scale: Vec3::splat(VOX_SCALE),
     }
 }
@@ -22,7 +22,9 @@ use crate::{
     client::{
         client_sync_players, client_sync_players_state,
         console_commands::ConsoleCommandPlugins,
+        dimension::ClientDimensionPlugin,
         filled_object::{setdown_filled_object, ClientFilledObjectnPlugin},
+        lod_display::{lod_setdown, ClientLodPlugin},
         mesh_display::{mesh_chunk_map_setdown, ClientMeshPlugin},
         player::{
             controller::{CharacterController, CharacterControllerPlugin, ControllerFlag},
@@ -84,11 +86,13 @@ impl Plugin for GamePlugin {
             CharacterControllerPlugin,
             ClientClipSpheresPlugin::<CharacterController> { data: PhantomData },
             ClientMeshPlugin,
+            ClientLodPlugin,
             ClientSkyPlugins,
             MeshRayCastPlugin,
             ConsoleCommandPlugins,
             MouseControlPlugin,
             ClientFilledObjectnPlugin,
+            ClientDimensionPlugin,
             ToolBarSyncPlugin,
             SpMeshManagerPlugin,
         ));
@@ -119,7 +123,12 @@ impl Plugin for GamePlugin {
         );
         app.add_systems(
             OnExit(GameState::Game),
-            (setdown, mesh_chunk_map_setdown, setdown_filled_object),
+            (
+                setdown,
+                mesh_chunk_map_setdown,
+                lod_setdown,
+                setdown_filled_object,
+            ),
         );
     }
 }
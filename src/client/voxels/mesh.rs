@@ -4,115 +4,414 @@ use bevy::{
         mesh::{Indices, VertexAttributeValues},
         render_resource::PrimitiveTopology,
     },
+    utils::HashMap,
 };
-use block_mesh::{greedy_quads, GreedyQuadsBuffer, RIGHT_HANDED_Y_UP_CONFIG};
-use ndshape::{ConstShape, ConstShape3u32, Shape};
+use block_mesh::{
+    greedy_quads, GreedyQuadsBuffer, MergeVoxel, UnorientedQuad, Voxel as MeshVoxel,
+    VoxelVisibility, RIGHT_HANDED_Y_UP_CONFIG,
+};
+use ndshape::{ConstShape, ConstShape3u32, RuntimeShape, Shape};
 
 use crate::{
     client::voxels::mesh_material::ATTRIBUTE_DATA,
-    voxel_world::voxel::{Voxel, VoxelDirection, VoxelMaterial, Water},
-    CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32,
+    voxel_world::{
+        light::{emit_level, FULL_SKY_LIGHT},
+        lod::lod_shape,
+        voxel::{Voxel, VoxelDirection, VoxelMaterial, Water, WATER_SOURCE_LEVEL},
+    },
+    CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32,
 };
 
 use super::voxel_materail_config::MaterailConfiguration;
 
+// 一个区块生成mesh时的数据 每个方向多一层邻居 和 ChunkMap::get_neighbors 一致
+pub type SectionShape =
+    ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
+
+// 和 RIGHT_HANDED_Y_UP_CONFIG.faces 的顺序一致 -X -Y -Z +X +Y +Z
+const FACE_OFFSETS: [[i32; 3]; 6] = [
+    [-1, 0, 0],
+    [0, -1, 0],
+    [0, 0, -1],
+    [1, 0, 0],
+    [0, 1, 0],
+    [0, 0, 1],
+];
+
+// 生成方块mesh的阶段 实心方块和镂空方块一起生成 半透明方块每一种单独生成
+// 半透明方块和同一种方块之间的面不生成 和其他种类的半透明方块之间的面生成
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MeshPass {
+    Opaque,
+    Translucent(u8),
+}
+
+impl MeshPass {
+    // 这个阶段中方块的可见性 实心方块总是会挡住后面的面
+    fn visibility(&self, voxel: &Voxel) -> VoxelVisibility {
+        match (self, voxel.get_visibility()) {
+            (_, VoxelVisibility::Opaque) => VoxelVisibility::Opaque,
+            // 镂空方块在实心阶段中 贴图透明的部分由着色器丢弃
+            (MeshPass::Opaque, VoxelVisibility::Translucent) if voxel.is_cutout() => {
+                VoxelVisibility::Translucent
+            }
+            (MeshPass::Translucent(id), VoxelVisibility::Translucent) if voxel.id == *id => {
+                VoxelVisibility::Translucent
+            }
+            _ => VoxelVisibility::Empty,
+        }
+    }
+
+    // 这个阶段只保留自己的面 半透明阶段也会生成实心方块的面
+    fn keep(&self, visibility: VoxelVisibility) -> bool {
+        match self {
+            MeshPass::Opaque => visibility != VoxelVisibility::Empty,
+            MeshPass::Translucent(_) => visibility == VoxelVisibility::Translucent,
+        }
+    }
+
+    // 顶点数据的第26位 着色器中保留贴图的透明度
+    fn data_flag(&self) -> u32 {
+        match self {
+            MeshPass::Opaque => 0,
+            MeshPass::Translucent(_) => 1 << 26u32,
+        }
+    }
+}
+
+// 生成mesh时使用的体素 带上六个面外面的光照 每个面一个字节
+// 以及每个面四个角的环境光遮蔽 每个角两位 光照或者遮蔽不同的面不会合并
+#[derive(Debug, Clone, Copy)]
+struct LitVoxel {
+    voxel: Voxel,
+    visibility: VoxelVisibility,
+    light: u64,
+    occlusion: u64,
+}
+
+impl MeshVoxel for LitVoxel {
+    fn get_visibility(&self) -> VoxelVisibility {
+        self.visibility
+    }
+}
+
+impl MergeVoxel for LitVoxel {
+    type MergeValue = (u8, u64, u64);
+
+    fn merge_value(&self) -> Self::MergeValue {
+        (self.voxel.merge_value(), self.light, self.occlusion)
+    }
+}
+
+impl LitVoxel {
+    fn face_light(&self, face_index: usize) -> u8 {
+        (self.light >> (8 * face_index)) as u8
+    }
+
+    /**
+     * 面上一个顶点的遮蔽 0是没有遮挡 3是最暗
+     * 按照顶点在 quad 的哪一头找到对应的角 合并了的面每一格的遮蔽都一样
+     */
+    fn corner_occlusion(&self, face_index: usize, position: [f32; 3], minimum: [u32; 3]) -> u8 {
+        let [a, b] = face_tangents(face_index);
+        let corner = (position[a] > minimum[a] as f32) as usize
+            + 2 * (position[b] > minimum[b] as f32) as usize;
+        (self.occlusion >> (8 * face_index + 2 * corner)) as u8 & 3
+    }
+}
+
+// 面所在平面的两个轴
+fn face_tangents(face_index: usize) -> [usize; 2] {
+    match face_index % 3 {
+        0 => [1, 2],
+        1 => [0, 2],
+        _ => [0, 1],
+    }
+}
+
+// 顶点旁边的两个侧面和一个角都是实心的时候最暗
+fn vertex_occlusion(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        3
+    } else {
+        side1 as u8 + side2 as u8 + corner as u8
+    }
+}
+
+/**
+ * 四个角的遮蔽不一样时 沿着亮的两个角的对角线切分 否则暗的部分会随着方向改变
+ * indices 是两个三角形 它们共用的两个顶点是现在的对角线
+ */
+fn orient_quad(indices: [u32; 6], start: u32, occlusions: [u8; 4]) -> [u32; 6] {
+    let (first, second) = indices.split_at(3);
+    let Some(offset) = (0..3).find(|i| !second.contains(&first[*i])) else {
+        return indices;
+    };
+    let p = first[offset];
+    let s1 = first[(offset + 1) % 3];
+    let s2 = first[(offset + 2) % 3];
+    let Some(&q) = second.iter().find(|i| !first.contains(i)) else {
+        return indices;
+    };
+    let occlusion = |i: u32| occlusions[(i - start) as usize];
+    if occlusion(p) + occlusion(q) < occlusion(s1) + occlusion(s2) {
+        [p, s1, q, q, s2, p]
+    } else {
+        indices
+    }
+}
+
+// 取每个面外面那个位置的光照 没有光照数据时当作露天 发光的方块自己是亮的
+// 同时计算露出来的面四个角的环境光遮蔽 形状外面当作空气
+fn light_voxels<S: Shape<3, Coord = u32>>(
+    voxels: &[Voxel],
+    lights: Option<&[u8]>,
+    voxels_shape: &S,
+    pass: MeshPass,
+) -> Vec<LitVoxel> {
+    let size = voxels_shape.as_array();
+    let opaque = |p: [i32; 3]| {
+        (0..3).all(|i| p[i] >= 0 && p[i] < size[i] as i32)
+            && matches!(
+                voxels[voxels_shape.linearize(p.map(|v| v as u32)) as usize].get_visibility(),
+                VoxelVisibility::Opaque
+            )
+    };
+    voxels
+        .iter()
+        .enumerate()
+        .map(|(index, voxel)| {
+            let mut light = 0u64;
+            let mut occlusion = 0u64;
+            let visibility = pass.visibility(voxel);
+            if visibility != VoxelVisibility::Empty {
+                let p = voxels_shape.delinearize(index as u32);
+                let emit = emit_level(*voxel);
+                for (face_index, offset) in FACE_OFFSETS.iter().enumerate() {
+                    let n = [0, 1, 2].map(|i| p[i] as i32 + offset[i]);
+                    let mut face_light = FULL_SKY_LIGHT;
+                    if let Some(lights) = lights {
+                        if (0..3).all(|i| n[i] >= 0 && n[i] < size[i] as i32) {
+                            let n = n.map(|v| v as u32);
+                            face_light = lights[voxels_shape.linearize(n) as usize];
+                        }
+                    }
+                    face_light = (face_light & 0xF0) | (face_light & 0x0F).max(emit);
+                    light |= (face_light as u64) << (8 * face_index);
+                    // 被挡住的面不会生成
+                    if opaque(n) {
+                        continue;
+                    }
+                    let [a, b] = face_tangents(face_index);
+                    for corner in 0..4 {
+                        let mut side1 = n;
+                        side1[a] += if corner & 1 == 1 { 1 } else { -1 };
+                        let mut side2 = n;
+                        side2[b] += if corner & 2 == 2 { 1 } else { -1 };
+                        let mut diagonal = side1;
+                        diagonal[b] = side2[b];
+                        let value =
+                            vertex_occlusion(opaque(side1), opaque(side2), opaque(diagonal));
+                        occlusion |= (value as u64) << (8 * face_index + 2 * corner);
+                    }
+                }
+            }
+            LitVoxel {
+                voxel: *voxel,
+                visibility,
+                light,
+                occlusion,
+            }
+        })
+        .collect()
+}
+
+/**
+ * 生成方块的mesh
+ * lights: 和 voxels 形状一样的光照数据 没有时当作露天
+ */
 pub fn gen_mesh_volex<S>(
     voxels: Vec<Voxel>,
+    lights: Option<&[u8]>,
     material_config: MaterailConfiguration,
     voxels_shape: &S,
     max: [u32; 3],
-    mut deal_vec: impl FnMut(Vec<[f32; 3]>) -> Vec<[f32; 3]>,
+    deal_vec: impl FnMut(Vec<[f32; 3]>) -> Vec<[f32; 3]>,
 ) -> Option<Mesh>
 where
-    S: Shape<3, Coord = u32> + ConstShape<3, Coord = u32>,
+    S: Shape<3, Coord = u32>,
 {
-    let mut buffer = GreedyQuadsBuffer::new(S::SIZE as usize);
-    let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
-    greedy_quads(&voxels, voxels_shape, [0; 3], max, &faces, &mut buffer);
-    let num_indices = buffer.quads.num_quads() * 6;
-    let num_vertices = buffer.quads.num_quads() * 4;
-    if num_indices == 0 {
+    gen_mesh_passes(
+        voxels,
+        lights,
+        material_config,
+        voxels_shape,
+        max,
+        deal_vec,
+        &[MeshPass::Opaque],
+    )
+}
+
+/**
+ * 生成半透明方块的mesh 和 gen_mesh 一样是一个区块带着一圈邻居
+ * 每一种半透明方块单独计算 不同种类的方块之间的面都会生成
+ */
+pub fn gen_mesh_translucent(
+    voxels: Vec<Voxel>,
+    lights: &[u8],
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    let mut ids: Vec<u8> = voxels
+        .iter()
+        .filter(|voxel| voxel.is_translucent())
+        .map(|voxel| voxel.id)
+        .collect();
+    ids.sort();
+    ids.dedup();
+    if ids.is_empty() {
         return None;
     }
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-    let mut tex_coords = Vec::with_capacity(num_vertices);
-    let mut data = Vec::with_capacity(num_vertices);
+    let passes: Vec<MeshPass> = ids.into_iter().map(MeshPass::Translucent).collect();
+    gen_mesh_passes(
+        voxels,
+        Some(lights),
+        material_config,
+        &SectionShape {},
+        [CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1],
+        |a| a,
+        &passes,
+    )
+}
 
-    for (block_face_normal_index, (group, face)) in buffer
-        .quads
-        .groups
-        .as_ref()
-        .iter()
-        .zip(faces.into_iter())
-        .enumerate()
-    {
-        for quad in group.iter() {
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&face.quad_mesh_positions(quad, 1.0));
-            normals.extend_from_slice(&face.quad_mesh_normals());
-            // 这里可以生成Data 但是怎么知道 是那个面的？
-            let index = <S as ConstShape<3>>::linearize(quad.minimum);
-
-            // 这里处理一下问题
-            if block_face_normal_index == 1 || block_face_normal_index == 4 {
-                match voxels[index as usize].direction.clone() {
-                    VoxelDirection::Z => {
-                        tex_coords.extend_from_slice(&[
-                            [0.0, quad.height as f32],
-                            [quad.width as f32, quad.height as f32],
-                            [0.0, 0.0],
-                            [quad.width as f32, 0.0],
-                        ]);
-                    }
-                    VoxelDirection::X => {
-                        tex_coords.extend_from_slice(&[
-                            [quad.width as f32, quad.height as f32],
-                            [quad.width as f32, 0.0],
-                            [0.0, quad.height as f32],
-                            [0.0, 0.0],
-                        ]);
-                    }
-                    VoxelDirection::NZ => {
-                        tex_coords.extend_from_slice(&[
-                            [quad.width as f32, 0.0],
-                            [0.0, 0.0],
-                            [quad.width as f32, quad.height as f32],
-                            [0.0, quad.height as f32],
-                        ]);
-                    }
-                    VoxelDirection::NX => {
-                        tex_coords.extend_from_slice(&[
-                            [0.0, 0.0],
-                            [0.0, quad.height as f32],
-                            [quad.width as f32, 0.0],
-                            [quad.width as f32, quad.height as f32],
-                        ]);
-                    }
+fn gen_mesh_passes<S>(
+    voxels: Vec<Voxel>,
+    lights: Option<&[u8]>,
+    material_config: MaterailConfiguration,
+    voxels_shape: &S,
+    max: [u32; 3],
+    mut deal_vec: impl FnMut(Vec<[f32; 3]>) -> Vec<[f32; 3]>,
+    passes: &[MeshPass],
+) -> Option<Mesh>
+where
+    S: Shape<3, Coord = u32>,
+{
+    let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    let mut indices = Vec::new();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut data = Vec::new();
+
+    for pass in passes {
+        let mut buffer = GreedyQuadsBuffer::new(voxels_shape.size() as usize);
+        let lit_voxels = light_voxels(&voxels, lights, voxels_shape, *pass);
+        greedy_quads(&lit_voxels, voxels_shape, [0; 3], max, &faces, &mut buffer);
+
+        for (block_face_normal_index, (group, face)) in buffer
+            .quads
+            .groups
+            .as_ref()
+            .iter()
+            .zip(faces.into_iter())
+            .enumerate()
+        {
+            for quad in group.iter() {
+                // 这里可以生成Data 但是怎么知道 是那个面的？
+                let index = voxels_shape.linearize(quad.minimum);
+                if !pass.keep(lit_voxels[index as usize].visibility) {
+                    continue;
                 }
-            } else {
-                tex_coords.extend_from_slice(&face.tex_coords(
-                    RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
-                    true,
-                    quad,
+                let start = positions.len() as u32;
+                let quad_positions = face.quad_mesh_positions(quad, 1.0);
+                // 四个顶点的环境光遮蔽
+                let occlusions = quad_positions.map(|position| {
+                    lit_voxels[index as usize].corner_occlusion(
+                        block_face_normal_index,
+                        position,
+                        quad.minimum,
+                    )
+                });
+                indices.extend_from_slice(&orient_quad(
+                    face.quad_mesh_indices(start),
+                    start,
+                    occlusions,
                 ));
-            }
+                positions.extend_from_slice(&quad_positions);
+                normals.extend_from_slice(&face.quad_mesh_normals());
 
-            // 法向量值
-            let normol_num = (block_face_normal_index as u32) << 8u32;
-            // 计算贴图索引
-            let txt_index = MaterailConfiguration::find_volex_index(
-                material_config.clone(),
-                block_face_normal_index as u8,
-                &voxels[index as usize].id,
-                voxels[index as usize].direction.clone(),
-            );
+                // 这里处理一下问题
+                if block_face_normal_index == 1 || block_face_normal_index == 4 {
+                    match voxels[index as usize].direction.clone() {
+                        VoxelDirection::Z => {
+                            tex_coords.extend_from_slice(&[
+                                [0.0, quad.height as f32],
+                                [quad.width as f32, quad.height as f32],
+                                [0.0, 0.0],
+                                [quad.width as f32, 0.0],
+                            ]);
+                        }
+                        VoxelDirection::X => {
+                            tex_coords.extend_from_slice(&[
+                                [quad.width as f32, quad.height as f32],
+                                [quad.width as f32, 0.0],
+                                [0.0, quad.height as f32],
+                                [0.0, 0.0],
+                            ]);
+                        }
+                        VoxelDirection::NZ => {
+                            tex_coords.extend_from_slice(&[
+                                [quad.width as f32, 0.0],
+                                [0.0, 0.0],
+                                [quad.width as f32, quad.height as f32],
+                                [0.0, quad.height as f32],
+                            ]);
+                        }
+                        VoxelDirection::NX => {
+                            tex_coords.extend_from_slice(&[
+                                [0.0, 0.0],
+                                [0.0, quad.height as f32],
+                                [quad.width as f32, 0.0],
+                                [quad.width as f32, quad.height as f32],
+                            ]);
+                        }
+                    }
+                } else {
+                    tex_coords.extend_from_slice(&face.tex_coords(
+                        RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
+                        true,
+                        quad,
+                    ));
+                }
 
-            //  这里后面要知道是那个面的方便渲染
-            data.extend_from_slice(&[normol_num | (txt_index); 4]);
+                // 法向量值
+                let normol_num = (block_face_normal_index as u32) << 8u32;
+                // 计算贴图索引
+                let txt_index = MaterailConfiguration::find_volex_index(
+                    material_config.clone(),
+                    block_face_normal_index as u8,
+                    &voxels[index as usize].id,
+                    voxels[index as usize].direction.clone(),
+                );
+
+                // 光照值
+                let light_num = (lit_voxels[index as usize].face_light(block_face_normal_index)
+                    as u32)
+                    << 16u32;
+
+                //  这里后面要知道是那个面的方便渲染 24-25位是顶点的环境光遮蔽
+                data.extend(occlusions.map(|occlusion| {
+                    (occlusion as u32) << 24u32
+                        | pass.data_flag()
+                        | light_num
+                        | normol_num
+                        | txt_index
+                }));
+            }
         }
     }
+    if indices.is_empty() {
+        return None;
+    }
 
     let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
@@ -125,6 +424,34 @@ where
     Some(render_mesh)
 }
 
+/**
+ * 生成远处LOD的mesh voxels 是 lod_shape(factor) 形状的降采样数据
+ * 四周和上下补一圈空气 列的边上都会生成侧面 挡住和旁边不同精度的列之间的缝隙
+ * 一个格子的大小是1 显示时再按倍数缩放
+ */
+pub fn gen_mesh_lod(
+    voxels: &[Voxel],
+    factor: u32,
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    let shape = lod_shape(factor);
+    let [x, y, z] = shape.as_array();
+    let padded_shape = RuntimeShape::<u32, 3>::new([x + 2, y + 2, z + 2]);
+    let mut padded = vec![Voxel::EMPTY; padded_shape.size() as usize];
+    for (index, voxel) in voxels.iter().enumerate() {
+        let [px, py, pz] = shape.delinearize(index as u32);
+        padded[padded_shape.linearize([px + 1, py + 1, pz + 1]) as usize] = *voxel;
+    }
+    gen_mesh_volex(
+        padded,
+        None,
+        material_config,
+        &padded_shape,
+        [x + 1, y + 1, z + 1],
+        |a| a,
+    )
+}
+
 pub fn gen_one_volex_mesh(voxel: Voxel, material_config: MaterailConfiguration) -> Option<Mesh> {
     type Tmp = ConstShape3u32<3, 3, 3>;
     let mut voxels = Vec::new();
@@ -139,78 +466,173 @@ pub fn gen_one_volex_mesh(voxel: Voxel, material_config: MaterailConfiguration)
             }
         }
     }
-    return gen_mesh_volex::<Tmp>(voxels, material_config, &Tmp {}, [2, 2, 2], |list| {
+    return gen_mesh_volex::<Tmp>(voxels, None, material_config, &Tmp {}, [2, 2, 2], |list| {
         list.iter()
             .map(|a| [a[0] - 1.5, a[1] - 1.5, a[2] - 1.5])
             .collect()
     });
 }
 
-pub fn gen_mesh(voxels: Vec<Voxel>, material_config: MaterailConfiguration) -> Option<Mesh> {
-    type Tmp = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, 256, CHUNK_SIZE_ADD_2_U32>;
-    return gen_mesh_volex::<Tmp>(
+pub fn gen_mesh(
+    voxels: Vec<Voxel>,
+    lights: &[u8],
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    return gen_mesh_volex(
         voxels,
+        Some(lights),
         material_config,
-        &Tmp {},
-        [(CHUNK_SIZE + 1) as u32, 255, (CHUNK_SIZE + 1) as u32],
+        &SectionShape {},
+        [CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1],
         |a| a,
     );
 }
 
+// 生成水的mesh 时使用的单元格 里面是水位 0表示没有水
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaterCell(pub u8);
+
+impl MeshVoxel for WaterCell {
+    fn get_visibility(&self) -> VoxelVisibility {
+        if self.0 > 0 {
+            VoxelVisibility::Opaque
+        } else {
+            VoxelVisibility::Empty
+        }
+    }
+}
+
+// 水面在单元格中的高度
+fn water_height(level: u8) -> f32 {
+    level.min(WATER_SOURCE_LEVEL) as f32 / WATER_SOURCE_LEVEL as f32
+}
+
+/**
+ * 相邻的两个水单元格之间 greedy_quads 不会生成面
+ * 水位不同时 高的一侧要补上从低的水面到高的水面的一段侧面 不然会看到缝隙
+ * 返回 面的序号 单元格 侧面的下边缘和上边缘
+ */
+fn water_steps(voxels: &[WaterCell]) -> Vec<(usize, UnorientedQuad, f32, f32)> {
+    let mut ret = Vec::new();
+    for x in 1..=CHUNK_SIZE_U32 {
+        for y in 1..=CHUNK_SIZE_U32 {
+            for z in 1..=CHUNK_SIZE_U32 {
+                let level = voxels[SectionShape::linearize([x, y, z]) as usize].0;
+                if level == 0 {
+                    continue;
+                }
+                for (face_index, offset) in FACE_OFFSETS.iter().enumerate() {
+                    // 只有水平方向的面
+                    if offset[1] != 0 {
+                        continue;
+                    }
+                    let n = [
+                        (x as i32 + offset[0]) as u32,
+                        y,
+                        (z as i32 + offset[2]) as u32,
+                    ];
+                    let side = voxels[SectionShape::linearize(n) as usize].0;
+                    if side == 0 || side >= level {
+                        continue;
+                    }
+                    ret.push((
+                        face_index,
+                        UnorientedQuad {
+                            minimum: [x, y, z],
+                            width: 1,
+                            height: 1,
+                        },
+                        water_height(side),
+                        water_height(level),
+                    ));
+                }
+            }
+        }
+    }
+    ret
+}
+
+impl MergeVoxel for WaterCell {
+    type MergeValue = u8;
+
+    fn merge_value(&self) -> Self::MergeValue {
+        self.0
+    }
+}
+
 // 生成水的mesh
-pub fn gen_mesh_water(voxels: Vec<Voxel>, material_config: MaterailConfiguration) -> Option<Mesh> {
-    type SampleShape = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, 256, CHUNK_SIZE_ADD_2_U32>;
-    let mut buffer = GreedyQuadsBuffer::new(SampleShape::SIZE as usize);
+pub fn gen_mesh_water(
+    voxels: Vec<WaterCell>,
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    let mut buffer = GreedyQuadsBuffer::new(SectionShape::SIZE as usize);
     let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
     // let water_voxels = pick_water(voxels);
     greedy_quads(
         &voxels,
-        &SampleShape {},
+        &SectionShape {},
         [0; 3],
-        [(CHUNK_SIZE + 1) as u32, 255, (CHUNK_SIZE + 1) as u32],
+        [CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1, CHUNK_SIZE_U32 + 1],
         &faces,
         &mut buffer,
     );
-    let num_indices = buffer.quads.num_quads() * 6;
-    let num_vertices = buffer.quads.num_quads() * 4;
-    if num_indices == 0 {
+    let steps = water_steps(&voxels);
+    let num_quads = buffer.quads.num_quads() + steps.len();
+    if num_quads == 0 {
         return None;
     }
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-    let mut tex_coords = Vec::with_capacity(num_vertices);
-    let mut data = Vec::with_capacity(num_vertices);
+    let mut indices = Vec::with_capacity(num_quads * 6);
+    let mut positions = Vec::with_capacity(num_quads * 4);
+    let mut normals = Vec::with_capacity(num_quads * 4);
+    let mut tex_coords = Vec::with_capacity(num_quads * 4);
+    let mut data = Vec::with_capacity(num_quads * 4);
+    // heights 是面的下边缘和上边缘在单元格中的高度 没有时使用原来的面
+    let mut push_quad = |face_index: usize, quad: &UnorientedQuad, heights: Option<(f32, f32)>| {
+        let face = &faces[face_index];
+        indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
+        let mut quad_positions = face.quad_mesh_positions(quad, 1.0);
+        if let Some((bottom, top)) = heights {
+            let min_y = quad.minimum[1] as f32;
+            for position in quad_positions.iter_mut() {
+                position[1] = if position[1] > min_y {
+                    min_y + top
+                } else {
+                    min_y + bottom
+                };
+            }
+        }
+        positions.extend_from_slice(&quad_positions);
+        normals.extend_from_slice(&face.quad_mesh_normals());
+        tex_coords.extend_from_slice(&face.tex_coords(
+            RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
+            true,
+            quad,
+        ));
+        // 法向量值
+        let normol_num = (face_index as u32) << 8u32;
+        // 贴图索引
+        let txt_index = MaterailConfiguration::find_volex_index(
+            material_config.clone(),
+            face_index as u8,
+            &Water::ID,
+            VoxelDirection::Z,
+        );
+        data.extend_from_slice(&[normol_num | (txt_index); 4]);
+    };
 
-    for (block_face_normal_index, (group, face)) in buffer
-        .quads
-        .groups
-        .as_ref()
-        .iter()
-        .zip(faces.into_iter())
-        .enumerate()
-    {
+    for (block_face_normal_index, group) in buffer.quads.groups.as_ref().iter().enumerate() {
         for quad in group.iter() {
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&face.quad_mesh_positions(quad, 1.0));
-            normals.extend_from_slice(&face.quad_mesh_normals());
-            tex_coords.extend_from_slice(&face.tex_coords(
-                RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
-                true,
-                quad,
-            ));
-            // 法向量值
-            let normol_num = (block_face_normal_index as u32) << 8u32;
-            // 贴图索引
-            let txt_index = MaterailConfiguration::find_volex_index(
-                material_config.clone(),
-                block_face_normal_index as u8,
-                &Water::ID,
-                VoxelDirection::Z,
-            );
-            data.extend_from_slice(&[normol_num | (txt_index); 4]);
+            // 没有满的水 把面的上边缘降低到水位的高度
+            // 上面有水的单元格都当作满的 所以不满的面高度一定是1
+            let level = voxels[SectionShape::linearize(quad.minimum) as usize].0;
+            let heights = (level < WATER_SOURCE_LEVEL).then(|| (0.0, water_height(level)));
+            push_quad(block_face_normal_index, quad, heights);
         }
     }
+    // 水和水之间的面不会生成 水位高的一侧补上高出来的那一段
+    for (face_index, quad, bottom, top) in steps {
+        push_quad(face_index, &quad, Some((bottom, top)));
+    }
 
     let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
 
@@ -222,15 +644,183 @@ pub fn gen_mesh_water(voxels: Vec<Voxel>, material_config: MaterailConfiguration
     Some(render_mesh)
 }
 
+// 生成装饰方块的mesh 每个装饰方块是两个交叉的面片
+pub fn gen_mesh_decoration(
+    voxels: &Vec<Voxel>,
+    lights: &[u8],
+    material_config: MaterailConfiguration,
+) -> Option<Mesh> {
+    let mut indices = Vec::new();
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut data = Vec::new();
+    // 贴图索引的缓存
+    let mut txt_map: HashMap<u8, u32> = HashMap::new();
+    // 使用朝上的法向量 这样光照和地面一致
+    let normol_num = 4u32 << 8u32;
+
+    for index in 0..SectionShape::SIZE {
+        let voxel = voxels[index as usize];
+        if !voxel.is_decoration() {
+            continue;
+        }
+        let [x, y, z] = SectionShape::delinearize(index);
+        // 只处理当前区块 不处理邻居
+        if [x, y, z].iter().any(|v| *v == 0 || *v > CHUNK_SIZE_U32) {
+            continue;
+        }
+        let txt_index = *txt_map.entry(voxel.id).or_insert_with(|| {
+            MaterailConfiguration::find_volex_index(
+                material_config.clone(),
+                0,
+                &voxel.id,
+                VoxelDirection::Z,
+            )
+        });
+        // 装饰方块不挡光 直接使用自己位置的光照
+        let light_num = (lights[index as usize] as u32) << 16u32;
+        let [x, y, z] = [x as f32, y as f32, z as f32];
+        for [(x0, z0), (x1, z1)] in [[(x, z), (x + 1.0, z + 1.0)], [(x + 1.0, z), (x, z + 1.0)]] {
+            // 正反两面
+            for back in [false, true] {
+                let start = positions.len() as u32;
+                positions.extend_from_slice(&[
+                    [x0, y, z0],
+                    [x1, y, z1],
+                    [x0, y + 1.0, z0],
+                    [x1, y + 1.0, z1],
+                ]);
+                normals.extend_from_slice(&[[0.0, 1.0, 0.0]; 4]);
+                tex_coords.extend_from_slice(&[[0.0, 1.0], [1.0, 1.0], [0.0, 0.0], [1.0, 0.0]]);
+                data.extend_from_slice(&[light_num | normol_num | txt_index; 4]);
+                if back {
+                    indices.extend_from_slice(&[
+                        start,
+                        start + 2,
+                        start + 1,
+                        start + 1,
+                        start + 2,
+                        start + 3,
+                    ]);
+                } else {
+                    indices.extend_from_slice(&[
+                        start,
+                        start + 1,
+                        start + 2,
+                        start + 2,
+                        start + 1,
+                        start + 3,
+                    ]);
+                }
+            }
+        }
+    }
+    if indices.is_empty() {
+        return None;
+    }
+
+    let mut render_mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    render_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    render_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    render_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, tex_coords);
+    render_mesh.insert_attribute(ATTRIBUTE_DATA, VertexAttributeValues::Uint32(data));
+    render_mesh.set_indices(Some(Indices::U32(indices)));
+    Some(render_mesh)
+}
+
 // 把水单元格转成 其他
-pub fn pick_water(voxels: Vec<Voxel>) -> Vec<Voxel> {
-    let mut ret = Vec::new();
-    for v in voxels {
-        if v.id == Water::ID {
-            ret.push(Voxel::FILLED);
+pub fn pick_water(voxels: Vec<Voxel>) -> Vec<WaterCell> {
+    let mut ret = Vec::with_capacity(voxels.len());
+    for (index, v) in voxels.iter().enumerate() {
+        let level = v.water_level();
+        if level == 0 {
+            ret.push(WaterCell(0));
+            continue;
+        }
+        // 上面还有水的时候是满的
+        let [x, y, z] = SectionShape::delinearize(index as u32);
+        if y + 1 < CHUNK_SIZE_ADD_2_U32
+            && voxels[SectionShape::linearize([x, y + 1, z]) as usize].is_water()
+        {
+            ret.push(WaterCell(WATER_SOURCE_LEVEL));
         } else {
-            ret.push(Voxel::EMPTY);
+            ret.push(WaterCell(level));
         }
     }
     ret
 }
+
+#[test]
+fn test_ambient_occlusion() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    let shape = RuntimeShape::<u32, 3>::new([3, 3, 3]);
+    let mut voxels = vec![Voxel::EMPTY; shape.size() as usize];
+    // 中间下面一个方块 它的上面的 +X 一侧还有一个方块
+    voxels[shape.linearize([1, 0, 1]) as usize] = Stone::into_voxel();
+    voxels[shape.linearize([2, 1, 1]) as usize] = Stone::into_voxel();
+    let lit = light_voxels(&voxels, None, &shape, MeshPass::Opaque);
+    let top = lit[shape.linearize([1, 0, 1]) as usize];
+    // 上面的面 靠近 +X 的两个角被挡住一个侧面
+    assert_eq!(top.corner_occlusion(4, [1.0, 1.0, 1.0], [1, 0, 1]), 0);
+    assert_eq!(top.corner_occlusion(4, [2.0, 1.0, 1.0], [1, 0, 1]), 1);
+    assert_eq!(top.corner_occlusion(4, [2.0, 1.0, 2.0], [1, 0, 1]), 1);
+    assert_eq!(top.corner_occlusion(4, [1.0, 1.0, 2.0], [1, 0, 1]), 0);
+    // 挡住的角在现在的对角线上时换一条对角线
+    let indices = [0, 1, 2, 1, 3, 2];
+    assert_eq!(orient_quad(indices, 0, [1, 0, 0, 0]), indices);
+    assert_eq!(orient_quad(indices, 0, [0, 1, 0, 0]), [0, 1, 3, 3, 2, 0]);
+    assert_eq!(orient_quad(indices, 0, [1, 1, 1, 1]), indices);
+}
+
+#[test]
+fn test_translucent_faces() {
+    use crate::voxel_world::voxel::{Glass, Ice, Stone, VoxelMaterial};
+    let shape = SectionShape {};
+    let mut voxels = vec![Voxel::EMPTY; SectionShape::SIZE as usize];
+    // 两个玻璃挨着一个冰 左边的玻璃上面是石头
+    voxels[shape.linearize([1, 1, 1]) as usize] = Glass::into_voxel();
+    voxels[shape.linearize([2, 1, 1]) as usize] = Glass::into_voxel();
+    voxels[shape.linearize([3, 1, 1]) as usize] = Ice::into_voxel();
+    voxels[shape.linearize([1, 2, 1]) as usize] = Stone::into_voxel();
+    let lights = vec![FULL_SKY_LIGHT; SectionShape::SIZE as usize];
+    let config = MaterailConfiguration::default();
+    // 石头的六个面都会生成 下面的玻璃不会挡住它
+    let opaque = gen_mesh(voxels.clone(), &lights, config.clone()).unwrap();
+    assert_eq!(opaque.count_vertices(), 6 * 4);
+    // 玻璃之间没有面 被石头挡住的面也没有 玻璃和冰之间两边的面都有
+    let translucent = gen_mesh_translucent(voxels, &lights, config).unwrap();
+    assert_eq!(translucent.count_vertices(), (6 + 6) * 4);
+}
+
+#[test]
+fn test_water_steps() {
+    let shape = SectionShape {};
+    let mut cells = vec![WaterCell(0); SectionShape::SIZE as usize];
+    // 满的水旁边是水位 3 的水
+    cells[shape.linearize([1, 1, 1]) as usize] = WaterCell(WATER_SOURCE_LEVEL);
+    cells[shape.linearize([2, 1, 1]) as usize] = WaterCell(3);
+    let steps = water_steps(&cells);
+    assert_eq!(steps.len(), 1);
+    let (face_index, quad, bottom, top) = steps[0];
+    // +X 方向的面 从低的水面到高的水面
+    assert_eq!(FACE_OFFSETS[face_index], [1, 0, 0]);
+    assert_eq!(quad.minimum, [1, 1, 1]);
+    assert_eq!(bottom, 3.0 / WATER_SOURCE_LEVEL as f32);
+    assert_eq!(top, 1.0);
+
+    // 两个单元格之间 x = 2 的平面上有一个 3/8 到 1 的侧面
+    let mesh = gen_mesh_water(cells, MaterailConfiguration::default()).unwrap();
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        panic!("没有顶点");
+    };
+    let step: Vec<&[f32; 3]> = positions
+        .chunks(4)
+        .filter(|quad| quad.iter().all(|p| p[0] == 2.0))
+        .flatten()
+        .collect();
+    assert_eq!(step.len(), 4);
+    assert!(step.iter().all(|p| p[1] == 1.375 || p[1] == 2.0));
+}
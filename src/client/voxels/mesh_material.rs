@@ -1,9 +1,10 @@
-use std::num::NonZeroU32;
+use std::{
+    num::NonZeroU32,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use bevy::{
-    prelude::{
-        AlphaMode, AssetServer, Assets, Handle, Image, Material, Mesh, Res, ResMut, Resource,
-    },
+    prelude::{AlphaMode, AssetServer, Assets, Handle, Image, Material, Mesh, Res, Resource},
     reflect::{TypePath, TypeUuid},
     render::{
         mesh::MeshVertexAttribute,
@@ -19,12 +20,29 @@ use bevy::{
     },
 };
 
-use crate::MAX_TEXTURE_COUNT;
+// 贴图数组的大小 创建渲染管线之前由方块配置中的贴图数量确定
+static TEXTURE_COUNT: AtomicUsize = AtomicUsize::new(1);
+
+pub fn set_texture_count(count: usize) {
+    TEXTURE_COUNT.store(count.max(1), Ordering::Relaxed);
+}
+
+pub fn texture_count() -> usize {
+    TEXTURE_COUNT.load(Ordering::Relaxed)
+}
 
 #[derive(Debug, Clone, TypeUuid, TypePath)]
 #[uuid = "8dd2b424-45a2-4a53-ac29-7ce356b2d5fe"]
 pub struct BindlessMaterial {
     textures: Vec<Handle<Image>>,
+    // 半透明方块的材质使用 Blend
+    alpha_mode: AlphaMode,
+}
+
+impl BindlessMaterial {
+    pub fn set_textures(&mut self, textures: Vec<Handle<Image>>) {
+        self.textures = textures;
+    }
 }
 
 impl AsBindGroup for BindlessMaterial {
@@ -46,7 +64,8 @@ impl AsBindGroup for BindlessMaterial {
             ..Default::default()
         });
 
-        for handle in self.textures.iter().take(MAX_TEXTURE_COUNT) {
+        let texture_count = texture_count();
+        for handle in self.textures.iter().take(texture_count) {
             match image_assets.get(handle) {
                 Some(image) => {
                     images.push(image);
@@ -56,12 +75,12 @@ impl AsBindGroup for BindlessMaterial {
         }
         let fallback_image = &fallback_image.d2;
 
-        let textures = vec![&fallback_image.texture_view; MAX_TEXTURE_COUNT];
+        let textures = vec![&fallback_image.texture_view; texture_count];
 
         // convert bevy's resource types to WGPU's references
         let mut textures: Vec<_> = textures.into_iter().map(|texture| &**texture).collect();
 
-        // fill in up to the first `MAX_TEXTURE_COUNT` textures and samplers to the arrays
+        // fill in up to the first `texture_count` textures and samplers to the arrays
         for (id, image) in images.into_iter().enumerate() {
             textures[id] = &*image.texture_view;
         }
@@ -105,7 +124,7 @@ impl AsBindGroup for BindlessMaterial {
                         view_dimension: TextureViewDimension::D2,
                         multisampled: false,
                     },
-                    count: NonZeroU32::new(MAX_TEXTURE_COUNT as u32),
+                    count: NonZeroU32::new(texture_count() as u32),
                 },
                 // @group(1) @binding(1) var nearest_sampler: sampler;
                 BindGroupLayoutEntry {
@@ -115,7 +134,7 @@ impl AsBindGroup for BindlessMaterial {
                     count: None,
                     // Note: as textures, multiple samplers can also be bound onto one binding slot.
                     // One may need to pay attention to the limit of sampler binding amount on some platforms.
-                    // count: NonZeroU32::new(MAX_TEXTURE_COUNT as u32),
+                    // count: NonZeroU32::new(texture_count() as u32),
                 },
             ],
         })
@@ -147,7 +166,7 @@ impl Material for BindlessMaterial {
     }
 
     fn alpha_mode(&self) -> AlphaMode {
-        AlphaMode::Opaque
+        self.alpha_mode
     }
 
     fn depth_bias(&self) -> f32 {
@@ -169,23 +188,45 @@ pub const ATTRIBUTE_DATA: MeshVertexAttribute =
 #[derive(Resource)]
 pub struct MaterialStorge(pub Handle<BindlessMaterial>);
 
+// 半透明方块的材质 所有半透明方块的mesh共用一个
+#[derive(Resource)]
+pub struct TranslucentMaterial(pub Handle<BindlessMaterial>);
+
+// 按照文件列表加载贴图 下标就是贴图的索引
+pub fn load_textures(asset_server: &AssetServer, files: &[String]) -> Vec<Handle<Image>> {
+    files
+        .iter()
+        .map(|path| {
+            println!("加载资源{}", path);
+            asset_server.load(path)
+        })
+        .collect()
+}
+
 impl MaterialStorge {
     pub fn init_with_files(
         asset_server: Res<AssetServer>,
-        mut materials: ResMut<Assets<BindlessMaterial>>,
+        materials: &mut Assets<BindlessMaterial>,
         files: Vec<String>,
     ) -> Self {
-        let textures: Vec<_> = files
-            .iter()
-            .map(|path| {
-                // 这里是文件生成的规则 0x,xx
-                // > 0 表示 用0补齐两位
-                println!("加载资源{}", path);
-                asset_server.load(path)
-            })
-            .collect();
+        let textures = load_textures(&asset_server, &files);
         // 这个东西 可以后续的处理！
-        let mat = materials.add(BindlessMaterial { textures });
+        let mat = materials.add(BindlessMaterial {
+            textures,
+            alpha_mode: AlphaMode::Opaque,
+        });
         Self(mat)
     }
+
+    // 同样的贴图 混合显示的材质 半透明方块使用
+    pub fn translucent(&self, materials: &mut Assets<BindlessMaterial>) -> TranslucentMaterial {
+        let textures = materials
+            .get(&self.0)
+            .map(|material| material.textures.clone())
+            .unwrap_or_default();
+        TranslucentMaterial(materials.add(BindlessMaterial {
+            textures,
+            alpha_mode: AlphaMode::Blend,
+        }))
+    }
 }
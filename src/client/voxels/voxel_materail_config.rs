@@ -1,11 +1,17 @@
-use std::io::Write;
+use std::path::Path;
 
-use bevy::{prelude::Resource, reflect::Reflect, utils::HashMap};
+use bevy::{
+    prelude::{Event, Resource},
+    reflect::Reflect,
+    utils::HashMap,
+};
 use bevy_inspector_egui::prelude::*;
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
 
-use crate::voxel_world::voxel::{Grass, Soli, Stone, VoxelDirection, VoxelMaterial};
+use crate::voxel_world::{
+    block_registry::{BlockRegistry, BLOCKS},
+    voxel::VoxelDirection,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, Reflect, InspectorOptions)]
 #[reflect(InspectorOptions)]
@@ -27,100 +33,127 @@ pub struct VoxelTypeConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize, Resource, InspectorOptions, Default, Reflect)]
 pub struct MaterailConfiguration {
-    // 体素类型列表
+    // 体素类型列表 由方块配置中的贴图生成
     pub voxels: HashMap<u8, VoxelTypeConfig>,
-    // 文件地址列表
+    // 文件地址列表 下标就是贴图的索引
     pub files: Vec<String>,
 }
 
-#[macro_export]
-macro_rules! add_volex {
-    ($types: ident,$class: expr) => {
-        if (!$class.voxels.contains_key(&$types::ID)) {
-            $class.voxels.insert(
-                $types::ID,
-                VoxelTypeConfig {
-                    type_name: String::from($types::NAME),
-                    type_ch_name: String::from($types::CN_NAME),
-                    ..Default::default()
-                },
-            );
-            println!(
-                "* 加载体素[{}][{}]",
-                String::from($types::NAME),
-                String::from($types::CN_NAME),
-            );
-        } else {
-            println!(
-                "加载体素[{}][{}]",
-                String::from($types::NAME),
-                String::from($types::CN_NAME),
-            );
-        }
-    };
-}
+// 服务器重新加载了方块配置 推送过来的贴图列表和索引
+#[derive(Debug, Event)]
+pub struct MaterialSyncEvent(pub MaterailConfiguration);
 
 impl MaterailConfiguration {
     // 初始化
     pub fn new() -> Self {
-        // 读取文件夹下的
         Self {
             voxels: HashMap::default(),
             files: Vec::new(),
         }
     }
 
-    // 加载文件夹下的数据
-    pub fn load_pic_files(mut self, dir: String) -> Self {
-        for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-            if entry.file_type().is_file() {
-                let file_path = entry.path();
-                // 在这里处理文件，例如打印文件路径
-
-                let path = file_path.to_str().unwrap().replace("assets/", "");
-                if self.files.contains(&path) {
-                    self.files.push(path);
-                    println!("* 文件路径: {}", file_path.display());
-                } else {
-                    println!("文件路径: {}", file_path.display());
-                }
-            }
+    // 使用全局的方块配置 配置有问题时打印出来
+    pub fn load() -> Self {
+        let config = Self::from_blocks(&BLOCKS);
+        for error in config.validate(Path::new("assets")) {
+            println!("贴图配置错误: {}", error);
         }
-        self
+        println!("加载了{}张贴图", config.files.len());
+        config
     }
 
-    pub fn load_all_voxels(mut self) -> Self {
-        // 初始化全部的数据
-        add_volex!(Stone, self);
-        add_volex!(Soli, self);
-        add_volex!(Grass, self);
-        // todo 加载其他的类型
-        self
+    /**
+     * 从方块配置生成贴图列表和索引
+     * 按照方块id的顺序 先默认贴图再按法向量排列 同一个文件只出现一次
+     */
+    pub fn from_blocks(registry: &BlockRegistry) -> Self {
+        let mut config = Self::new();
+        let mut blocks: Vec<_> = registry.blocks.values().collect();
+        blocks.sort_by_key(|block| block.id);
+        for block in blocks {
+            let Some(textures) = &block.textures else {
+                continue;
+            };
+            let default = config.texture_config(&textures.default);
+            let mut normals: Vec<_> = textures.normal.iter().collect();
+            normals.sort_by_key(|(normal, _)| **normal);
+            let normal = normals
+                .into_iter()
+                .map(|(normal, path)| (*normal, config.texture_config(path)))
+                .collect();
+            config.voxels.insert(
+                block.id,
+                VoxelTypeConfig {
+                    type_name: block.name.clone(),
+                    type_ch_name: block.ch_name.clone(),
+                    default,
+                    normal,
+                },
+            );
+        }
+        config
     }
 
-    pub fn read_file(self, path: String) -> Result<Self, ron::Error> {
-        let reader = std::fs::File::open(path);
-        match reader {
-            Ok(file) => {
-                // 如果成功取配置
-                let res: Self = ron::de::from_reader(file).unwrap();
-                let new_self = res.load_all_voxels();
-                // new_self = self.load_pic_files(String::from("assets/textures"));
-                Ok(new_self)
-            }
-            Err(_) => {
-                print!("没有找配置文件第一次加载");
-                let mut new_self = self.load_pic_files(String::from("assets/textures"));
-                new_self = new_self.load_all_voxels();
-                Ok(new_self)
+    // 图片在文件列表中的索引 没有的话加到最后
+    fn texture_config(&mut self, path: &String) -> VoxelConfig {
+        let index = match self.files.iter().position(|file| file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path.clone());
+                self.files.len() - 1
             }
+        };
+        VoxelConfig {
+            index: index as u32,
+            path: path.clone(),
         }
     }
 
-    pub fn write_file(self, path: String) {
-        let res = ron::to_string(&self).unwrap();
-        let mut file = std::fs::File::create(path).expect("create failed");
-        file.write_all(res.as_bytes()).unwrap();
+    /**
+     * 检查贴图配置 返回所有的错误
+     * 文件不存在 路径重复 索引超出范围或者和路径对不上 法向量不在 0..6 中
+     */
+    pub fn validate(&self, assets_dir: &Path) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut seen = HashMap::default();
+        for (index, path) in self.files.iter().enumerate() {
+            if let Some(first) = seen.insert(path.as_str(), index) {
+                errors.push(format!("贴图{}重复了: {} 和 {}", path, first, index));
+            }
+            if !assets_dir.join(path).is_file() {
+                errors.push(format!("贴图文件不存在: {}", path));
+            }
+        }
+        let mut ids: Vec<_> = self.voxels.keys().collect();
+        ids.sort();
+        for id in ids {
+            let type_config = &self.voxels[id];
+            let configs = std::iter::once((None, &type_config.default)).chain(
+                type_config
+                    .normal
+                    .iter()
+                    .map(|(normal, config)| (Some(*normal), config)),
+            );
+            for (normal, config) in configs {
+                if let Some(normal) = normal.filter(|normal| *normal >= 6) {
+                    errors.push(format!("方块{}的法向量{}不存在", id, normal));
+                }
+                match self.files.get(config.index as usize) {
+                    Some(path) if *path == config.path => {}
+                    Some(path) => errors.push(format!(
+                        "方块{}的贴图索引{}是{} 不是{}",
+                        id, config.index, path, config.path
+                    )),
+                    None => errors.push(format!(
+                        "方块{}的贴图索引{}超出范围 一共{}张贴图",
+                        id,
+                        config.index,
+                        self.files.len()
+                    )),
+                }
+            }
+        }
+        errors
     }
 
     // 通过面 和 体素类型获取 图片的索引
@@ -163,3 +196,26 @@ fn rotate_half_pi(normal: u8) -> u8 {
         _ => normal,
     };
 }
+
+#[test]
+fn test_block_textures() {
+    use crate::voxel_world::{
+        block_registry::{BlockRegistry, BLOCKS_RON},
+        voxel::{Grass, VoxelMaterial},
+    };
+    let registry = BlockRegistry::load(BLOCKS_RON);
+    let config = MaterailConfiguration::from_blocks(&registry);
+    assert_eq!(config.validate(Path::new("assets")), Vec::<String>::new());
+    // 草的顶面和侧面是不同的贴图
+    let grass = &config.voxels[&Grass::ID];
+    let top = grass.normal.get(&4).unwrap_or(&grass.default);
+    assert_eq!(config.files[top.index as usize], top.path);
+
+    // 索引超出范围和文件不存在都能检查出来
+    let mut broken = config.clone();
+    broken.files.truncate(1);
+    broken.files.push(String::from("textures/不存在.png"));
+    let errors = broken.validate(Path::new("assets"));
+    assert!(errors.iter().any(|error| error.contains("超出范围")));
+    assert!(errors.iter().any(|error| error.contains("不存在")));
+}
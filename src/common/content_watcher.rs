@@ -0,0 +1,148 @@
+// 游戏内容配置的热加载
+// 使用 notify 监听根目录下的配置文件 修改之后发出 ContentChangedEvent
+// 怎么重新加载由各自的模块处理 解析失败时保留原来的数据
+
+use std::{
+    path::Path,
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    prelude::{Event, EventWriter, Plugin, PreUpdate, ResMut, Resource},
+    utils::HashMap,
+};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::voxel_world::block_registry::BLOCKS_RON;
+
+pub const STAFF_RON: &str = "staff.ron";
+pub const STAFF_RULES_RON: &str = "staff_rules.ron";
+
+// 保存一次文件会有好几个事件 文件不再变化之后才加载
+const RELOAD_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentFile {
+    Staff,
+    StaffRules,
+    Blocks,
+}
+
+impl ContentFile {
+    pub const ALL: [ContentFile; 3] = [
+        ContentFile::Staff,
+        ContentFile::StaffRules,
+        ContentFile::Blocks,
+    ];
+
+    pub fn path(&self) -> &'static str {
+        match self {
+            ContentFile::Staff => STAFF_RON,
+            ContentFile::StaffRules => STAFF_RULES_RON,
+            ContentFile::Blocks => BLOCKS_RON,
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        Self::ALL.into_iter().find(|file| file.path() == file_name)
+    }
+}
+
+// 配置文件修改了
+#[derive(Debug, Clone, Copy, Event)]
+pub struct ContentChangedEvent(pub ContentFile);
+
+#[derive(Resource)]
+pub struct ContentWatcher {
+    // watcher 释放之后就不再监听了
+    _watcher: Mutex<RecommendedWatcher>,
+    receiver: Mutex<Receiver<notify::Result<notify::Event>>>,
+    // 修改了的文件 以及最后一次修改的时间
+    pending: HashMap<ContentFile, Instant>,
+}
+
+impl ContentWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        // 编辑器保存时可能是替换文件 所以监听目录
+        watcher.watch(Path::new("."), RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            _watcher: Mutex::new(watcher),
+            receiver: Mutex::new(receiver),
+            pending: HashMap::default(),
+        })
+    }
+}
+
+pub struct ContentWatcherPlugin;
+
+impl Plugin for ContentWatcherPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<ContentChangedEvent>();
+        match ContentWatcher::new() {
+            Ok(watcher) => {
+                app.insert_resource(watcher);
+                app.add_systems(PreUpdate, watch_content_files);
+            }
+            Err(err) => {
+                println!("监听配置文件失败 不能热加载{:?}", err);
+            }
+        }
+    }
+}
+
+fn watch_content_files(
+    mut watcher: ResMut<ContentWatcher>,
+    mut event_writer: EventWriter<ContentChangedEvent>,
+) {
+    let now = Instant::now();
+    let events: Vec<_> = watcher.receiver.lock().unwrap().try_iter().collect();
+    for event in events {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                println!("监听配置文件出错{:?}", err);
+                continue;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        for file in event
+            .paths
+            .iter()
+            .filter_map(|path| ContentFile::from_path(path))
+        {
+            watcher.pending.insert(file, now);
+        }
+    }
+    watcher.pending.retain(|file, changed| {
+        if now.duration_since(*changed) < RELOAD_DELAY {
+            return true;
+        }
+        println!("配置文件修改了: {}", file.path());
+        event_writer.send(ContentChangedEvent(*file));
+        false
+    });
+}
+
+#[test]
+fn test_content_file_from_path() {
+    assert_eq!(
+        ContentFile::from_path(Path::new("./staff_rules.ron")),
+        Some(ContentFile::StaffRules)
+    );
+    assert_eq!(
+        ContentFile::from_path(Path::new("/root/game/blocks.ron")),
+        Some(ContentFile::Blocks)
+    );
+    assert_eq!(ContentFile::from_path(Path::new("staff.ron.swp")), None);
+}
@@ -7,12 +7,16 @@ use bevy::{
 };
 use bevy_inspector_egui::InspectorOptions;
 
-use crate::{server::player::Player, VIEW_RADIUS};
+use crate::{server::player::Player, voxel_world::dimension::DimensionId, VIEW_RADIUS};
+
+pub mod content_watcher;
 
 #[derive(Debug, Clone, Copy, Reflect, InspectorOptions)]
 pub struct Sphere3 {
     pub center: Vec3,
     pub radius: f32,
+    // 球体所在的维度 通过球体找到的区块都在这个维度
+    pub dimension: DimensionId,
 }
 
 #[derive(Debug, Resource, Clone, Copy, Reflect, InspectorOptions)]
@@ -37,6 +41,7 @@ pub fn update_clip_shpere_system<T>(
     clip_spheres.new_sphere = Sphere3 {
         center: position,
         radius: VIEW_RADIUS,
+        dimension: DimensionId::OVERWORLD,
     }
 }
 
@@ -52,6 +57,7 @@ impl<T: Component> Plugin for ClientClipSpheresPlugin<T> {
         let init_shpere = Sphere3 {
             center: eye,
             radius: VIEW_RADIUS,
+            dimension: DimensionId::OVERWORLD,
         };
 
         let clip_spheres = ClipSpheres {
@@ -82,14 +88,15 @@ impl Plugin for ServerClipSpheresPlugin {
 
 pub fn update_all_clip_shpere_system(
     mut server_clip_spheres: ResMut<ServerClipSpheres>,
-    query: Query<(&Player, &Transform)>,
+    query: Query<(&Player, &Transform, &DimensionId)>,
 ) {
     let mut old_keys: HashSet<u64> = server_clip_spheres.clip_spheres.keys().cloned().collect();
-    for (player, transform) in query.iter() {
+    for (player, transform, dimension) in query.iter() {
         let client_id = player.id;
         let sphere = Sphere3 {
             center: transform.translation,
             radius: VIEW_RADIUS,
+            dimension: *dimension,
         };
         old_keys.remove(&client_id);
         if let Some(clip_sphere) = server_clip_spheres.clip_spheres.get_mut(&client_id) {
@@ -13,7 +13,6 @@ pub mod voxel_world;
 
 pub const PRIVATE_KEY: &[u8; NETCODE_KEY_BYTES] = b"an example very very secret key."; // 32-bytes
 pub const WORD_PATH: &str = "world_test";
-pub const MATERIAL_RON: &str = "volex.ron";
 pub const PROTOCOL_ID: u64 = 7;
 
 pub type SmallKeyHashMap<K, V> = ahash::AHashMap<K, V>;
@@ -26,8 +25,6 @@ pub const PY_DISTANCE: i32 = 1;
 pub const CHUNK_SIZE: i32 = 16;
 pub const CHUNK_SIZE_U32: u32 = CHUNK_SIZE as u32;
 pub const CHUNK_SIZE_ADD_2_U32: u32 = CHUNK_SIZE_U32 + 2;
-// 贴图个数
-pub const MAX_TEXTURE_COUNT: usize = 22;
 // 物体选择半径
 pub const TOUCH_RADIUS: f32 = 5.;
 pub const CLIENT_DEBUG: bool = false;
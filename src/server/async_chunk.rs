@@ -14,15 +14,19 @@ use crate::{
         chunk::ChunkKey,
         chunk_map::ChunkMap,
         compress::compress,
-        map_database::{DbSaveTasks, MapDataBase},
+        dimension::DimensionId,
+        lod::{downsample_column, LOD_FACTORS},
+        map_database::{peek_chunk, DbSaveTasks, MapDataBase},
         player_state::PlayerOnTimeState,
-        voxel::{BasicStone, Voxel, VoxelMaterial},
-        voxel_mesh::VOXEL_MESH_MAP,
+        pos::LocalPos,
+        voxel::Voxel,
+        world_height::WORLD_HEIGHT,
     },
-    CHUNK_SIZE, CHUNK_SIZE_U32,
+    CHUNK_SIZE_U32,
 };
 
 use super::{
+    block_update::BlockChangeEvent,
     message_def::chunk_result::ChunkResult,
     object_filing::ObjectFillEvent,
     player::ServerLobby,
@@ -30,9 +34,10 @@ use super::{
     terrain_physics::{ColliderManager, ColliderTasksManager, ColliderUpdateTasksManager},
 };
 
+// client_id 为0时 发送给这个维度中的全部玩家
 #[derive(Debug, Resource)]
 pub struct ChunkResultTasks {
-    pub tasks: Vec<Task<(u64, Vec<u8>)>>,
+    pub tasks: Vec<Task<(u64, DimensionId, Vec<u8>)>>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -52,16 +57,19 @@ pub fn deal_chunk_query_system(
     server_lobby: Res<ServerLobby>,
     mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
     mut event_writer: EventWriter<DespawnSpEvent>,
+    mut block_change_event: EventWriter<BlockChangeEvent>,
 ) {
     let pool = AsyncComputeTaskPool::get();
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, ClientChannel::ChunkQuery) {
             let chunk_query: ChunkQuery = bincode::deserialize(&message).unwrap();
+            // 客户端只知道自己所在的维度 这里换成玩家所在的维度
+            let dimension = server_lobby.dimension_of(client_id);
             match chunk_query {
                 ChunkQuery::GetFullY(chunk_key) => {
+                    let chunk_key = chunk_key.with_dimension(dimension);
                     // 获取全部的值 然后返回
-                    let last_inex = -128 / CHUNK_SIZE + 1;
-                    for y_offset in last_inex..=128 / CHUNK_SIZE {
+                    for y_offset in WORLD_HEIGHT.chunk_ys() {
                         let mut new_key = chunk_key;
                         new_key.0.y = y_offset;
                         let voxels;
@@ -86,17 +94,57 @@ pub fn deal_chunk_query_system(
                             .unwrap()
                         };
 
-                        let task = pool.spawn(async move { (client_id, message) });
+                        let task = pool.spawn(async move { (client_id, dimension, message) });
                         tasks.tasks.push(task);
                     }
                 }
+                ChunkQuery::GetLod(chunk_key, factor) => {
+                    if !LOD_FACTORS.contains(&factor) {
+                        warn!("不支持的LOD倍数{}", factor);
+                        continue;
+                    }
+                    let chunk_key = chunk_key.with_dimension(dimension);
+                    // 已经加载的区块直接使用 其他的在任务中读取或者生成
+                    let loaded: Vec<(ChunkKey, Option<Vec<Voxel>>)> = WORLD_HEIGHT
+                        .chunk_ys()
+                        .map(|chunk_y| {
+                            let mut new_key = chunk_key;
+                            new_key.0.y = chunk_y;
+                            (new_key, chunk_map.map_data.get(&new_key).cloned())
+                        })
+                        .collect();
+                    let db = db.db.clone();
+                    let task = pool.spawn(async move {
+                        let chunks: Vec<Vec<Voxel>> = loaded
+                            .into_iter()
+                            .map(|(key, voxels)| voxels.unwrap_or_else(|| peek_chunk(&db, key)))
+                            .collect();
+                        let voxels = downsample_column(&chunks, factor);
+                        let (buffer, tree) = compress(voxels.clone());
+                        let result = if buffer.len() == 0 {
+                            ChunkResult::LodSame {
+                                key: chunk_key,
+                                factor,
+                                voxel: voxels[0],
+                            }
+                        } else {
+                            ChunkResult::LodColumn {
+                                key: chunk_key,
+                                factor,
+                                data: (buffer, tree),
+                            }
+                        };
+                        (client_id, dimension, bincode::serialize(&result).unwrap())
+                    });
+                    tasks.tasks.push(task);
+                }
                 ChunkQuery::Change {
-                    chunk_key,
-                    pos,
+                    block,
                     voxel_type,
-                    center,
                     active_index,
                 } => {
+                    let (chunk_key, LocalPos(pos)) = ChunkKey::from_block(block, dimension);
+                    let center = block.center();
                     if let Some(voxel) = chunk_map.map_data.get_mut(&chunk_key) {
                         // 1. 更新 chunk_map 数据
                         type SampleShape =
@@ -106,8 +154,8 @@ pub fn deal_chunk_query_system(
                         let old_voxel = voxel[index].clone();
                         println!("老的位置体素为{:?}", old_voxel);
                         println!("新的位置:[{:?},{}]", chunk_key, index);
-                        if voxel[index].id == BasicStone::ID {
-                            warn!("基岩无法破坏");
+                        if !old_voxel.is_breakable() {
+                            warn!("{:?}无法破坏", old_voxel);
                             continue;
                         }
                         if old_voxel.id != Voxel::EMPTY.id
@@ -155,25 +203,31 @@ pub fn deal_chunk_query_system(
                         let task =
                             pool.spawn(async move { (chunk_key.as_u8_array(), new_voxels_clone) });
                         db_save_task.tasks.push(task);
-                        // 3. 通知 全体 更新数据
+                        // 3. 通知 同一个维度的全体 更新数据
                         let message = bincode::serialize(&ChunkResult::ChunkUpdateOne {
                             chunk_key,
                             pos,
                             voxel_type,
                         })
                         .unwrap();
-                        server.broadcast_message(ServerChannel::ChunkResult, message);
+                        server_lobby.broadcast_in(
+                            &mut server,
+                            dimension,
+                            ServerChannel::ChunkResult,
+                            message,
+                        );
+                        block_change_event.send(BlockChangeEvent { chunk_key, pos });
+                        // 特殊物体被破坏或者旋转了 删除原来的碰撞体 旋转的会按照新的方向重新生成
+                        if old_voxel.has_model() && old_voxel != voxel_type {
+                            event_writer.send(DespawnSpEvent {
+                                chunk_key,
+                                index: index.clone(),
+                            });
+                        }
                         // FIXME: 这里要考虑把代码格式简化 一下
                         // 发送物体被打下来的消息 old_voxel  chunk_key, pos, 还原物体的位置!
                         if old_voxel.id != Voxel::EMPTY.id && voxel_type.id == Voxel::EMPTY.id {
                             println!("cube被打下来了: {:?}", old_voxel);
-                            if VOXEL_MESH_MAP.contains_key(&old_voxel.id) {
-                                // 如果是特殊物体被破坏要处理物理地形
-                                event_writer.send(DespawnSpEvent {
-                                    chunk_key,
-                                    index: index.clone(),
-                                });
-                            }
 
                             // 物体时被打下来了 这里通过配置掉落
                             if let Some(staff_list) =
@@ -190,80 +244,15 @@ pub fn deal_chunk_query_system(
                                 }
                             }
                         }
-                        // 4. 判断 并更新codiller 存在的情况下才更新
+                        // 4. 更新碰撞体 长方体碰撞体不需要邻居区块的数据
                         send_codiller_task(
                             chunk_key,
+                            &[pos],
                             &collider_manager,
                             &chunk_map,
                             &mut collider_update_tasks_manager,
                             &mut collider_tasks,
                         );
-                        if pos[0] == 0 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.x -= 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
-                        if pos[0] == CHUNK_SIZE_U32 - 1 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.x += 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
-                        if pos[1] == 0 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.y -= 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
-                        if pos[1] == CHUNK_SIZE_U32 - 1 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.y += 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
-                        if pos[2] == 0 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.z -= 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
-                        if pos[2] == CHUNK_SIZE_U32 - 1 {
-                            let mut new_chunk_key_i3 = chunk_key.0;
-                            new_chunk_key_i3.z += 1;
-                            send_codiller_task(
-                                ChunkKey(new_chunk_key_i3),
-                                &collider_manager,
-                                &chunk_map,
-                                &mut collider_update_tasks_manager,
-                                &mut collider_tasks,
-                            );
-                        }
                     }
                 }
             }
@@ -271,38 +260,55 @@ pub fn deal_chunk_query_system(
     }
 }
 
-fn send_codiller_task(
+// 已经生成过碰撞体的区块只更新修改的那几层 没有的在后台生成
+pub fn send_codiller_task(
     chunk_key: ChunkKey,
+    positions: &[[u32; 3]],
     collider_manager: &ColliderManager,
     chunk_map: &ChunkMap,
     collider_update_tasks_manager: &mut ColliderUpdateTasksManager,
     collider_tasks: &mut ColliderTasksManager,
 ) {
-    let pool = AsyncComputeTaskPool::get();
-    if let Some(&entity) = collider_manager.entities.get(&chunk_key) {
-        let new_voxels_clone = chunk_map.get_neighbors(chunk_key);
-        let task = pool.spawn(async move { (entity, chunk_key, new_voxels_clone) });
-        collider_update_tasks_manager.tasks.push(task);
-    } else if chunk_map.map_data.contains_key(&chunk_key) {
-        let voxel_with_neighbor = chunk_map.get_neighbors(chunk_key);
-        let task = pool.spawn(async move { (chunk_key, voxel_with_neighbor) });
-        collider_tasks.tasks.push(task);
+    if collider_manager.shapes.contains_key(&chunk_key) {
+        collider_update_tasks_manager.mark(chunk_key, positions);
+    } else if let Some(voxels) = chunk_map.get(chunk_key) {
+        // 碰撞体还在后台生成时 用修改之后的方块重新生成
+        collider_tasks.rebuild(chunk_key, voxels.clone());
     }
 }
 
-pub fn send_message(mut tasks: ResMut<ChunkResultTasks>, mut server: ResMut<RenetServer>) {
-    let l = tasks.tasks.len().min(16);
-    for ele in tasks.tasks.drain(..l) {
-        if let Some((client_id, message)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
+pub fn send_message(
+    mut tasks: ResMut<ChunkResultTasks>,
+    mut server: ResMut<RenetServer>,
+    server_lobby: Res<ServerLobby>,
+) {
+    // 一次最多发送16个 没有完成的任务留到下一帧
+    let mut sent = 0;
+    let mut pending = Vec::with_capacity(tasks.tasks.len());
+    for mut ele in tasks.tasks.drain(..) {
+        if sent >= 16 {
+            pending.push(ele);
+            continue;
+        }
+        if let Some((client_id, dimension, message)) =
+            futures_lite::future::block_on(futures_lite::future::poll_once(&mut ele))
         {
+            sent += 1;
             if client_id == 0 {
-                server.broadcast_message(ServerChannel::ChunkResult, message);
+                server_lobby.broadcast_in(
+                    &mut server,
+                    dimension,
+                    ServerChannel::ChunkResult,
+                    message,
+                );
             } else {
                 server.send_message(client_id, ServerChannel::ChunkResult, message);
             }
+        } else {
+            pending.push(ele);
         }
     }
+    tasks.tasks = pending;
 }
 
 pub struct ChunkDataPlugin;
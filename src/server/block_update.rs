@@ -0,0 +1,159 @@
+// 方块更新
+// 服务端的模拟(流体 下落的方块等)修改方块时 先写入 BlockUpdateQueue
+// 每帧结束时按照区块合并 一起保存到数据库 广播给客户端 并更新碰撞体
+// 同时发出 BlockChangeEvent 让其他模拟可以继续响应
+
+use bevy::{
+    prelude::{Event, EventWriter, IVec3, Plugin, PostUpdate, Res, ResMut, Resource},
+    tasks::AsyncComputeTaskPool,
+    utils::HashMap,
+};
+use bevy_renet::renet::RenetServer;
+use ndshape::{ConstShape, ConstShape3u32};
+
+use crate::{
+    voxel_world::{
+        chunk::ChunkKey, chunk_map::ChunkMap, map_database::DbSaveTasks, pos::LocalPos,
+        voxel::Voxel,
+    },
+    CHUNK_SIZE_U32,
+};
+
+use super::{
+    async_chunk::send_codiller_task,
+    message_def::{chunk_result::ChunkResult, ServerChannel},
+    player::ServerLobby,
+    terrain_physics::{ColliderManager, ColliderTasksManager, ColliderUpdateTasksManager},
+};
+
+type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+
+// 相邻的六个方向
+pub const NEIGHBOR_OFFSETS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+// 某个方块被改变了 模拟相关的系统监听这个事件
+#[derive(Debug, Clone, Copy, Event)]
+pub struct BlockChangeEvent {
+    pub chunk_key: ChunkKey,
+    pub pos: [u32; 3],
+}
+
+// 等待同步的方块修改
+#[derive(Debug, Resource, Default)]
+pub struct BlockUpdateQueue {
+    pub changes: HashMap<ChunkKey, Vec<([u32; 3], Voxel)>>,
+}
+
+impl BlockUpdateQueue {
+    /**
+     * 修改 chunk_map 中的方块 并记录下来等待同步
+     * 区块没有加载时返回 false
+     */
+    pub fn set_block(
+        &mut self,
+        chunk_map: &mut ChunkMap,
+        chunk_key: ChunkKey,
+        pos: [u32; 3],
+        voxel: Voxel,
+    ) -> bool {
+        let Some(voxels) = chunk_map.map_data.get_mut(&chunk_key) else {
+            return false;
+        };
+        voxels[SampleShape::linearize(pos) as usize] = voxel;
+        let list = self.changes.entry(chunk_key).or_default();
+        // 同一帧内多次修改同一个位置 只保留最后一次
+        if let Some(change) = list.iter_mut().find(|(p, _)| *p == pos) {
+            change.1 = voxel;
+        } else {
+            list.push((pos, voxel));
+        }
+        true
+    }
+}
+
+/**
+ * 相对某个方块偏移后的方块位置 可以跨越区块
+ */
+pub fn offset_block_pos(chunk_key: ChunkKey, pos: [u32; 3], offset: IVec3) -> (ChunkKey, [u32; 3]) {
+    let block = chunk_key.block(LocalPos(pos)).offset(offset);
+    let (chunk_key, LocalPos(pos)) = ChunkKey::from_block(block, chunk_key.1);
+    (chunk_key, pos)
+}
+
+// 把一帧内的修改合并后同步
+#[allow(clippy::too_many_arguments)]
+pub fn flush_block_updates(
+    mut queue: ResMut<BlockUpdateQueue>,
+    chunk_map: Res<ChunkMap>,
+    mut db_save_task: ResMut<DbSaveTasks>,
+    mut server: ResMut<RenetServer>,
+    collider_manager: Res<ColliderManager>,
+    mut collider_update_tasks_manager: ResMut<ColliderUpdateTasksManager>,
+    mut collider_tasks: ResMut<ColliderTasksManager>,
+    mut block_change_event: EventWriter<BlockChangeEvent>,
+    server_lobby: Res<ServerLobby>,
+) {
+    if queue.changes.is_empty() {
+        return;
+    }
+    let pool = AsyncComputeTaskPool::get();
+    for (chunk_key, changes) in queue.changes.drain() {
+        let Some(voxels) = chunk_map.map_data.get(&chunk_key) else {
+            continue;
+        };
+        // 1. 保存数据
+        let new_voxels_clone = voxels.clone();
+        let task = pool.spawn(async move { (chunk_key.as_u8_array(), new_voxels_clone) });
+        db_save_task.tasks.push(task);
+        // 2. 只更新修改的那几层碰撞体
+        let positions: Vec<[u32; 3]> = changes.iter().map(|(pos, _)| *pos).collect();
+        send_codiller_task(
+            chunk_key,
+            &positions,
+            &collider_manager,
+            &chunk_map,
+            &mut collider_update_tasks_manager,
+            &mut collider_tasks,
+        );
+        // 修改也会影响周围的模拟
+        for pos in positions {
+            block_change_event.send(BlockChangeEvent { chunk_key, pos });
+        }
+        // 3. 通知区块所在维度的全体
+        let message =
+            bincode::serialize(&ChunkResult::ChunkUpdateBatch { chunk_key, changes }).unwrap();
+        server_lobby.broadcast_in(
+            &mut server,
+            chunk_key.1,
+            ServerChannel::ChunkResult,
+            message,
+        );
+    }
+}
+
+pub struct BlockUpdatePlugin;
+
+impl Plugin for BlockUpdatePlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<BlockChangeEvent>();
+        app.insert_resource(BlockUpdateQueue::default());
+        app.add_systems(PostUpdate, flush_block_updates);
+    }
+}
+
+#[test]
+fn test_offset_block_pos() {
+    let key = ChunkKey::new(IVec3::new(0, 0, 0));
+    assert_eq!(
+        offset_block_pos(key, [0, 5, 15], IVec3::new(-1, 0, 1)),
+        (ChunkKey::new(IVec3::new(-1, 0, 1)), [15, 5, 0])
+    );
+    assert_eq!(offset_block_pos(key, [3, 5, 7], IVec3::Y), (key, [3, 6, 7]));
+}
@@ -0,0 +1,272 @@
+// 地形的体素碰撞体
+// 区块中碰撞体是 Cube 的方块合并成长方体 组成一个复合碰撞体 不再使用三角面
+// 区块按照高度分成几层 修改方块时只重新合并它所在的那一层
+// 长方体是实心的 不会像三角面那样穿过去 也不需要邻居区块的数据
+
+use bevy::prelude::{Quat, UVec3, Vec3};
+use bevy_rapier3d::prelude::Collider;
+use ndshape::{ConstShape, ConstShape3u32, RuntimeShape, Shape};
+
+use crate::{
+    voxel_world::{
+        block_registry::ColliderShape,
+        voxel::Voxel,
+        voxel_mesh::vox_collider::{merge_cuboids, VoxCuboid},
+    },
+    CHUNK_SIZE_U32,
+};
+
+// 每一层的高度
+pub const COLLIDER_SLAB_HEIGHT: u32 = 4;
+pub const COLLIDER_SLAB_COUNT: u32 = CHUNK_SIZE_U32 / COLLIDER_SLAB_HEIGHT;
+
+type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+
+#[derive(Debug, Clone, Default)]
+pub struct ChunkColliderShape {
+    // 每一层合并好的长方体 坐标是区块内的方块坐标
+    pub slabs: Vec<Vec<VoxCuboid>>,
+}
+
+impl ChunkColliderShape {
+    pub fn new(voxels: &[Voxel]) -> Self {
+        Self {
+            slabs: (0..COLLIDER_SLAB_COUNT)
+                .map(|slab| slab_cuboids(voxels, slab))
+                .collect(),
+        }
+    }
+
+    // 方块所在的层
+    pub fn slab_of(y: u32) -> u32 {
+        y / COLLIDER_SLAB_HEIGHT
+    }
+
+    // 重新合并某一层
+    pub fn update_slab(&mut self, voxels: &[Voxel], slab: u32) {
+        self.slabs[slab as usize] = slab_cuboids(voxels, slab);
+    }
+
+    pub fn cuboid_count(&self) -> usize {
+        self.slabs.iter().map(|slab| slab.len()).sum()
+    }
+
+    // 复合碰撞体 原点是区块的最小方块 没有方块时为 None
+    pub fn collider(&self) -> Option<Collider> {
+        if self.cuboid_count() == 0 {
+            return None;
+        }
+        let shapes = self
+            .slabs
+            .iter()
+            .flatten()
+            .map(|cuboid| {
+                let min = cuboid.min.as_vec3();
+                let max = cuboid.max.as_vec3();
+                let half_size = (max - min) / 2.0;
+                (
+                    (min + max) / 2.0,
+                    Quat::IDENTITY,
+                    Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                )
+            })
+            .collect::<Vec<(Vec3, Quat, Collider)>>();
+        Some(Collider::compound(shapes))
+    }
+}
+
+// 一层中的方块合并成长方体
+fn slab_cuboids(voxels: &[Voxel], slab: u32) -> Vec<VoxCuboid> {
+    let size = UVec3::new(CHUNK_SIZE_U32, COLLIDER_SLAB_HEIGHT, CHUNK_SIZE_U32);
+    let shape = RuntimeShape::<u32, 3>::new(size.to_array());
+    let base_y = slab * COLLIDER_SLAB_HEIGHT;
+    let filled: Vec<bool> = (0..shape.size())
+        .map(|index| {
+            let [x, y, z] = shape.delinearize(index);
+            let voxel = voxels[SampleShape::linearize([x, base_y + y, z]) as usize];
+            voxel.collider_shape() == ColliderShape::Cube
+        })
+        .collect();
+    merge_cuboids(size, &filled)
+        .into_iter()
+        .map(|cuboid| VoxCuboid {
+            min: cuboid.min + UVec3::new(0, base_y, 0),
+            max: cuboid.max + UVec3::new(0, base_y, 0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn test_terrain() -> Vec<Voxel> {
+    use crate::voxel_world::voxel::{Grass, Stone, VoxelMaterial, Water};
+    // 起伏的地面 上面有水和零散的方块
+    (0..SampleShape::SIZE)
+        .map(|index| {
+            let [x, y, z] = SampleShape::delinearize(index);
+            let height = 6 + (x * 7 + z * 13) % 5;
+            if y < height {
+                Stone::into_voxel()
+            } else if y == height {
+                Grass::into_voxel()
+            } else if y < 12 {
+                Water::into_voxel()
+            } else if (x * 31 + y * 17 + z * 7) % 23 == 0 {
+                Stone::into_voxel()
+            } else {
+                Voxel::EMPTY
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_chunk_collider_shape() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    let mut voxels = test_terrain();
+    let solid = voxels
+        .iter()
+        .filter(|voxel| voxel.collider_shape() == ColliderShape::Cube)
+        .count() as u32;
+    let mut shape = ChunkColliderShape::new(&voxels);
+    let volume = |shape: &ChunkColliderShape| -> u32 {
+        shape
+            .slabs
+            .iter()
+            .flatten()
+            .map(|cuboid| {
+                let size = cuboid.max - cuboid.min;
+                size.x * size.y * size.z
+            })
+            .sum()
+    };
+    // 长方体正好覆盖全部的实心方块
+    assert_eq!(volume(&shape), solid);
+    assert!(shape.cuboid_count() < solid as usize / 2);
+
+    // 只更新修改的那一层 结果和重新生成一样
+    let index = SampleShape::linearize([3, 13, 5]) as usize;
+    voxels[index] = if voxels[index].id == Voxel::EMPTY.id {
+        Stone::into_voxel()
+    } else {
+        Voxel::EMPTY
+    };
+    shape.update_slab(&voxels, ChunkColliderShape::slab_of(13));
+    assert_eq!(shape.slabs, ChunkColliderShape::new(&voxels).slabs);
+    assert!(shape.collider().is_some());
+    let empty = [Voxel::EMPTY; SampleShape::SIZE as usize];
+    assert!(ChunkColliderShape::new(&empty).collider().is_none());
+}
+
+/**
+ * 通过包含邻居的体素数据 获取三角面的碰撞体
+ * 只有碰撞体是 Cube 的方块参与 特殊模型的碰撞体单独生成
+ * 地形已经改用 ChunkColliderShape 这里只留给性能比较使用
+ */
+#[cfg(test)]
+fn gen_collider(voxels: Vec<Voxel>) -> Option<Collider> {
+    use block_mesh::{greedy_quads, GreedyQuadsBuffer, RIGHT_HANDED_Y_UP_CONFIG};
+
+    use crate::{CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32};
+
+    type SampleShape =
+        ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
+    let voxels: Vec<Voxel> = voxels
+        .into_iter()
+        .map(|voxel| match voxel.collider_shape() {
+            ColliderShape::Cube => Voxel::FILLED,
+            ColliderShape::None | ColliderShape::Model => Voxel::EMPTY,
+        })
+        .collect();
+    let mut buffer = GreedyQuadsBuffer::new(SampleShape::SIZE as usize);
+    let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
+    greedy_quads(
+        &voxels,
+        &SampleShape {},
+        [0; 3],
+        [
+            (CHUNK_SIZE + 1) as u32,
+            (CHUNK_SIZE + 1) as u32,
+            (CHUNK_SIZE + 1) as u32,
+        ],
+        &faces,
+        &mut buffer,
+    );
+    let num_indices = buffer.quads.num_quads() * 6;
+    let num_vertices = buffer.quads.num_quads() * 4;
+    if num_indices == 0 {
+        return None;
+    }
+    let mut indices = Vec::with_capacity(num_indices);
+    let mut positions = Vec::with_capacity(num_vertices);
+    let mut normals = Vec::with_capacity(num_vertices);
+
+    for (_, (group, face)) in buffer
+        .quads
+        .groups
+        .as_ref()
+        .iter()
+        .zip(faces.into_iter())
+        .enumerate()
+    {
+        for quad in group.iter() {
+            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
+            positions.extend_from_slice(&face.quad_mesh_positions(quad, 1.0));
+            normals.extend_from_slice(&face.quad_mesh_normals());
+        }
+    }
+    let collider_vertices: Vec<Vec3> = positions.iter().cloned().map(Vec3::from).collect();
+    let collider_indices: Vec<[u32; 3]> = indices.chunks(3).map(|i| [i[0], i[1], i[2]]).collect();
+    let collider = Collider::trimesh(collider_vertices, collider_indices);
+    Some(collider)
+}
+
+/**
+ * 和原来的三角面碰撞体比较生成的时间
+ * cargo test --release bench_terrain_collider -- --ignored --nocapture
+ */
+#[test]
+#[ignore]
+fn bench_terrain_collider() {
+    use std::time::Instant;
+
+    use crate::CHUNK_SIZE_ADD_2_U32;
+
+    type PaddedShape =
+        ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
+    let voxels = test_terrain();
+    // 三角面需要带一圈邻居的数据 这里邻居是空的
+    let mut padded = vec![Voxel::EMPTY; PaddedShape::SIZE as usize];
+    for index in 0..SampleShape::SIZE {
+        let [x, y, z] = SampleShape::delinearize(index);
+        padded[PaddedShape::linearize([x + 1, y + 1, z + 1]) as usize] = voxels[index as usize];
+    }
+    let rounds = 200;
+
+    let start = Instant::now();
+    for _ in 0..rounds {
+        gen_collider(padded.clone());
+    }
+    let trimesh = start.elapsed() / rounds;
+
+    let start = Instant::now();
+    for _ in 0..rounds {
+        ChunkColliderShape::new(&voxels).collider();
+    }
+    let cuboids = start.elapsed() / rounds;
+
+    let mut shape = ChunkColliderShape::new(&voxels);
+    let start = Instant::now();
+    for _ in 0..rounds {
+        shape.update_slab(&voxels, 3);
+        shape.collider();
+    }
+    let incremental = start.elapsed() / rounds;
+
+    println!("三角面碰撞体: {:?}", trimesh);
+    println!(
+        "长方体碰撞体: {:?} ({}个长方体)",
+        cuboids,
+        shape.cuboid_count()
+    );
+    println!("修改一个方块后更新: {:?}", incremental);
+}
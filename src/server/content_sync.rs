@@ -0,0 +1,198 @@
+// 物品定义 合成规则和方块配置的热加载
+// 配置文件修改之后 重新读取成功才整体替换 然后推送给所有客户端
+// 之后连接的客户端也会收到重新加载过的定义 保证和服务器一致
+
+use std::path::Path;
+
+use bevy::{
+    prelude::{EventReader, Plugin, Res, ResMut, Resource, Update},
+    utils::HashMap,
+};
+use bevy_renet::renet::{RenetServer, ServerEvent};
+
+use crate::{
+    client::voxels::voxel_materail_config::MaterailConfiguration,
+    common::content_watcher::{ContentChangedEvent, ContentFile},
+    staff::{rule::StaffRules, StaffConfigs, StaffInfoStroge},
+    voxel_world::block_registry::{BlockDrop, BlockRegistry},
+};
+
+use super::message_def::{server_messages::ServerMessages, ServerChannel};
+
+// 运行中重新加载过的定义 没有重新加载过的客户端自己读取文件
+#[derive(Debug, Default, Resource)]
+pub struct ReloadedContent {
+    pub staff: Option<StaffConfigs>,
+    pub rules: Option<StaffRules>,
+    pub drops: Option<HashMap<u8, Vec<BlockDrop>>>,
+    pub material: Option<MaterailConfiguration>,
+}
+
+impl ReloadedContent {
+    fn messages(&self) -> Vec<ServerMessages> {
+        let mut messages = Vec::new();
+        if let Some(configs) = &self.staff {
+            messages.push(ServerMessages::StaffSync {
+                configs: configs.clone(),
+            });
+        }
+        if let Some(rules) = &self.rules {
+            messages.push(ServerMessages::StaffRulesSync {
+                rules: rules.rules.values().cloned().collect(),
+            });
+        }
+        if let Some(drops) = &self.drops {
+            messages.push(ServerMessages::BlockDropsSync {
+                drops: drops.clone(),
+            });
+        }
+        if let Some(config) = &self.material {
+            messages.push(ServerMessages::MaterialSync {
+                config: config.clone(),
+            });
+        }
+        messages
+    }
+}
+
+pub struct ServerContentSyncPlugin;
+
+impl Plugin for ServerContentSyncPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(ReloadedContent::default());
+        app.add_systems(Update, (reload_content, send_reloaded_content));
+    }
+}
+
+fn reload_content(
+    mut events: EventReader<ContentChangedEvent>,
+    mut storge: ResMut<StaffInfoStroge>,
+    mut staff_rules: ResMut<StaffRules>,
+    mut reloaded: ResMut<ReloadedContent>,
+    mut server: ResMut<RenetServer>,
+) {
+    for ContentChangedEvent(file) in events.iter() {
+        for message in reload_file(
+            *file,
+            file.path(),
+            &mut storge,
+            &mut staff_rules,
+            &mut reloaded,
+        ) {
+            let message = bincode::serialize(&message).unwrap();
+            server.broadcast_message(ServerChannel::ServerMessages, message);
+        }
+    }
+}
+
+/**
+ * 重新读取一个配置文件 返回要推送给客户端的消息
+ * 解析失败时不修改任何数据 也不推送
+ */
+fn reload_file(
+    file: ContentFile,
+    path: &str,
+    storge: &mut StaffInfoStroge,
+    staff_rules: &mut StaffRules,
+    reloaded: &mut ReloadedContent,
+) -> Vec<ServerMessages> {
+    match file {
+        ContentFile::Staff => match StaffConfigs::load(path) {
+            Ok(configs) => {
+                storge.replace_configs(configs.clone(), None);
+                println!("重新加载了物品定义 一共{}种物品", storge.data.len());
+                reloaded.staff = Some(configs.clone());
+                vec![ServerMessages::StaffSync { configs }]
+            }
+            Err(err) => {
+                println!("物品定义解析失败 继续使用原来的定义{}", err);
+                Vec::new()
+            }
+        },
+        ContentFile::StaffRules => match StaffRules::load(path) {
+            Ok(rules) => {
+                println!("重新加载了合成规则 一共{}条", rules.rules.len());
+                *staff_rules = rules.clone();
+                let message = ServerMessages::StaffRulesSync {
+                    rules: rules.rules.values().cloned().collect(),
+                };
+                reloaded.rules = Some(rules);
+                vec![message]
+            }
+            Err(err) => {
+                println!("合成规则解析失败 继续使用原来的规则{}", err);
+                Vec::new()
+            }
+        },
+        // 掉落物和贴图可以重新加载 贴图列表和索引推送给客户端
+        // 类型和碰撞体等属性影响已经生成的 mesh 和碰撞体 还是需要重启
+        ContentFile::Blocks => match BlockRegistry::read(path) {
+            Ok(registry) => {
+                let drops = registry.drops();
+                println!("重新加载了方块掉落物 一共{}种方块", drops.len());
+                storge.block_drops = drops.clone();
+                reloaded.drops = Some(drops.clone());
+                let mut messages = vec![ServerMessages::BlockDropsSync { drops }];
+                let config = MaterailConfiguration::from_blocks(&registry);
+                let errors = config.validate(Path::new("assets"));
+                if errors.is_empty() {
+                    reloaded.material = Some(config.clone());
+                    messages.push(ServerMessages::MaterialSync { config });
+                } else {
+                    for error in errors {
+                        println!("贴图配置错误 继续使用原来的贴图: {}", error);
+                    }
+                }
+                messages
+            }
+            Err(err) => {
+                println!("方块配置解析失败 继续使用原来的配置{}", err);
+                Vec::new()
+            }
+        },
+    }
+}
+
+fn send_reloaded_content(
+    mut server_events: EventReader<ServerEvent>,
+    reloaded: Res<ReloadedContent>,
+    mut server: ResMut<RenetServer>,
+) {
+    for event in server_events.iter() {
+        if let ServerEvent::ClientConnected { client_id } = event {
+            for message in reloaded.messages() {
+                let message = bincode::serialize(&message).unwrap();
+                server.send_message(*client_id, ServerChannel::ServerMessages, message);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_reload_parse_failure() {
+    use crate::common::content_watcher::{STAFF_RON, STAFF_RULES_RON};
+
+    let mut storge = StaffInfoStroge::from_configs(StaffConfigs::load(STAFF_RON).unwrap(), None);
+    let mut staff_rules = StaffRules::load(STAFF_RULES_RON).unwrap();
+    let mut reloaded = ReloadedContent::default();
+    let (staff_count, rule_count, drop_count) = (
+        storge.data.len(),
+        staff_rules.rules.len(),
+        storge.block_drops.len(),
+    );
+    assert!(staff_count > 0 && rule_count > 0);
+
+    let path = std::env::temp_dir().join("content_sync_broken.ron");
+    std::fs::write(&path, "(这不是 ron").unwrap();
+    let path = path.to_str().unwrap();
+    for file in ContentFile::ALL {
+        let messages = reload_file(file, path, &mut storge, &mut staff_rules, &mut reloaded);
+        assert!(messages.is_empty());
+    }
+    // 原来的数据都还在 也没有记录要推送给新客户端的内容
+    assert_eq!(storge.data.len(), staff_count);
+    assert_eq!(storge.block_drops.len(), drop_count);
+    assert_eq!(staff_rules.rules.len(), rule_count);
+    assert!(reloaded.messages().is_empty());
+    let _ = std::fs::remove_file(path);
+}
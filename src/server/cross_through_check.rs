@@ -1,59 +1,153 @@
 // 穿透检查
+// 物体卡进有碰撞体的方块时 移动到三维空间中最近的空位
+// 所在区块的地形碰撞体还没有生成时 先冻结物体 等地形加载之后再恢复 防止掉出世界
 
 use bevy::prelude::{
-    Commands, Component, Entity, Plugin, PreUpdate, Query, Res, ResMut, Transform, Update, Vec3,
-    Without,
+    Commands, Component, Entity, IVec3, IntoSystemConfigs, Plugin, PreUpdate, Query, Res, ResMut,
+    Transform, Update, Vec3, With, Without,
 };
 use bevy_rapier3d::{
-    prelude::{RapierContext, RapierRigidBodyHandle},
-    rapier::prelude::RigidBodyType,
+    prelude::{RapierContext, RapierRigidBodyHandle, RigidBodyDisabled},
+    rapier::{math::Vector, prelude::RigidBodyType},
 };
 
-use crate::{
-    tools::{chunk_key_any_xyz_to_vec3, pos_to_center, vec3_to_chunk_key_any_xyz},
-    voxel_world::{chunk_map::ChunkMap, voxel::Voxel},
+use crate::voxel_world::{
+    chunk::ChunkKey, chunk_map::ChunkMap, dimension::DimensionId, pos::BlockPos,
+    world_height::WORLD_HEIGHT,
 };
 
+use super::terrain_physics::{ColliderManager, ColliderSystem};
+
+// 寻找空位的范围 超出之后沿着这一列往上找
+pub const UNSTUCK_DISTANCE: i32 = 3;
+
+// 检查时允许陷进地面的深度
+const PENETRATION_TOLERANCE: f32 = 0.1;
+
 #[derive(Debug, Clone, Copy, Component)]
-pub struct CossTroughCheck;
+pub struct CossTroughCheck {
+    // 物体最低点在位置下方多远
+    pub bottom: f32,
+    // 物体占几格高
+    pub height: i32,
+}
+
+impl CossTroughCheck {
+    // 玩家的胶囊体 从 -1.15 到 0.7
+    pub const PLAYER: Self = Self {
+        bottom: 1.15,
+        height: 2,
+    };
+    // 掉落物
+    pub const OBJECT: Self = Self {
+        bottom: 0.05,
+        height: 1,
+    };
+
+    // 物体最低点所在的方块
+    fn feet(&self, translation: Vec3) -> BlockPos {
+        BlockPos::from_vec3(translation - Vec3::Y * (self.bottom - PENETRATION_TOLERANCE))
+    }
+}
 
 #[derive(Debug, Clone, Copy, Component)]
 pub struct CossTroughFixed(Vec3);
 
+// 等待地形碰撞体加载的物体
+#[derive(Debug, Clone, Copy, Component)]
+pub struct TerrainWaiting;
+
 pub struct CossTroughCheckPlugin;
 
 impl Plugin for CossTroughCheckPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(PreUpdate, cross_through_check);
+        app.add_systems(
+            PreUpdate,
+            (wait_terrain, cross_through_check)
+                .chain()
+                .after(ColliderSystem::ColliderSpawn),
+        );
         app.add_systems(Update, cross_through_fixed);
     }
 }
 
+// 没有地形碰撞体的区块中的物体先冻结
+fn wait_terrain(
+    mut commands: Commands,
+    collider_manager: Res<ColliderManager>,
+    query: Query<
+        (Entity, &Transform, &DimensionId, Option<&TerrainWaiting>),
+        With<CossTroughCheck>,
+    >,
+) {
+    for (entity, trf, dimension, waiting) in query.iter() {
+        let (chunk_key, _) = ChunkKey::from_block(BlockPos::from_vec3(trf.translation), *dimension);
+        let loaded = terrain_loaded(&collider_manager, chunk_key);
+        match (loaded, waiting.is_some()) {
+            (false, false) => {
+                commands
+                    .entity(entity)
+                    .insert((TerrainWaiting, RigidBodyDisabled));
+            }
+            (true, true) => {
+                commands
+                    .entity(entity)
+                    .remove::<(TerrainWaiting, RigidBodyDisabled)>();
+            }
+            _ => {}
+        }
+    }
+}
+
+// 世界高度之外的区块永远没有地形 当作已经加载 不然跳出世界顶部或者掉进虚空的物体会一直冻结
+fn terrain_loaded(collider_manager: &ColliderManager, chunk_key: ChunkKey) -> bool {
+    !WORLD_HEIGHT.contains_chunk_y(chunk_key.0.y)
+        || collider_manager.shapes.contains_key(&chunk_key)
+}
+
 fn cross_through_check(
     mut commands: Commands,
     mut context: ResMut<RapierContext>,
     query: Query<
-        (Entity, &RapierRigidBodyHandle, &Transform, &CossTroughCheck),
-        Without<CossTroughFixed>,
+        (
+            Entity,
+            &RapierRigidBodyHandle,
+            &Transform,
+            &CossTroughCheck,
+            &DimensionId,
+        ),
+        (Without<CossTroughFixed>, Without<TerrainWaiting>),
     >,
     chunk_map: Res<ChunkMap>,
 ) {
-    for (entity, body_handle, trf, _) in query.iter() {
-        let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(pos_to_center(trf.translation));
-        if let Some(test_voxel) = chunk_map.get_block(chunk_key, xyz) {
-            if test_voxel.id != Voxel::EMPTY.id {
-                if let Some((new_chunk_key, new_xyz)) =
-                    chunk_map.find_closest_block_y(chunk_key, xyz, Voxel::EMPTY.id)
-                {
-                    if let Some(body) = context.bodies.get_mut(body_handle.0) {
-                        body.set_body_type(RigidBodyType::KinematicPositionBased, true);
-                        let mut pos = chunk_key_any_xyz_to_vec3(new_chunk_key, new_xyz);
-                        pos.x = trf.translation.x;
-                        pos.z = trf.translation.z;
-                        commands.entity(entity).insert(CossTroughFixed(pos));
-                    }
-                }
+    for (entity, body_handle, trf, check, dimension) in query.iter() {
+        let feet = check.feet(trf.translation);
+        // 没有碰撞体的方块 可以站在里面
+        let stuck = (0..check.height).any(|dy| {
+            chunk_map
+                .get_block_at(*dimension, feet.offset(IVec3::Y * dy))
+                .map_or(false, |voxel| voxel.has_collider())
+        });
+        if !stuck {
+            continue;
+        }
+        let Some(free) =
+            chunk_map.find_free_space(*dimension, feet, check.height, UNSTUCK_DISTANCE)
+        else {
+            continue;
+        };
+        if let Some(body) = context.bodies.get_mut(body_handle.0) {
+            body.set_body_type(RigidBodyType::KinematicPositionBased, true);
+            body.set_linvel(Vector::zeros(), true);
+            // 最低点放在空位的底面上
+            let mut pos = free.center();
+            pos.y = free.0.y as f32 + check.bottom;
+            // 同一个方块中时保持水平位置
+            if free.0.x == feet.0.x && free.0.z == feet.0.z {
+                pos.x = trf.translation.x;
+                pos.z = trf.translation.z;
             }
+            commands.entity(entity).insert(CossTroughFixed(pos));
         }
     }
 }
@@ -77,3 +171,25 @@ fn cross_through_fixed(
         }
     }
 }
+
+#[test]
+fn test_terrain_loaded() {
+    use crate::{voxel_world::voxel::Voxel, CHUNK_SIZE};
+
+    use super::chunk_collider::ChunkColliderShape;
+
+    let mut collider_manager = ColliderManager::default();
+    let dimension = DimensionId(0);
+    let inside = ChunkKey(IVec3::new(0, WORLD_HEIGHT.min_chunk_y, 0), dimension);
+    assert!(!terrain_loaded(&collider_manager, inside));
+    let voxels = vec![Voxel::EMPTY; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize];
+    collider_manager
+        .shapes
+        .insert(inside, ChunkColliderShape::new(&voxels));
+    assert!(terrain_loaded(&collider_manager, inside));
+    // 世界顶部之上和底部之下
+    let above = ChunkKey(IVec3::new(0, WORLD_HEIGHT.max_chunk_y + 1, 0), dimension);
+    let below = ChunkKey(IVec3::new(0, WORLD_HEIGHT.min_chunk_y - 1, 0), dimension);
+    assert!(terrain_loaded(&collider_manager, above));
+    assert!(terrain_loaded(&collider_manager, below));
+}
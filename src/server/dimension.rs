@@ -0,0 +1,130 @@
+// 维度切换
+// 管理员通过命令把玩家送到另一个维度 玩家的碰撞分组 位置和所在维度都会改变
+// 原来维度中的玩家看到他离开 新维度中的玩家看到他出现
+
+use bevy::prelude::{Entity, Event, EventReader, Plugin, Query, ResMut, Transform, Update, Vec3};
+use bevy_rapier3d::prelude::{CollisionGroups, Group};
+use bevy_renet::renet::RenetServer;
+
+use crate::voxel_world::{
+    dimension::{dimension_collision_groups, get_dimension, DimensionId},
+    player_state::PlayerOnTimeState,
+};
+
+use super::{
+    message_def::{server_messages::ServerMessages, ServerChannel},
+    player::{Player, ServerLobby},
+};
+
+#[derive(Debug, Event)]
+pub struct ChangeDimensionEvent {
+    pub client_id: u64,
+    pub dimension: DimensionId,
+}
+
+pub struct ServerDimensionPlugin;
+
+impl Plugin for ServerDimensionPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_event::<ChangeDimensionEvent>();
+        app.add_systems(Update, change_dimension_system);
+    }
+}
+
+pub fn change_dimension_system(
+    mut events: EventReader<ChangeDimensionEvent>,
+    mut server: ResMut<RenetServer>,
+    mut server_lobby: ResMut<ServerLobby>,
+    mut query: Query<(
+        Entity,
+        &Player,
+        &mut Transform,
+        &mut PlayerOnTimeState,
+        &mut DimensionId,
+        &mut CollisionGroups,
+    )>,
+) {
+    for ChangeDimensionEvent {
+        client_id,
+        dimension,
+    } in events.iter()
+    {
+        let Some(config) = get_dimension(*dimension) else {
+            continue;
+        };
+        let Some(entity) = server_lobby.players.get(client_id).copied() else {
+            continue;
+        };
+        let old_dimension = server_lobby.dimension_of(*client_id);
+        if old_dimension == *dimension {
+            continue;
+        }
+        let Ok((_, player, mut transform, mut player_state, mut player_dimension, mut groups)) =
+            query.get_mut(entity)
+        else {
+            continue;
+        };
+        transform.translation = Vec3::from(config.spawn);
+        player_state.0.dimension = *dimension;
+        player_state.0.position = config.spawn;
+        *player_dimension = *dimension;
+        *groups =
+            dimension_collision_groups(*dimension, Group::GROUP_3, Group::GROUP_1 | Group::GROUP_3);
+        let username = player.username.clone();
+        println!("{}从维度{:?}进入{}", username, old_dimension, config.name);
+
+        // 1. 原来维度中的玩家移除这个玩家
+        server_lobby.dimensions.insert(*client_id, *dimension);
+        let message = bincode::serialize(&ServerMessages::PlayerRemove { id: *client_id }).unwrap();
+        server_lobby.broadcast_in(
+            &mut server,
+            old_dimension,
+            ServerChannel::ServerMessages,
+            message,
+        );
+        // 2. 这个玩家移除原来维度中的玩家 然后切换维度
+        for other in server_lobby.clients_in(old_dimension) {
+            let message = bincode::serialize(&ServerMessages::PlayerRemove { id: other }).unwrap();
+            server.send_message(*client_id, ServerChannel::ServerMessages, message);
+        }
+        let message = bincode::serialize(&ServerMessages::DimensionChange {
+            dimension: *dimension,
+            name: config.name.clone(),
+            translation: config.spawn,
+        })
+        .unwrap();
+        server.send_message(*client_id, ServerChannel::ServerMessages, message);
+        // 3. 这个玩家创建新维度中的玩家
+        for other in server_lobby.clients_in(*dimension) {
+            if other == *client_id {
+                continue;
+            }
+            let Some(other_entity) = server_lobby.players.get(&other) else {
+                continue;
+            };
+            if let Ok((_, other_player, other_transform, _, _, _)) = query.get(*other_entity) {
+                let message = bincode::serialize(&ServerMessages::PlayerCreate {
+                    id: other,
+                    entity: *other_entity,
+                    translation: other_transform.translation.into(),
+                    username: other_player.username.clone(),
+                })
+                .unwrap();
+                server.send_message(*client_id, ServerChannel::ServerMessages, message);
+            }
+        }
+        // 4. 新维度中的玩家创建这个玩家
+        let message = bincode::serialize(&ServerMessages::PlayerCreate {
+            id: *client_id,
+            entity,
+            translation: config.spawn,
+            username,
+        })
+        .unwrap();
+        for other in server_lobby.clients_in(*dimension) {
+            if other != *client_id {
+                server.send_message(other, ServerChannel::ServerMessages, message.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,283 @@
+// 下落的方块
+// 沙子 雪这类方块下面空了以后 变成刚体往下掉 落地后重新变成方块
+// 落地的位置被占用时 按照掉落配置变成掉落物
+
+use bevy::{
+    prelude::{
+        Commands, Component, Entity, EventReader, EventWriter, IVec3, IntoSystemConfigs, Local,
+        Plugin, PreUpdate, Query, Res, ResMut, Resource, Transform, Update, Vec3,
+    },
+    utils::HashMap,
+};
+use bevy_rapier3d::prelude::Velocity;
+use bevy_renet::renet::RenetServer;
+
+use crate::{
+    staff::StaffInfoStroge,
+    voxel_world::{
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        dimension::DimensionId,
+        pos::{BlockPos, LocalPos},
+        voxel::Voxel,
+        world_height::WORLD_HEIGHT,
+    },
+};
+
+use super::{
+    block_update::{offset_block_pos, BlockChangeEvent, BlockUpdateQueue},
+    message_def::{filled_object_message::FilledObjectMessage, ServerChannel},
+    object_filing::{spawn_drop_body, ObjectFillEvent},
+    player::ServerLobby,
+    terrain_physics::ColliderSystem,
+};
+
+// 掉到世界最低高度以下这么多的方块直接删除
+const FALLING_BLOCK_BELOW_WORLD: f32 = 8.0;
+// 速度小于这个值时认为已经落地
+const FALLING_BLOCK_REST_SPEED: f32 = 0.05;
+// 没有下落方块后 继续发送几次空列表 防止客户端丢包残留
+const EMPTY_SYNC_TIMES: usize = 10;
+
+#[derive(Debug, Component, Clone)]
+pub struct FallingBlock {
+    pub voxel: Voxel,
+}
+
+// 已经变成空气 等地形碰撞体更新之后再生成的下落方块
+// 不然刚体会生成在还没删除的碰撞体里面 被推到旁边
+#[derive(Debug, Default, Resource)]
+pub struct PendingFallingBlocks(Vec<(Vec3, DimensionId, Voxel)>);
+
+// 方块可以从中掉下去
+fn can_fall_through(voxel: Voxel) -> bool {
+    !voxel.has_collider()
+}
+
+// 方块被改变时 检查它自己和上面的方块要不要掉下来
+// 放在半空中的沙子 和下面被挖空的沙子都会掉
+fn falling_block_listen(
+    mut events: EventReader<BlockChangeEvent>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut block_update_queue: ResMut<BlockUpdateQueue>,
+    mut pending: ResMut<PendingFallingBlocks>,
+) {
+    for event in events.iter() {
+        check_falling(
+            chunk_map.as_mut(),
+            block_update_queue.as_mut(),
+            pending.as_mut(),
+            event.chunk_key,
+            event.pos,
+        );
+    }
+}
+
+// 检查改变的方块和它上面的方块 悬空的变成空气 等待生成下落的方块
+fn check_falling(
+    chunk_map: &mut ChunkMap,
+    block_update_queue: &mut BlockUpdateQueue,
+    pending: &mut PendingFallingBlocks,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+) {
+    for offset in [IVec3::ZERO, IVec3::Y] {
+        let (chunk_key, pos) = offset_block_pos(chunk_key, pos, offset);
+        let Some(voxel) = chunk_map.get_block(chunk_key, pos) else {
+            continue;
+        };
+        if !voxel.has_gravity() {
+            continue;
+        }
+        let (below_key, below_pos) = offset_block_pos(chunk_key, pos, IVec3::NEG_Y);
+        match chunk_map.get_block(below_key, below_pos) {
+            Some(below) if can_fall_through(below) => {}
+            _ => continue,
+        }
+        // 更上面的方块会在这次修改产生的事件中处理
+        if block_update_queue.set_block(chunk_map, chunk_key, pos, Voxel::EMPTY) {
+            pending
+                .0
+                .push((chunk_key.block(LocalPos(pos)).center(), chunk_key.1, voxel));
+        }
+    }
+}
+
+// 在地形碰撞体更新之后生成下落的方块
+fn spawn_falling_block(mut commands: Commands, mut pending: ResMut<PendingFallingBlocks>) {
+    for (center, dimension, voxel) in pending.0.drain(..) {
+        spawn_drop_body(&mut commands, 0.45, center, dimension)
+            .insert(Velocity::zero())
+            .insert(FallingBlock { voxel });
+    }
+}
+
+// 落地后重新变成方块
+fn falling_block_land(
+    mut commands: Commands,
+    query: Query<(Entity, &FallingBlock, &Transform, &Velocity, &DimensionId)>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut block_update_queue: ResMut<BlockUpdateQueue>,
+    staff_info_stroge: Res<StaffInfoStroge>,
+    mut fill_event: EventWriter<ObjectFillEvent>,
+) {
+    for (entity, falling_block, trf, velocity, dimension) in query.iter() {
+        if trf.translation.y < WORLD_HEIGHT.min_y() as f32 - FALLING_BLOCK_BELOW_WORLD {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if velocity.linvel.length() > FALLING_BLOCK_REST_SPEED {
+            continue;
+        }
+        let block = BlockPos::from_vec3(trf.translation);
+        let (chunk_key, LocalPos(pos)) = ChunkKey::from_block(block, *dimension);
+        let (below_key, below_pos) = offset_block_pos(chunk_key, pos, IVec3::NEG_Y);
+        // 下面还可以掉下去 只是刚生成时速度还是0
+        match chunk_map.get_block(below_key, below_pos) {
+            Some(below) if can_fall_through(below) => continue,
+            None => continue,
+            _ => {}
+        }
+        let Some(target) = chunk_map.get_block(chunk_key, pos) else {
+            continue;
+        };
+        if can_fall_through(target) {
+            block_update_queue.set_block(chunk_map.as_mut(), chunk_key, pos, falling_block.voxel);
+        } else if let Some(staff_list) = staff_info_stroge.voxel_to_staff_list(falling_block.voxel)
+        {
+            for staff in staff_list.into_iter() {
+                fill_event.send(ObjectFillEvent {
+                    chunk_key,
+                    xyz: pos,
+                    center: block.center(),
+                    staff,
+                });
+            }
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+// 下落的方块数量很少 直接广播给同一个维度中全部的玩家
+fn sync_falling_block_to_client(
+    query: Query<(Entity, &FallingBlock, &Transform, &DimensionId)>,
+    mut server: ResMut<RenetServer>,
+    server_lobby: Res<ServerLobby>,
+    mut empty_times: Local<usize>,
+) {
+    if query.is_empty() {
+        if *empty_times >= EMPTY_SYNC_TIMES {
+            return;
+        }
+        *empty_times += 1;
+    } else {
+        *empty_times = 0;
+    }
+    let mut lists: HashMap<DimensionId, Vec<(Entity, Voxel, [f32; 3])>> = HashMap::new();
+    // 没有下落方块的维度也要发送空列表
+    for dimension in server_lobby.dimensions.values() {
+        lists.entry(*dimension).or_default();
+    }
+    for (entity, falling_block, trf, dimension) in query.iter() {
+        lists.entry(*dimension).or_default().push((
+            entity,
+            falling_block.voxel,
+            trf.translation.to_array(),
+        ));
+    }
+    for (dimension, list) in lists {
+        let message = bincode::serialize(&FilledObjectMessage::SyncFallingBlock(list)).unwrap();
+        server_lobby.broadcast_in(
+            &mut server,
+            dimension,
+            ServerChannel::FilledObjectMessage,
+            message,
+        );
+    }
+}
+
+pub struct FallingBlockPlugin;
+
+impl Plugin for FallingBlockPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(PendingFallingBlocks::default());
+        app.add_systems(
+            PreUpdate,
+            spawn_falling_block.after(ColliderSystem::ColliderUpdate),
+        );
+        app.add_systems(
+            Update,
+            (
+                falling_block_listen,
+                falling_block_land,
+                sync_falling_block_to_client,
+            )
+                .chain(),
+        );
+    }
+}
+
+#[test]
+fn test_can_fall_through() {
+    use crate::voxel_world::voxel::{Empty, Sand, Stone, TallGrass, VoxelMaterial, Water};
+    assert!(can_fall_through(Empty::into_voxel()));
+    assert!(can_fall_through(Water::into_voxel()));
+    assert!(can_fall_through(Voxel::flowing_water(3)));
+    assert!(can_fall_through(TallGrass::into_voxel()));
+    assert!(!can_fall_through(Stone::into_voxel()));
+    assert!(!can_fall_through(Sand::into_voxel()));
+}
+
+#[test]
+fn test_check_falling() {
+    use crate::{
+        voxel_world::voxel::{Sand, Stone, VoxelMaterial},
+        CHUNK_SIZE_U32,
+    };
+    let key = ChunkKey(IVec3::ZERO, DimensionId::OVERWORLD);
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(key, vec![Voxel::EMPTY; CHUNK_SIZE_U32.pow(3) as usize]);
+    let mut block_update_queue = BlockUpdateQueue::default();
+    let mut pending = PendingFallingBlocks::default();
+    let sand = Sand::into_voxel();
+
+    // 放在石头上的沙子不会掉
+    block_update_queue.set_block(&mut chunk_map, key, [5, 4, 5], Stone::into_voxel());
+    block_update_queue.set_block(&mut chunk_map, key, [5, 5, 5], sand);
+    check_falling(
+        &mut chunk_map,
+        &mut block_update_queue,
+        &mut pending,
+        key,
+        [5, 5, 5],
+    );
+    assert!(pending.0.is_empty());
+    assert_eq!(chunk_map.get_block(key, [5, 5, 5]), Some(sand));
+
+    // 放在半空中的沙子会掉
+    block_update_queue.set_block(&mut chunk_map, key, [8, 5, 5], sand);
+    check_falling(
+        &mut chunk_map,
+        &mut block_update_queue,
+        &mut pending,
+        key,
+        [8, 5, 5],
+    );
+    assert_eq!(pending.0.len(), 1);
+    assert_eq!(pending.0[0].1, DimensionId::OVERWORLD);
+    assert_eq!(pending.0[0].2, sand);
+    assert_eq!(pending.0[0].0, key.block(LocalPos([8, 5, 5])).center());
+    assert_eq!(chunk_map.get_block(key, [8, 5, 5]), Some(Voxel::EMPTY));
+
+    // 下面的石头被挖掉 上面的沙子也会掉
+    block_update_queue.set_block(&mut chunk_map, key, [5, 4, 5], Voxel::EMPTY);
+    check_falling(
+        &mut chunk_map,
+        &mut block_update_queue,
+        &mut pending,
+        key,
+        [5, 4, 5],
+    );
+    assert_eq!(pending.0.len(), 2);
+    assert_eq!(chunk_map.get_block(key, [5, 5, 5]), Some(Voxel::EMPTY));
+}
@@ -0,0 +1,189 @@
+// 流体模拟
+// 水源是 Water 流动的水是 FlowingWater 水位 1..WATER_SOURCE_LEVEL
+// 只处理被激活的位置 方块改变时激活它和周围的位置 水位变化后继续激活周围 直到稳定
+
+use bevy::{
+    prelude::{EventReader, IVec3, IntoSystemConfigs, Plugin, Res, ResMut, Resource, Update},
+    time::{Time, Timer, TimerMode},
+    utils::HashSet,
+};
+
+use crate::voxel_world::{
+    chunk::ChunkKey,
+    chunk_map::ChunkMap,
+    voxel::{Voxel, WATER_SOURCE_LEVEL},
+};
+
+use super::block_update::{offset_block_pos, BlockChangeEvent, BlockUpdateQueue, NEIGHBOR_OFFSETS};
+
+// 流体更新的间隔
+pub const FLUID_TICK_MILLIS: u64 = 250;
+// 每次最多处理的位置数量
+pub const FLUID_MAX_UPDATES_PER_TICK: usize = 4096;
+
+const HORIZONTAL_OFFSETS: [IVec3; 4] = [IVec3::X, IVec3::NEG_X, IVec3::Z, IVec3::NEG_Z];
+
+#[derive(Debug, Resource)]
+pub struct FluidQueue {
+    pub timer: Timer,
+    // 下一次要检查的位置
+    pub active: HashSet<(ChunkKey, [u32; 3])>,
+}
+
+impl Default for FluidQueue {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(
+                bevy::utils::Duration::from_millis(FLUID_TICK_MILLIS),
+                TimerMode::Repeating,
+            ),
+            active: HashSet::new(),
+        }
+    }
+}
+
+impl FluidQueue {
+    // 激活位置以及周围的位置
+    pub fn activate(&mut self, chunk_key: ChunkKey, pos: [u32; 3]) {
+        self.active.insert((chunk_key, pos));
+        for offset in NEIGHBOR_OFFSETS {
+            self.active.insert(offset_block_pos(chunk_key, pos, offset));
+        }
+    }
+}
+
+// 水可以流进去的方块
+fn fluid_replaceable(voxel: Voxel) -> bool {
+    voxel.id == Voxel::EMPTY.id || (voxel.is_water() && voxel.water_level() < WATER_SOURCE_LEVEL)
+}
+
+// 可以托住水的方块 水在上面才会向四周扩散
+fn fluid_support(voxel: Voxel) -> bool {
+    voxel.has_collider()
+}
+
+/**
+ * 计算某个位置下一次的状态
+ * sides : 四周的方块以及它们下面的方块
+ */
+pub fn next_fluid_state(
+    center: Voxel,
+    above: Voxel,
+    below: Voxel,
+    sides: [(Voxel, Voxel); 4],
+) -> Voxel {
+    if !fluid_replaceable(center) {
+        return center;
+    }
+    // 上面有水时 水往下落
+    if above.is_water() {
+        return Voxel::flowing_water(WATER_SOURCE_LEVEL - 1);
+    }
+    let mut source_count = 0;
+    let mut level = 0;
+    for (side, side_below) in sides {
+        let side_level = side.water_level();
+        if side_level == WATER_SOURCE_LEVEL {
+            source_count += 1;
+        }
+        // 正在下落的水不向四周扩散
+        if side_level == WATER_SOURCE_LEVEL || (side_level > 0 && fluid_support(side_below)) {
+            level = level.max(side_level - 1);
+        }
+    }
+    // 两个水源之间 并且下面托得住 会形成新的水源
+    if source_count >= 2 && (fluid_support(below) || below.water_level() == WATER_SOURCE_LEVEL) {
+        return Voxel::flowing_water(WATER_SOURCE_LEVEL);
+    }
+    Voxel::flowing_water(level)
+}
+
+fn get_voxel(chunk_map: &ChunkMap, chunk_key: ChunkKey, pos: [u32; 3]) -> Voxel {
+    // 没有加载的区块当作墙壁
+    chunk_map.get_block(chunk_key, pos).unwrap_or(Voxel::FILLED)
+}
+
+fn fluid_listen(mut events: EventReader<BlockChangeEvent>, mut fluid_queue: ResMut<FluidQueue>) {
+    for event in events.iter() {
+        fluid_queue.activate(event.chunk_key, event.pos);
+    }
+}
+
+fn fluid_tick(
+    time: Res<Time>,
+    mut fluid_queue: ResMut<FluidQueue>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut block_update_queue: ResMut<BlockUpdateQueue>,
+) {
+    fluid_queue.timer.tick(time.delta());
+    if !fluid_queue.timer.finished() || fluid_queue.active.is_empty() {
+        return;
+    }
+    let mut list: Vec<(ChunkKey, [u32; 3])> = fluid_queue.active.drain().collect();
+    if list.len() > FLUID_MAX_UPDATES_PER_TICK {
+        for item in list.split_off(FLUID_MAX_UPDATES_PER_TICK) {
+            fluid_queue.active.insert(item);
+        }
+    }
+    // 先全部计算 再统一写入 结果和处理顺序无关
+    let mut changes = Vec::new();
+    for (chunk_key, pos) in list {
+        if !chunk_map.map_data.contains_key(&chunk_key) {
+            continue;
+        }
+        let at = |offset: IVec3| {
+            let (key, p) = offset_block_pos(chunk_key, pos, offset);
+            get_voxel(&chunk_map, key, p)
+        };
+        let center = get_voxel(&chunk_map, chunk_key, pos);
+        let sides = HORIZONTAL_OFFSETS.map(|offset| (at(offset), at(offset + IVec3::NEG_Y)));
+        let next = next_fluid_state(center, at(IVec3::Y), at(IVec3::NEG_Y), sides);
+        if next != center {
+            changes.push((chunk_key, pos, next));
+        }
+    }
+    for (chunk_key, pos, voxel) in changes {
+        if block_update_queue.set_block(chunk_map.as_mut(), chunk_key, pos, voxel) {
+            fluid_queue.activate(chunk_key, pos);
+        }
+    }
+}
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(FluidQueue::default());
+        app.add_systems(Update, (fluid_listen, fluid_tick).chain());
+    }
+}
+
+#[test]
+fn test_next_fluid_state() {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial, Water};
+    let water = Water::into_voxel();
+    let stone = Stone::into_voxel();
+    let empty = Voxel::EMPTY;
+    let wall = (stone, stone);
+    // 旁边是水源 流出一格
+    let next = next_fluid_state(empty, empty, stone, [(water, water), wall, wall, wall]);
+    assert_eq!(next.water_level(), WATER_SOURCE_LEVEL - 1);
+    // 两个水源中间形成水源
+    let next = next_fluid_state(
+        empty,
+        empty,
+        stone,
+        [(water, stone), (water, stone), wall, wall],
+    );
+    assert_eq!(next, water);
+    // 下落中的水不向四周扩散
+    let falling = Voxel::flowing_water(3);
+    let next = next_fluid_state(empty, empty, stone, [(falling, empty), wall, wall, wall]);
+    assert_eq!(next, empty);
+    // 没有来源的流水会消失
+    let next = next_fluid_state(Voxel::flowing_water(5), empty, stone, [wall; 4]);
+    assert_eq!(next, empty);
+    // 上面有水时往下落
+    let next = next_fluid_state(empty, water, empty, [wall; 4]);
+    assert_eq!(next.water_level(), WATER_SOURCE_LEVEL - 1);
+}
@@ -0,0 +1,294 @@
+// 树叶枯萎
+// 方块改变时 检查附近的树叶 没有通过树叶连接到 LEAF_DECAY_DISTANCE 以内的原木的树叶会慢慢枯萎
+// 枯萎的树叶按照掉落配置变成掉落物(苹果 木棍等) 修改通过 BlockUpdateQueue 批量同步给客户端
+
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::{
+        EventReader, EventWriter, IVec3, IntoSystemConfigs, Plugin, Res, ResMut, Resource, Update,
+    },
+    time::{Time, Timer, TimerMode},
+    utils::HashSet,
+};
+
+use crate::{
+    staff::StaffInfoStroge,
+    voxel_world::{chunk::ChunkKey, chunk_map::ChunkMap, pos::LocalPos, voxel::Voxel},
+};
+
+use super::{
+    block_update::{offset_block_pos, BlockChangeEvent, BlockUpdateQueue, NEIGHBOR_OFFSETS},
+    object_filing::ObjectFillEvent,
+};
+
+// 树叶到原木的最远距离 自然生成的树最远的树叶不会超过这个距离
+pub const LEAF_DECAY_DISTANCE: i32 = 8;
+// 检查的间隔
+pub const LEAF_DECAY_TICK_MILLIS: u64 = 200;
+// 每次最多枯萎的树叶数量 让树叶一片一片地消失
+pub const LEAF_DECAY_PER_TICK: usize = 16;
+// 每次最多检查的树叶数量
+pub const LEAF_DECAY_CHECKS_PER_TICK: usize = 256;
+
+#[derive(Debug, Resource)]
+pub struct LeafDecayQueue {
+    pub timer: Timer,
+    // 等待检查的树叶
+    pub pending: HashSet<(ChunkKey, [u32; 3])>,
+    // 这一轮枯萎的树叶 它们的修改只需要检查相邻的树叶
+    pub decayed: HashSet<(ChunkKey, [u32; 3])>,
+}
+
+impl Default for LeafDecayQueue {
+    fn default() -> Self {
+        Self {
+            timer: Timer::new(
+                bevy::utils::Duration::from_millis(LEAF_DECAY_TICK_MILLIS),
+                TimerMode::Repeating,
+            ),
+            pending: HashSet::new(),
+            decayed: HashSet::new(),
+        }
+    }
+}
+
+/**
+ * 树叶是否通过树叶连接到 max_distance 以内的原木
+ * get : 相对于这片树叶的位置上的方块 区块没有加载时返回 None 当作连接着
+ */
+pub fn leaf_connected(get: impl Fn(IVec3) -> Option<Voxel>, max_distance: i32) -> bool {
+    let mut visited = HashSet::new();
+    visited.insert(IVec3::ZERO);
+    let mut queue = VecDeque::new();
+    queue.push_back((IVec3::ZERO, 0));
+    while let Some((pos, distance)) = queue.pop_front() {
+        for offset in NEIGHBOR_OFFSETS {
+            let next = pos + offset;
+            if !visited.insert(next) {
+                continue;
+            }
+            match get(next) {
+                None => return true,
+                Some(voxel) if voxel.is_log() => return true,
+                Some(voxel) if voxel.is_leaf() && distance + 1 < max_distance => {
+                    queue.push_back((next, distance + 1));
+                }
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
+fn get_offset_block(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    offset: IVec3,
+) -> Option<Voxel> {
+    let (key, p) = offset_block_pos(chunk_key, pos, offset);
+    chunk_map.get_block(key, p)
+}
+
+// 方块改变后 把可能失去连接的树叶加入检查
+fn leaf_decay_listen(
+    mut events: EventReader<BlockChangeEvent>,
+    chunk_map: Res<ChunkMap>,
+    mut leaf_decay_queue: ResMut<LeafDecayQueue>,
+) {
+    for event in events.iter() {
+        queue_leaves_near(
+            &chunk_map,
+            &mut leaf_decay_queue,
+            event.chunk_key,
+            event.pos,
+        );
+    }
+}
+
+fn queue_leaves_near(
+    chunk_map: &ChunkMap,
+    leaf_decay_queue: &mut LeafDecayQueue,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+) {
+    // 枯萎的树叶只影响相邻的树叶 其他修改(比如砍掉原木)要检查范围内全部的树叶
+    // 旁边没有树叶时也要移除记录 不然会一直留在里面
+    let radius = if leaf_decay_queue.decayed.remove(&(chunk_key, pos)) {
+        1
+    } else {
+        LEAF_DECAY_DISTANCE
+    };
+    let near_leaf = NEIGHBOR_OFFSETS.iter().any(|offset| {
+        get_offset_block(chunk_map, chunk_key, pos, *offset).map_or(false, |v| v.is_leaf())
+    });
+    if !near_leaf {
+        return;
+    }
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                let (key, p) = offset_block_pos(chunk_key, pos, IVec3::new(x, y, z));
+                if chunk_map.get_block(key, p).map_or(false, |v| v.is_leaf()) {
+                    leaf_decay_queue.pending.insert((key, p));
+                }
+            }
+        }
+    }
+}
+
+fn leaf_decay_tick(
+    time: Res<Time>,
+    mut leaf_decay_queue: ResMut<LeafDecayQueue>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut block_update_queue: ResMut<BlockUpdateQueue>,
+    staff_info_stroge: Res<StaffInfoStroge>,
+    mut fill_event: EventWriter<ObjectFillEvent>,
+) {
+    leaf_decay_queue.timer.tick(time.delta());
+    if !leaf_decay_queue.timer.finished() || leaf_decay_queue.pending.is_empty() {
+        return;
+    }
+    for (chunk_key, pos, voxel) in decay_leaves(
+        &mut leaf_decay_queue,
+        chunk_map.as_mut(),
+        block_update_queue.as_mut(),
+    ) {
+        if let Some(staff_list) = staff_info_stroge.voxel_to_staff_list(voxel) {
+            let center = chunk_key.block(LocalPos(pos)).center();
+            for staff in staff_list.into_iter() {
+                fill_event.send(ObjectFillEvent {
+                    chunk_key,
+                    xyz: pos,
+                    center,
+                    staff,
+                });
+            }
+        }
+    }
+}
+
+// 检查一批等待的树叶 返回这次枯萎了的树叶
+fn decay_leaves(
+    leaf_decay_queue: &mut LeafDecayQueue,
+    chunk_map: &mut ChunkMap,
+    block_update_queue: &mut BlockUpdateQueue,
+) -> Vec<(ChunkKey, [u32; 3], Voxel)> {
+    let batch: Vec<(ChunkKey, [u32; 3])> = leaf_decay_queue
+        .pending
+        .iter()
+        .take(LEAF_DECAY_CHECKS_PER_TICK)
+        .copied()
+        .collect();
+    let mut ret = Vec::new();
+    for item in batch {
+        // 剩下的留到下一次
+        if ret.len() >= LEAF_DECAY_PER_TICK {
+            break;
+        }
+        leaf_decay_queue.pending.remove(&item);
+        let (chunk_key, pos) = item;
+        let Some(voxel) = chunk_map.get_block(chunk_key, pos) else {
+            continue;
+        };
+        if !voxel.is_leaf() {
+            continue;
+        }
+        let connected = leaf_connected(
+            |offset| get_offset_block(chunk_map, chunk_key, pos, offset),
+            LEAF_DECAY_DISTANCE,
+        );
+        if connected {
+            continue;
+        }
+        if !block_update_queue.set_block(chunk_map, chunk_key, pos, Voxel::EMPTY) {
+            continue;
+        }
+        leaf_decay_queue.decayed.insert(item);
+        ret.push((chunk_key, pos, voxel));
+    }
+    ret
+}
+
+pub struct LeafDecayPlugin;
+
+impl Plugin for LeafDecayPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource(LeafDecayQueue::default());
+        app.add_systems(Update, (leaf_decay_listen, leaf_decay_tick).chain());
+    }
+}
+
+#[test]
+fn test_leaf_connected() {
+    use crate::voxel_world::voxel::{AppleLeaf, AppleWood, VoxelMaterial};
+    use bevy::utils::HashMap;
+    let leaf = AppleLeaf::into_voxel();
+    // 一排树叶 一端是原木
+    let mut world: HashMap<IVec3, Voxel> = HashMap::new();
+    for x in 0..6 {
+        world.insert(IVec3::new(x, 0, 0), leaf);
+    }
+    world.insert(IVec3::new(6, 0, 0), AppleWood::into_voxel());
+    let get = |p: IVec3| Some(*world.get(&p).unwrap_or(&Voxel::EMPTY));
+    // 第一片树叶到原木的距离是6
+    assert!(leaf_connected(get, 6));
+    assert!(!leaf_connected(get, 5));
+    // 没有加载的区块当作连接着
+    assert!(leaf_connected(|p| if p.y > 0 { None } else { get(p) }, 1));
+    // 原木被砍掉以后
+    world.remove(&IVec3::new(6, 0, 0));
+    let get = |p: IVec3| Some(*world.get(&p).unwrap_or(&Voxel::EMPTY));
+    assert!(!leaf_connected(get, LEAF_DECAY_DISTANCE));
+}
+
+#[test]
+fn test_leaf_decay_queue() {
+    use crate::{
+        voxel_world::{
+            dimension::DimensionId,
+            voxel::{AppleLeaf, AppleWood, VoxelMaterial},
+        },
+        CHUNK_SIZE_U32,
+    };
+    let key = ChunkKey(IVec3::ZERO, DimensionId::OVERWORLD);
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(key, vec![Voxel::EMPTY; CHUNK_SIZE_U32.pow(3) as usize]);
+    let mut block_update_queue = BlockUpdateQueue::default();
+    let mut queue = LeafDecayQueue::default();
+    // 一根原木连着一排树叶 另一棵树离得很远
+    let log = [1, 5, 5];
+    block_update_queue.set_block(&mut chunk_map, key, log, AppleWood::into_voxel());
+    for x in 2..6 {
+        block_update_queue.set_block(&mut chunk_map, key, [x, 5, 5], AppleLeaf::into_voxel());
+    }
+    block_update_queue.set_block(&mut chunk_map, key, [12, 5, 5], AppleWood::into_voxel());
+    block_update_queue.set_block(&mut chunk_map, key, [12, 6, 5], AppleLeaf::into_voxel());
+    // 连着原木的树叶不会枯萎
+    // 范围内有这棵树的树叶和那一排最右边的两片
+    queue_leaves_near(&chunk_map, &mut queue, key, [12, 5, 5]);
+    assert_eq!(queue.pending.len(), 3);
+    assert!(decay_leaves(&mut queue, &mut chunk_map, &mut block_update_queue).is_empty());
+    assert!(queue.pending.is_empty());
+
+    // 砍掉原木之后 整排树叶都枯萎
+    block_update_queue.set_block(&mut chunk_map, key, log, Voxel::EMPTY);
+    queue_leaves_near(&chunk_map, &mut queue, key, log);
+    assert_eq!(queue.pending.len(), 4);
+    let decayed = decay_leaves(&mut queue, &mut chunk_map, &mut block_update_queue);
+    assert_eq!(decayed.len(), 4);
+    assert_eq!(queue.decayed.len(), 4);
+    assert_eq!(chunk_map.get_block(key, [3, 5, 5]), Some(Voxel::EMPTY));
+
+    // 枯萎产生的修改 旁边已经没有树叶了 记录也要清掉
+    for (chunk_key, pos, _) in decayed {
+        queue_leaves_near(&chunk_map, &mut queue, chunk_key, pos);
+    }
+    assert!(queue.decayed.is_empty());
+    assert!(queue.pending.is_empty());
+    assert_eq!(
+        chunk_map.get_block(key, [12, 6, 5]),
+        Some(AppleLeaf::into_voxel())
+    );
+}
@@ -21,4 +21,35 @@ pub enum ChunkResult {
         pos: [u32; 3],
         voxel_type: Voxel,
     },
+    // 服务端模拟产生的一批修改 同一个区块内的
+    ChunkUpdateBatch {
+        chunk_key: ChunkKey,
+        changes: Vec<([u32; 3], Voxel)>,
+    },
+    // 一列降采样之后的数据 key 的 y 是请求时的值
+    LodColumn {
+        key: ChunkKey,
+        factor: u32,
+        data: (BitVec, Tree<Voxel>),
+    },
+    // 降采样之后全部是一样的体素
+    LodSame {
+        key: ChunkKey,
+        factor: u32,
+        voxel: Voxel,
+    },
+}
+
+impl ChunkResult {
+    pub fn chunk_key_mut(&mut self) -> &mut ChunkKey {
+        match self {
+            ChunkResult::ChunkData { key, .. } => key,
+            ChunkResult::UpdateChunkData { key, .. } => key,
+            ChunkResult::ChunkSame((key, _)) => key,
+            ChunkResult::ChunkUpdateOne { chunk_key, .. } => chunk_key,
+            ChunkResult::ChunkUpdateBatch { chunk_key, .. } => chunk_key,
+            ChunkResult::LodColumn { key, .. } => key,
+            ChunkResult::LodSame { key, .. } => key,
+        }
+    }
 }
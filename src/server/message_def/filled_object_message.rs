@@ -1,8 +1,12 @@
 use bevy::prelude::{Component, Entity};
 use serde::{Deserialize, Serialize};
 
+use crate::voxel_world::voxel::Voxel;
+
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum FilledObjectMessage {
     // 同步区块内掉落物
     SyncFilledObject(Vec<(Entity, usize, [f32; 3])>),
+    // 同步正在下落的方块
+    SyncFallingBlock(Vec<(Entity, Voxel, [f32; 3])>),
 }
@@ -1,6 +1,15 @@
-use bevy::prelude::{Component, Entity};
+use bevy::{
+    prelude::{Component, Entity},
+    utils::HashMap,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    client::voxels::voxel_materail_config::MaterailConfiguration,
+    staff::{rule::StaffRule, StaffConfigs},
+    voxel_world::{block_registry::BlockDrop, dimension::DimensionId},
+};
+
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ServerMessages {
     // 创建角色
@@ -14,4 +23,26 @@ pub enum ServerMessages {
     PlayerRemove {
         id: u64,
     },
+    // 当前玩家进入了另一个维度 客户端要清空已经加载的区块
+    DimensionChange {
+        dimension: DimensionId,
+        name: String,
+        translation: [f32; 3],
+    },
+    // 服务器重新加载了物品定义
+    StaffSync {
+        configs: StaffConfigs,
+    },
+    // 服务器重新加载了合成规则
+    StaffRulesSync {
+        rules: Vec<StaffRule<u32>>,
+    },
+    // 服务器重新加载了方块的掉落物
+    BlockDropsSync {
+        drops: HashMap<u8, Vec<BlockDrop>>,
+    },
+    // 服务器重新加载了方块的贴图列表和索引
+    MaterialSync {
+        config: MaterailConfiguration,
+    },
 }
@@ -1,4 +1,7 @@
-use bevy::prelude::{Commands, Entity, EventReader, Query, Res, ResMut, Transform, Vec3, With};
+use bevy::{
+    prelude::{Commands, Entity, EventReader, Query, Res, ResMut, Transform, Vec3, With},
+    utils::HashMap,
+};
 use bevy_rapier3d::{
     prelude::{RapierContext, RapierRigidBodyHandle},
     rapier::prelude::RigidBodyMassProps,
@@ -15,6 +18,7 @@ use crate::{
     },
     users::Username,
     voxel_world::{
+        dimension::{get_dimension, DimensionId},
         map_database::MapDataBase,
         player_state::{PlayerOnTimeState, PlayerState, StoragePlayerState},
     },
@@ -26,11 +30,20 @@ use self::{
 };
 
 pub mod async_chunk;
+pub mod block_update;
 pub mod chunk;
+pub mod chunk_collider;
+pub mod content_sync;
 pub mod cross_through_check;
+pub mod dimension;
+pub mod falling_block;
+pub mod fluid;
+pub mod leaf_decay;
 pub mod message_def;
 pub mod object_filing;
 pub mod player;
+pub mod pregen;
+pub mod random_tick;
 pub mod sp_physics;
 pub mod staff_rule_sync;
 pub mod terrain_physics;
@@ -44,7 +57,13 @@ pub fn server_connect_system(
     mut commands: Commands,
     mut server_events: EventReader<ServerEvent>,
     mut visualizer: ResMut<RenetServerVisualizer<200>>,
-    players: Query<(Entity, &Player, &Transform, &PlayerOnTimeState)>,
+    players: Query<(
+        Entity,
+        &Player,
+        &Transform,
+        &PlayerOnTimeState,
+        &DimensionId,
+    )>,
     mut server: ResMut<RenetServer>,
     mut server_lobby: ResMut<ServerLobby>,
     transport: Res<NetcodeServerTransport>,
@@ -64,8 +83,30 @@ pub fn server_connect_system(
                 }
                 server_lobby.names.insert(username.clone());
                 visualizer.add_client(*client_id);
-                // 1. 先通知 当前连接 其他的已经存在的用户数据
-                for (entity, player, transform, _) in players.iter() {
+                // -- 获取 到用户的信息
+                let mut player_state;
+                if let Some(state) = map_database.get_player_state(username.clone()) {
+                    // 获取历史数据
+                    player_state = state;
+                } else {
+                    // 第一次新建数据
+                    player_state = PlayerState::default();
+                    player_state.position = [0., 60., 0.];
+                }
+                if get_dimension(player_state.dimension).is_none() {
+                    // 保存的维度已经从配置中删除了 回到主世界
+                    println!("{}所在的维度{:?}不存在", username, player_state.dimension);
+                    player_state.dimension = DimensionId::OVERWORLD;
+                    if let Some(config) = get_dimension(DimensionId::OVERWORLD) {
+                        player_state.position = config.spawn;
+                    }
+                }
+                let dimension = player_state.dimension;
+                // 1. 先通知 当前连接 同一个维度中其他的已经存在的用户数据
+                for (entity, player, transform, _, player_dimension) in players.iter() {
+                    if *player_dimension != dimension {
+                        continue;
+                    }
                     let translation: [f32; 3] = transform.translation.into();
                     let message = bincode::serialize(&ServerMessages::PlayerCreate {
                         id: player.id,
@@ -78,16 +119,6 @@ pub fn server_connect_system(
                 }
                 // 2. 创建这个用户并(注意这里不用mesh 直接创建 一个物理对象就可以了。因为服务器不关心物体的姿态)
                 let transform = Transform::from_xyz(0., 60., 0.);
-                // -- 获取 到用户的信息
-                let mut player_state;
-                if let Some(state) = map_database.get_player_state(username.clone()) {
-                    // 获取历史数据
-                    player_state = state;
-                } else {
-                    // 第一次新建数据
-                    player_state = PlayerState::default();
-                    player_state.position = [0., 60., 0.];
-                }
 
                 let player_entity = server_create_player(
                     &mut commands,
@@ -97,6 +128,7 @@ pub fn server_connect_system(
                 );
                 // 角色进入游戏大厅缓存中
                 server_lobby.players.insert(*client_id, player_entity);
+                server_lobby.dimensions.insert(*client_id, dimension);
                 // 3. 通知全部客户端知道
                 let translation: [f32; 3] = transform.translation.into();
                 let message = bincode::serialize(&ServerMessages::PlayerCreate {
@@ -108,7 +140,12 @@ pub fn server_connect_system(
                 .unwrap();
                 // 发送物品栏 相关的同步信息
                 send_all_tool_bar(*client_id, &mut server, player_state);
-                server.broadcast_message(ServerChannel::ServerMessages, message);
+                server_lobby.broadcast_in(
+                    &mut server,
+                    dimension,
+                    ServerChannel::ServerMessages,
+                    message,
+                );
             }
             ServerEvent::ClientDisconnected { client_id, reason } => {
                 if let bevy_renet::renet::DisconnectReason::DisconnectedByServer = reason {
@@ -119,10 +156,11 @@ pub fn server_connect_system(
                 // 告诉所有人减少了一个用户
                 if let Some(player_entity) = server_lobby.players.remove(client_id) {
                     // 在用户断开连接是保存用户数据到数据库
-                    if let Ok((_, player, tf, state)) = players.get(player_entity) {
+                    if let Ok((_, player, tf, state, dimension)) = players.get(player_entity) {
                         let mut save_state = state.0.clone();
                         save_state.position =
                             [tf.translation.x, tf.translation.y, tf.translation.z];
+                        save_state.dimension = *dimension;
                         server_lobby.names.remove(&player.username.clone());
                         map_database.save_player_state(player.username.clone(), save_state);
                     }
@@ -131,7 +169,14 @@ pub fn server_connect_system(
 
                 let message =
                     bincode::serialize(&ServerMessages::PlayerRemove { id: *client_id }).unwrap();
-                server.broadcast_message(ServerChannel::ServerMessages, message);
+                if let Some(dimension) = server_lobby.dimensions.remove(client_id) {
+                    server_lobby.broadcast_in(
+                        &mut server,
+                        dimension,
+                        ServerChannel::ServerMessages,
+                        message,
+                    );
+                }
             }
         }
     }
@@ -180,14 +225,23 @@ pub fn deal_message_system(
     }
 }
 
-// 同步玩家角色的位置 头部
+// 同步玩家角色的位置 头部 只同步给同一个维度中的玩家
 
 pub fn sync_body_and_head(
-    players: Query<(Entity, &Player, &Transform, &YawValue, &PitchValue)>,
+    players: Query<(
+        Entity,
+        &Player,
+        &Transform,
+        &YawValue,
+        &PitchValue,
+        &DimensionId,
+    )>,
     mut server: ResMut<RenetServer>,
+    lobby: Res<ServerLobby>,
 ) {
-    let mut networked_entities = NetworkedEntities::default();
-    for (_, player, transform, yaw_value, pitch_value) in players.iter() {
+    let mut dimension_entities: HashMap<DimensionId, NetworkedEntities> = HashMap::new();
+    for (_, player, transform, yaw_value, pitch_value, dimension) in players.iter() {
+        let networked_entities = dimension_entities.entry(*dimension).or_default();
         networked_entities.client_ids.push(player.id);
         networked_entities
             .translations
@@ -195,6 +249,13 @@ pub fn sync_body_and_head(
         networked_entities.yaws.push(yaw_value.0);
         networked_entities.pitch.push(pitch_value.0);
     }
-    let sync_message = bincode::serialize(&networked_entities).unwrap();
-    server.broadcast_message(ServerChannel::NetworkedEntities, sync_message);
+    for (dimension, networked_entities) in dimension_entities {
+        let sync_message = bincode::serialize(&networked_entities).unwrap();
+        lobby.broadcast_in(
+            &mut server,
+            dimension,
+            ServerChannel::NetworkedEntities,
+            sync_message,
+        );
+    }
 }
@@ -21,11 +21,10 @@ use seldom_state::{
 
 use crate::{
     server::{
-        cross_through_check::CossTroughCheck,
         message_def::{tool_bar_message::ToolBarMessage, ServerChannel},
         player::Player,
     },
-    voxel_world::player_state::PlayerOnTimeState,
+    voxel_world::{dimension::DimensionId, player_state::PlayerOnTimeState},
     CLOSE_RANGE, NEAR_RANGE, PICK_SPEED,
 };
 
@@ -62,8 +61,18 @@ struct CloseTo {
 
 impl OptionTrigger for Near {
     type Param<'w, 's> = (
-        Query<'w, 's, (Entity, &'static Transform, &'static Player), Without<FilledObject>>,
-        Query<'w, 's, &'static Transform, With<FilledObject>>,
+        Query<
+            'w,
+            's,
+            (
+                Entity,
+                &'static Transform,
+                &'static Player,
+                &'static DimensionId,
+            ),
+            Without<FilledObject>,
+        >,
+        Query<'w, 's, (&'static Transform, &'static DimensionId), With<FilledObject>>,
     );
     type Some = Entity;
 
@@ -73,8 +82,12 @@ impl OptionTrigger for Near {
         (player_query, mut filled_query): Self::Param<'_, '_>,
     ) -> Option<Self::Some> {
         let mut min_pair: Option<(Entity, f32)> = None;
-        if let Ok(from) = filled_query.get_mut(entity) {
-            for (player_entity, to, _) in player_query.iter() {
+        if let Ok((from, dimension)) = filled_query.get_mut(entity) {
+            // 只会被同一个维度中的玩家捡起
+            for (player_entity, to, _, _) in player_query
+                .iter()
+                .filter(|(_, _, _, player_dimension)| *player_dimension == dimension)
+            {
                 let dis = from.translation.distance(to.translation);
                 if let Some((_, old_distance)) = min_pair.clone() {
                     if dis < old_distance {
@@ -136,25 +149,21 @@ fn load_up_state_machine(
     query: Query<(Entity, &FilledObject), (Without<StateMachine>, Without<ThrowObject>)>,
 ) {
     for (entity, _) in query.iter() {
-        commands
-            .entity(entity)
-            .insert(CossTroughCheck)
-            .insert(Idle)
-            .insert(
-                StateMachine::default()
-                    .trans_builder(Near { range: NEAR_RANGE }, |_: &Idle, entity: Entity| {
-                        Some(Follow {
-                            target: entity,
-                            speed: PICK_SPEED,
-                        })
+        commands.entity(entity).insert(Idle).insert(
+            StateMachine::default()
+                .trans_builder(Near { range: NEAR_RANGE }, |_: &Idle, entity: Entity| {
+                    Some(Follow {
+                        target: entity,
+                        speed: PICK_SPEED,
                     })
-                    .trans::<Follow>(Near { range: NEAR_RANGE }.not(), Idle)
-                    .trans_builder(CloseTo { range: CLOSE_RANGE }, |follow: &Follow, _| {
-                        Some(Picked {
-                            target: follow.target,
-                        })
-                    }),
-            );
+                })
+                .trans::<Follow>(Near { range: NEAR_RANGE }.not(), Idle)
+                .trans_builder(CloseTo { range: CLOSE_RANGE }, |follow: &Follow, _| {
+                    Some(Picked {
+                        target: follow.target,
+                    })
+                }),
+        );
     }
 }
 
@@ -1,5 +1,6 @@
 // 物体掉落相关
 use bevy::{
+    ecs::system::EntityCommands,
     prelude::{
         Commands, Component, Entity, Event, EventReader, IntoSystemConfigs, Plugin, Query, Res,
         ResMut, Transform, Update, Vec3,
@@ -8,7 +9,7 @@ use bevy::{
     utils::HashMap,
 };
 use bevy_rapier3d::prelude::{
-    Ccd, Collider, ColliderMassProperties, CollisionGroups, Group, LockedAxes, RigidBody, Sleeping,
+    Ccd, Collider, ColliderMassProperties, Group, LockedAxes, RigidBody, Sleeping,
 };
 use bevy_renet::renet::RenetServer;
 use rand::Rng;
@@ -16,10 +17,11 @@ use rand::Rng;
 use crate::{
     common::ServerClipSpheres,
     staff::{Staff, StaffInfoStroge},
-    tools::vec3_to_chunk_key_any_xyz,
     voxel_world::{
         chunk::{find_chunk_keys_array_by_sphere, generate_offset_array, ChunkKey},
+        dimension::{dimension_collision_groups, DimensionId},
         map_database::MapDataBase,
+        pos::ChunkPos,
     },
     PY_DISTANCE,
 };
@@ -27,6 +29,7 @@ use crate::{
 use self::{follow::ObjectFilingFollowPlugin, throw_object::ThrowObjectPlugin};
 
 use super::{
+    cross_through_check::CossTroughCheck,
     message_def::{filled_object_message::FilledObjectMessage, ServerChannel},
     terrain_physics::ColliderSystem,
 };
@@ -82,10 +85,9 @@ fn deal_object_filing(mut commands: Commands, mut fill_event: EventReader<Object
 }
 
 // 同步数据每个时刻的 位移信息
-fn update_filled_object_chunk_key(mut query: Query<(&mut FilledObject, &Transform)>) {
-    for (mut obj, trf) in query.iter_mut() {
-        let (chunk_key, _) = vec3_to_chunk_key_any_xyz(trf.translation);
-        obj.chunk_key = chunk_key;
+fn update_filled_object_chunk_key(mut query: Query<(&mut FilledObject, &Transform, &DimensionId)>) {
+    for (mut obj, trf, dimension) in query.iter_mut() {
+        obj.chunk_key = ChunkPos::from_vec3(trf.translation).key(*dimension);
     }
 }
 
@@ -154,29 +156,47 @@ pub fn gen_filled_object(
     staff: Staff,
 ) -> Entity {
     let mut rng = rand::thread_rng();
-
-    commands
-        .spawn(FilledObject {
+    // 添加随机偏移
+    let translation = Vec3::new(
+        center.x + rng.gen_range(-0.05..=0.05),
+        center.y + rng.gen_range(-0.05..=0.05),
+        center.z + rng.gen_range(-0.05..=0.05),
+    );
+    spawn_drop_body(commands, 0.05, translation, chunk_key.1)
+        .insert(FilledObject {
             chunk_key: chunk_key,
             staff: staff,
         })
-        .insert(Collider::cuboid(0.05, 0.05, 0.05))
+        // 扔出去的掉落物也要检查穿透
+        .insert(CossTroughCheck::OBJECT)
+        .id()
+}
+
+// 生成只和同一个维度的地形碰撞的刚体 掉落物和下落的方块共用
+pub fn spawn_drop_body<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    half_size: f32,
+    translation: Vec3,
+    dimension: DimensionId,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut entity_commands = commands.spawn(Collider::cuboid(half_size, half_size, half_size));
+    entity_commands
         .insert(RigidBody::Dynamic)
         .insert(Sleeping::default())
         .insert(ColliderMassProperties::Mass(300.0))
         .insert(LockedAxes::ROTATION_LOCKED)
         .insert(Ccd::enabled())
-        .insert(CollisionGroups::new(Group::GROUP_2, Group::GROUP_1))
+        .insert(dimension_collision_groups(
+            dimension,
+            Group::GROUP_2,
+            Group::GROUP_1,
+        ))
+        .insert(dimension)
         .insert(TransformBundle {
-            // 添加随机偏移
-            local: Transform::from_xyz(
-                center.x + rng.gen_range(-0.05..=0.05),
-                center.y + rng.gen_range(-0.05..=0.05),
-                center.z + rng.gen_range(-0.05..=0.05),
-            ),
+            local: Transform::from_translation(translation),
             ..Default::default()
-        })
-        .id()
+        });
+    entity_commands
 }
 
 #[derive(Debug, Component, Clone)]
@@ -192,6 +212,15 @@ impl GetChunkKey for NeedSave {
     }
 }
 
+// 掉落物在数据库中的键 主世界保持原来的键
+fn filled_db_key(chunk_key: ChunkKey) -> String {
+    if chunk_key.1 == DimensionId::OVERWORLD {
+        format!("FILL:ChunkKey({:?})", chunk_key.0)
+    } else {
+        format!("FILL:{}:ChunkKey({:?})", chunk_key.1 .0, chunk_key.0)
+    }
+}
+
 // 掉落物进入存储和加载到存储
 // 加载地图中的数据
 fn load_filled(
@@ -210,7 +239,7 @@ fn load_filled(
         .drain(..)
         {
             hashed_object.remove(&chunk_key);
-            let key = format!("FILL:{:?}", chunk_key);
+            let key = filled_db_key(chunk_key);
             if let Ok(data) = db.db.remove(key.clone()) {
                 if let Some(data) = data {
                     let data: Vec<(usize, [f32; 3])> = bincode::deserialize(&data).unwrap();
@@ -241,7 +270,7 @@ fn save_filled(
 ) {
     let hashed_object = map_chunk_key_filled_object(&query);
     for (chunk_key, vec_list) in hashed_object {
-        let key = format!("FILL:{:?}", chunk_key);
+        let key = filled_db_key(chunk_key);
         // 数据保存进行数据库
         let data: Vec<(usize, [f32; 3])> = vec_list
             .clone()
@@ -1,6 +1,8 @@
 // 接受处理 物体被丢弃的消息
 use bevy::{
-    prelude::{Commands, Component, Entity, Plugin, Query, Res, ResMut, Transform, Update},
+    prelude::{
+        Commands, Component, Entity, EventWriter, Plugin, Query, Res, ResMut, Transform, Update,
+    },
     time::{Time, Timer, TimerMode},
 };
 use bevy_rapier3d::prelude::ExternalImpulse;
@@ -8,10 +10,17 @@ use bevy_renet::renet::RenetServer;
 
 use crate::{
     client::message_def::{user_command::UserCommandMessage, ClientChannel},
-    server::{player::ServerLobby, tool_bar_sync::send_all_tool_bar},
+    server::{
+        dimension::ChangeDimensionEvent,
+        player::{Player, ServerLobby},
+        tool_bar_sync::send_all_tool_bar,
+    },
     staff::StaffInfoStroge,
-    tools::vec3_to_chunk_key_any_xyz,
-    voxel_world::player_state::PlayerOnTimeState,
+    voxel_world::{
+        dimension::{find_dimension_by_name, DimensionId},
+        player_state::PlayerOnTimeState,
+        pos::ChunkPos,
+    },
 };
 
 use super::gen_filled_object;
@@ -23,13 +32,20 @@ pub fn deal_with_throw_object(
     mut commands: Commands,
     mut server: ResMut<RenetServer>,
     server_lobby: Res<ServerLobby>,
-    mut query: Query<(Entity, &Transform, &mut PlayerOnTimeState)>,
+    mut query: Query<(
+        Entity,
+        &Transform,
+        &mut PlayerOnTimeState,
+        &Player,
+        &DimensionId,
+    )>,
     staff_info_stroge: Res<StaffInfoStroge>,
+    mut dimension_events: EventWriter<ChangeDimensionEvent>,
 ) {
     for client_id in server.clients_id() {
         while let Some(message) = server.receive_message(client_id, ClientChannel::Command) {
             if let Some(entity) = server_lobby.players.get(&client_id) {
-                if let Ok((_, trf, mut player_state)) = query.get_mut(*entity) {
+                if let Ok((_, trf, mut player_state, player, dimension)) = query.get_mut(*entity) {
                     let message: UserCommandMessage = bincode::deserialize(&message).unwrap();
                     match message {
                         UserCommandMessage::Throw {
@@ -47,10 +63,9 @@ pub fn deal_with_throw_object(
                                         player_state.0.clone(),
                                     );
                                     // 生成 丢弃物
-                                    let (chunk_key, _) = vec3_to_chunk_key_any_xyz(trf.translation);
                                     let throw_object = gen_filled_object(
                                         &mut commands,
-                                        chunk_key,
+                                        ChunkPos::from_vec3(trf.translation).key(*dimension),
                                         trf.translation,
                                         staff,
                                     );
@@ -68,6 +83,20 @@ pub fn deal_with_throw_object(
                                 }
                             }
                         }
+                        UserCommandMessage::ChangeDimension { name } => {
+                            if !server_lobby.admins.contains(&player.username) {
+                                println!("{}不是管理员 不能切换维度", player.username);
+                                continue;
+                            }
+                            if let Some(config) = find_dimension_by_name(&name) {
+                                dimension_events.send(ChangeDimensionEvent {
+                                    client_id,
+                                    dimension: config.dimension(),
+                                });
+                            } else {
+                                println!("没有找到维度{}", name);
+                            }
+                        }
                     }
                 }
             }
@@ -5,12 +5,16 @@ use bevy::{
     utils::HashMap,
 };
 use bevy_rapier3d::prelude::{
-    Ccd, Collider, ColliderMassProperties, CollisionGroups, Group, LockedAxes, RigidBody, Sleeping,
+    Ccd, Collider, ColliderMassProperties, Group, LockedAxes, RigidBody, Sleeping,
 };
+use bevy_renet::renet::RenetServer;
 
-use crate::voxel_world::player_state::{PlayerOnTimeState, PlayerState};
+use crate::voxel_world::{
+    dimension::{dimension_collision_groups, DimensionId},
+    player_state::{PlayerOnTimeState, PlayerState},
+};
 
-use super::cross_through_check::CossTroughCheck;
+use super::{cross_through_check::CossTroughCheck, message_def::ServerChannel};
 
 #[derive(Debug, Component)]
 pub struct Player {
@@ -23,6 +27,42 @@ pub struct ServerLobby {
     // client_id ==> entity
     pub players: HashMap<u64, Entity>,
     pub names: HashSet<String>,
+    // client_id ==> 所在的维度
+    pub dimensions: HashMap<u64, DimensionId>,
+    // 可以使用管理员命令的用户名
+    pub admins: HashSet<String>,
+}
+
+impl ServerLobby {
+    pub fn dimension_of(&self, client_id: u64) -> DimensionId {
+        self.dimensions
+            .get(&client_id)
+            .copied()
+            .unwrap_or(DimensionId::OVERWORLD)
+    }
+
+    // 某个维度中的全部玩家
+    pub fn clients_in(&self, dimension: DimensionId) -> Vec<u64> {
+        self.dimensions
+            .iter()
+            .filter(|(_, d)| **d == dimension)
+            .map(|(client_id, _)| *client_id)
+            .collect()
+    }
+
+    // 只发送给同一个维度中的玩家
+    pub fn broadcast_in(
+        &self,
+        server: &mut RenetServer,
+        dimension: DimensionId,
+        channel: ServerChannel,
+        message: Vec<u8>,
+    ) {
+        let channel: u8 = channel.into();
+        for client_id in self.clients_in(dimension) {
+            server.send_message(client_id, channel, message.clone());
+        }
+    }
 }
 
 pub fn server_create_player(
@@ -32,6 +72,7 @@ pub fn server_create_player(
     username: String,
 ) -> Entity {
     let pos = player_state.position;
+    let dimension = player_state.dimension;
     let transform = Transform::from_xyz(pos[0], pos[1], pos[2]);
     commands
         .spawn(Player {
@@ -52,11 +93,13 @@ pub fn server_create_player(
         .insert(YawValue::default())
         .insert(PitchValue::default())
         .insert(PlayerOnTimeState(player_state))
-        .insert(CollisionGroups::new(
+        .insert(dimension_collision_groups(
+            dimension,
             Group::GROUP_3,
             Group::GROUP_1 | Group::GROUP_3,
         ))
-        .insert(CossTroughCheck)
+        .insert(dimension)
+        .insert(CossTroughCheck::PLAYER)
         .id()
 }
 
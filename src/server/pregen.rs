@@ -0,0 +1,255 @@
+// 离线预生成地图
+// 在不启动网络服务的情况下 生成并保存一个范围内的全部区块
+
+use std::time::Instant;
+
+use bevy::{
+    prelude::{IVec3, Vec3},
+    tasks::{AsyncComputeTaskPool, TaskPool},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    voxel_world::{
+        biomes::{apply_other_tree_tasks, OtherTreeTasksMap},
+        chunk::{generate_offset_array_with_y_0, ChunkKey},
+        chunk_map::ChunkMap,
+        dimension::DimensionId,
+        map_database::{DbSaveTasks, MapDataBase},
+        pos::ChunkPos,
+        world_height::WORLD_HEIGHT,
+    },
+    CHUNK_SIZE, CLIENT_MAP_GEN,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct PregenReport {
+    // 生成的区块列数
+    pub columns: usize,
+    // 写入数据库的次数
+    pub saved: usize,
+    // 超出范围没有处理的树和结构
+    pub dropped_trees: usize,
+}
+
+// 获取要生成的区块列 按照距离中心的远近排序
+pub fn pregen_columns(center: Vec3, radius: f32) -> Vec<IVec3> {
+    let mut center_chunk_point = ChunkPos::from_vec3(center).0;
+    center_chunk_point.y = 0;
+    let chunk_distance = radius as i32 / CHUNK_SIZE;
+    let mut offsets: Vec<IVec3> = generate_offset_array_with_y_0(chunk_distance)
+        .into_iter()
+        .filter(|offset| {
+            offset.x * offset.x + offset.z * offset.z <= chunk_distance * chunk_distance
+        })
+        .collect();
+    offsets.sort_by_key(|offset| offset.x * offset.x + offset.z * offset.z);
+    offsets
+        .into_iter()
+        .map(|offset| center_chunk_point + offset)
+        .collect()
+}
+
+fn column_keys(column: IVec3, dimension: DimensionId) -> impl Iterator<Item = ChunkKey> {
+    WORLD_HEIGHT
+        .chunk_ys()
+        .map(move |y_offset| ChunkKey(IVec3::new(column.x, y_offset, column.z), dimension))
+}
+
+// 把已经保存过的区块加载进来 然后写入等待中的树
+fn apply_pending_trees(
+    db: &mut MapDataBase,
+    chunk_map: &mut ChunkMap,
+    db_save_tasks: &mut DbSaveTasks,
+    other_tree_tasks_map: &mut OtherTreeTasksMap,
+    loaded_columns: &HashSet<IVec3>,
+) -> usize {
+    // 先保证数据库中有最新的数据
+    let mut saved = db_save_tasks.flush(db);
+    let pending: Vec<ChunkKey> = other_tree_tasks_map
+        .tree_map
+        .keys()
+        .filter(|key| {
+            loaded_columns.contains(&key.to_y_zore().0) && !chunk_map.map_data.contains_key(key)
+        })
+        .copied()
+        .collect();
+    for key in pending {
+        let data = db.find_by_chunk_key(key, db_save_tasks, other_tree_tasks_map);
+        chunk_map.write_chunk(key, data);
+    }
+    for key in apply_other_tree_tasks(chunk_map, other_tree_tasks_map) {
+        if let Some(voxels) = chunk_map.get(key) {
+            let voxels = voxels.clone();
+            let task =
+                AsyncComputeTaskPool::get().spawn(async move { (key.as_u8_array(), voxels) });
+            db_save_tasks.tasks.push(task);
+        }
+    }
+    saved += db_save_tasks.flush(db);
+    saved
+}
+
+/**
+ * 预生成 dimension 维度中以 center 为中心 radius 为半径范围内的全部区块列
+ * 跨区块的树也会被写入 并且全部数据都会保存到数据库
+ */
+pub fn pregenerate_world(
+    db: &mut MapDataBase,
+    dimension: DimensionId,
+    center: Vec3,
+    radius: f32,
+) -> PregenReport {
+    AsyncComputeTaskPool::init(TaskPool::default);
+    if CLIENT_MAP_GEN {
+        println!("CLIENT_MAP_GEN 开启时每次都会重新生成地形 预生成的数据不会被使用");
+    }
+
+    let mut report = PregenReport::default();
+    let mut db_save_tasks = DbSaveTasks { tasks: Vec::new() };
+    let mut other_tree_tasks_map = OtherTreeTasksMap {
+        tree_map: HashMap::new(),
+    };
+    let mut loaded_columns: HashSet<IVec3> = HashSet::new();
+
+    let columns = pregen_columns(center, radius);
+    let total = columns.len();
+    let start = Instant::now();
+    let mut last_percent = 0;
+    println!(
+        "开始预生成 {} 个区块列 中心:{} 半径:{}",
+        total, center, radius
+    );
+
+    for (index, column) in columns.iter().enumerate() {
+        // 每一列使用单独的缓存 防止占用过多内存
+        let mut chunk_map = ChunkMap::new();
+        for key in column_keys(*column, dimension) {
+            let data = db.find_by_chunk_key(key, &mut db_save_tasks, &mut other_tree_tasks_map);
+            chunk_map.write_chunk(key, data);
+        }
+        loaded_columns.insert(*column);
+        report.saved += apply_pending_trees(
+            db,
+            &mut chunk_map,
+            &mut db_save_tasks,
+            &mut other_tree_tasks_map,
+            &loaded_columns,
+        );
+        report.columns += 1;
+
+        let percent = (index + 1) * 100 / total;
+        if percent != last_percent || index + 1 == total {
+            last_percent = percent;
+            let elapsed = start.elapsed().as_secs_f32();
+            let remaining = elapsed / (index + 1) as f32 * (total - index - 1) as f32;
+            println!(
+                "预生成进度 {}/{} ({}%) 用时 {:.1}s 剩余约 {:.1}s",
+                index + 1,
+                total,
+                percent,
+                elapsed,
+                remaining
+            );
+        }
+    }
+
+    // 范围边缘的树会延伸到范围外 这里生成对应的区块来承接
+    let margin_keys: Vec<ChunkKey> = other_tree_tasks_map
+        .tree_map
+        .keys()
+        .filter(|key| !loaded_columns.contains(&key.to_y_zore().0))
+        .copied()
+        .collect();
+    let mut chunk_map = ChunkMap::new();
+    for key in margin_keys {
+        let data = db.find_by_chunk_key(key, &mut db_save_tasks, &mut other_tree_tasks_map);
+        chunk_map.write_chunk(key, data);
+    }
+    report.saved += apply_pending_trees(
+        db,
+        &mut chunk_map,
+        &mut db_save_tasks,
+        &mut other_tree_tasks_map,
+        &loaded_columns,
+    );
+    report.dropped_trees = other_tree_tasks_map
+        .tree_map
+        .values()
+        .map(|list| list.len())
+        .sum();
+
+    if let Err(err) = db.db.flush() {
+        println!("数据库刷新失败{:?}", err);
+    }
+    println!(
+        "预生成完成 区块列:{} 保存次数:{} 未处理的树:{} 用时:{:.1}s",
+        report.columns,
+        report.saved,
+        report.dropped_trees,
+        start.elapsed().as_secs_f32()
+    );
+    report
+}
+
+#[test]
+fn test_pregen_columns() {
+    let center = Vec3::new(40.0, 70.0, -20.0);
+    let mut center_column = ChunkPos::from_vec3(center).0;
+    center_column.y = 0;
+    // 半径不到一个区块时只有中心一列
+    assert_eq!(pregen_columns(center, 0.0), vec![center_column]);
+    assert_eq!(pregen_columns(center, 15.0), vec![center_column]);
+
+    let columns = pregen_columns(center, 2.0 * CHUNK_SIZE as f32);
+    // 半径为2个区块的圆内一共13列
+    assert_eq!(columns.len(), 13);
+    assert_eq!(columns[0], center_column);
+    let unique: HashSet<IVec3> = columns.iter().copied().collect();
+    assert_eq!(unique.len(), columns.len());
+    let distances: Vec<i32> = columns
+        .iter()
+        .map(|column| {
+            let offset = *column - center_column;
+            assert_eq!(offset.y, 0);
+            offset.x * offset.x + offset.z * offset.z
+        })
+        .collect();
+    assert!(distances.iter().all(|distance| *distance <= 4));
+    assert!(distances.windows(2).all(|pair| pair[0] <= pair[1]));
+}
+
+#[test]
+fn test_pregenerate_skips_saved_chunks() {
+    use crate::voxel_world::voxel::{Stone, Voxel, VoxelMaterial};
+    let path = std::env::temp_dir().join("pregen_skips_saved_chunks");
+    let _ = std::fs::remove_dir_all(&path);
+    let mut db = MapDataBase::new(path.to_str().unwrap());
+    let column = IVec3::ZERO;
+    // 最下面的区块已经保存过 内容和生成的地形不同
+    let saved_key = ChunkKey(
+        IVec3::new(column.x, WORLD_HEIGHT.min_chunk_y, column.z),
+        DimensionId::OVERWORLD,
+    );
+    let marker = vec![Stone::into_voxel(); crate::CHUNK_SIZE_U32.pow(3) as usize];
+    db.db
+        .insert(
+            saved_key.as_u8_array(),
+            bincode::serialize(&marker).unwrap(),
+        )
+        .unwrap();
+
+    let report = pregenerate_world(&mut db, DimensionId::OVERWORLD, Vec3::ZERO, 0.0);
+    assert_eq!(report.columns, 1);
+    // 保存过的区块没有被重新生成和覆盖
+    let data = db.db.get(saved_key.as_u8_array()).unwrap().unwrap();
+    let voxels: Vec<Voxel> = bincode::deserialize(&data).unwrap();
+    assert_eq!(voxels, marker);
+    // 其他区块都生成并保存了
+    for key in column_keys(column, DimensionId::OVERWORLD) {
+        assert!(db.db.contains_key(key.as_u8_array()).unwrap());
+    }
+    assert!(report.saved >= WORLD_HEIGHT.chunk_ys().count() - 1);
+    drop(db);
+    let _ = std::fs::remove_dir_all(&path);
+}
@@ -0,0 +1,381 @@
+// 随机刻
+// 每隔一段时间 在玩家附近已经加载的区块中随机选择一些方块 调用方块类型注册的处理函数
+// 草方块蔓延 雪融化 树苗长大等都是通过随机刻实现的
+// 随机数使用固定的种子 相同的区块和种子会选择相同的方块
+
+use bevy::{
+    prelude::{
+        Commands, IVec3, Plugin, Query, Res, ResMut, Resource, Startup, Transform, Update, Vec3,
+        With,
+    },
+    time::{Time, Timer, TimerMode},
+    utils::HashMap,
+};
+use block_mesh::{Voxel as MeshVoxel, VoxelVisibility};
+use ndshape::{ConstShape, ConstShape3u32};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    voxel_world::{
+        biomes::TreeGentor,
+        chunk::ChunkKey,
+        chunk_map::ChunkMap,
+        dimension::DimensionId,
+        light::emit_level,
+        pos::{ChunkPos, LocalPos},
+        voxel::{
+            AppleLeaf, AppleWood, BuleGrass, DryGrass, Grass, Sapling, Soli, Sown, Voxel,
+            VoxelMaterial, WATER_SOURCE_LEVEL,
+        },
+        world_height::WORLD_HEIGHT,
+    },
+    CHUNK_SIZE_U32,
+};
+
+use super::{
+    block_update::{offset_block_pos, BlockUpdateQueue},
+    player::Player,
+};
+
+type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+
+// 每个区块每次选择的方块数量
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+
+// 雪方块附近这个范围内有发光的方块时会融化
+const SNOW_MELT_RADIUS: i32 = 2;
+// 树苗每次被选中时长成树的概率
+const SAPLING_GROW_CHANCE: f64 = 1.0 / 7.0;
+
+/**
+ * 随机刻的处理函数
+ * 返回要修改的方块 由调度器统一写入 BlockUpdateQueue
+ */
+pub type RandomTickFn =
+    fn(&ChunkMap, ChunkKey, [u32; 3], Voxel, &mut StdRng) -> Vec<(ChunkKey, [u32; 3], Voxel)>;
+
+#[derive(Debug, Clone, Resource)]
+pub struct RandomTickConfig {
+    // 两次随机刻之间的间隔
+    pub interval_millis: u64,
+    // 每个区块每次选择的方块数量 为0时关闭随机刻
+    pub blocks_per_chunk: u32,
+    // 玩家周围多少个区块以内
+    pub radius: i32,
+    pub seed: u64,
+}
+
+impl Default for RandomTickConfig {
+    fn default() -> Self {
+        Self {
+            interval_millis: 200,
+            blocks_per_chunk: DEFAULT_RANDOM_TICK_SPEED,
+            radius: 4,
+            seed: 0x52414E44,
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct RandomTickScheduler {
+    pub timer: Timer,
+    pub rng: StdRng,
+}
+
+impl RandomTickScheduler {
+    pub fn new(config: &RandomTickConfig) -> Self {
+        Self {
+            timer: Timer::new(
+                bevy::utils::Duration::from_millis(config.interval_millis),
+                TimerMode::Repeating,
+            ),
+            rng: StdRng::seed_from_u64(config.seed),
+        }
+    }
+}
+
+// 方块id -> 随机刻处理函数
+#[derive(Resource, Default)]
+pub struct RandomTickRegistry {
+    pub handlers: HashMap<u8, RandomTickFn>,
+}
+
+impl RandomTickRegistry {
+    pub fn register(&mut self, id: u8, handler: RandomTickFn) {
+        if self.handlers.insert(id, handler).is_some() {
+            println!("方块{}的随机刻处理函数被覆盖", id);
+        }
+    }
+}
+
+/**
+ * 玩家附近已经加载的区块 排序后返回
+ * 排序保证了相同的种子会选择相同的方块
+ */
+pub fn ticking_chunk_keys(
+    chunk_map: &ChunkMap,
+    players: impl Iterator<Item = (Vec3, DimensionId)>,
+    radius: i32,
+) -> Vec<ChunkKey> {
+    let mut keys = Vec::new();
+    for (pos, dimension) in players {
+        let center = ChunkPos::from_vec3(pos).0;
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                for y in WORLD_HEIGHT.chunk_ys() {
+                    let key = ChunkKey(IVec3::new(center.x + dx, y, center.z + dz), dimension);
+                    if chunk_map.map_data.contains_key(&key) {
+                        keys.push(key);
+                    }
+                }
+            }
+        }
+    }
+    keys.sort_by_key(|key| (key.1 .0, key.0.x, key.0.y, key.0.z));
+    keys.dedup();
+    keys
+}
+
+// 每个区块随机选择 blocks_per_chunk 个位置
+pub fn pick_random_blocks(
+    rng: &mut StdRng,
+    chunk_keys: &[ChunkKey],
+    blocks_per_chunk: u32,
+) -> Vec<(ChunkKey, [u32; 3])> {
+    let mut ret = Vec::with_capacity(chunk_keys.len() * blocks_per_chunk as usize);
+    for key in chunk_keys.iter() {
+        for _ in 0..blocks_per_chunk {
+            let pos = [
+                rng.gen_range(0..CHUNK_SIZE_U32),
+                rng.gen_range(0..CHUNK_SIZE_U32),
+                rng.gen_range(0..CHUNK_SIZE_U32),
+            ];
+            ret.push((*key, pos));
+        }
+    }
+    ret
+}
+
+fn setup_random_tick(mut commands: Commands, config: Res<RandomTickConfig>) {
+    commands.insert_resource(RandomTickScheduler::new(&config));
+}
+
+fn random_tick_system(
+    time: Res<Time>,
+    config: Res<RandomTickConfig>,
+    registry: Res<RandomTickRegistry>,
+    mut scheduler: ResMut<RandomTickScheduler>,
+    players: Query<(&Transform, &DimensionId), With<Player>>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut block_update_queue: ResMut<BlockUpdateQueue>,
+) {
+    scheduler.timer.tick(time.delta());
+    if !scheduler.timer.finished() || config.blocks_per_chunk == 0 {
+        return;
+    }
+    let keys = ticking_chunk_keys(
+        &chunk_map,
+        players
+            .iter()
+            .map(|(trf, dimension)| (trf.translation, *dimension)),
+        config.radius,
+    );
+    let picked = pick_random_blocks(&mut scheduler.rng, &keys, config.blocks_per_chunk);
+    // 先全部计算 再统一写入
+    let mut changes = Vec::new();
+    for (chunk_key, pos) in picked {
+        let Some(voxel) = chunk_map.get_block(chunk_key, pos) else {
+            continue;
+        };
+        if let Some(handler) = registry.handlers.get(&voxel.id) {
+            changes.extend(handler(
+                &chunk_map,
+                chunk_key,
+                pos,
+                voxel,
+                &mut scheduler.rng,
+            ));
+        }
+    }
+    for (chunk_key, pos, voxel) in changes {
+        block_update_queue.set_block(chunk_map.as_mut(), chunk_key, pos, voxel);
+    }
+}
+
+fn get_offset_block(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    offset: IVec3,
+) -> Option<Voxel> {
+    let (key, p) = offset_block_pos(chunk_key, pos, offset);
+    chunk_map.get_block(key, p)
+}
+
+// 上面被不透明的方块或者水挡住了
+fn is_covered(chunk_map: &ChunkMap, chunk_key: ChunkKey, pos: [u32; 3]) -> bool {
+    get_offset_block(chunk_map, chunk_key, pos, IVec3::Y).map_or(false, |voxel| {
+        matches!(voxel.get_visibility(), VoxelVisibility::Opaque) || voxel.is_water()
+    })
+}
+
+// 草方块 被挡住时变回土壤 否则向附近的土壤蔓延
+fn grass_tick(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    _voxel: Voxel,
+    rng: &mut StdRng,
+) -> Vec<(ChunkKey, [u32; 3], Voxel)> {
+    if is_covered(chunk_map, chunk_key, pos) {
+        return vec![(chunk_key, pos, Soli::into_voxel())];
+    }
+    let offset = IVec3::new(
+        rng.gen_range(-1..=1),
+        rng.gen_range(-3..=1),
+        rng.gen_range(-1..=1),
+    );
+    let (key, p) = offset_block_pos(chunk_key, pos, offset);
+    match chunk_map.get_block(key, p) {
+        Some(target) if target.id == Soli::ID && !is_covered(chunk_map, key, p) => {
+            vec![(key, p, Grass::into_voxel())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// 雪方块 附近有发光的方块时融化成水
+fn snow_tick(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    _voxel: Voxel,
+    _rng: &mut StdRng,
+) -> Vec<(ChunkKey, [u32; 3], Voxel)> {
+    for x in -SNOW_MELT_RADIUS..=SNOW_MELT_RADIUS {
+        for y in -SNOW_MELT_RADIUS..=SNOW_MELT_RADIUS {
+            for z in -SNOW_MELT_RADIUS..=SNOW_MELT_RADIUS {
+                let near = get_offset_block(chunk_map, chunk_key, pos, IVec3::new(x, y, z));
+                if near.map_or(false, |voxel| emit_level(voxel) > 0) {
+                    return vec![(chunk_key, pos, Voxel::flowing_water(WATER_SOURCE_LEVEL - 1))];
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+// 树苗 有一定概率长成苹果树 上面要有足够的空间
+fn sapling_tick(
+    chunk_map: &ChunkMap,
+    chunk_key: ChunkKey,
+    pos: [u32; 3],
+    _voxel: Voxel,
+    rng: &mut StdRng,
+) -> Vec<(ChunkKey, [u32; 3], Voxel)> {
+    if !rng.gen_bool(SAPLING_GROW_CHANCE) {
+        return Vec::new();
+    }
+    match get_offset_block(chunk_map, chunk_key, pos, IVec3::NEG_Y) {
+        Some(below) if [Soli::ID, Grass::ID, DryGrass::ID, BuleGrass::ID].contains(&below.id) => {}
+        _ => return Vec::new(),
+    }
+    let h: u32 = rng.gen_range(2..5);
+    let r: f32 = rng.gen_range(2.0..3.2);
+    for y in 1..=h {
+        match get_offset_block(chunk_map, chunk_key, pos, IVec3::new(0, y as i32, 0)) {
+            Some(voxel) if voxel.id == Voxel::EMPTY.id || voxel.is_decoration() => {}
+            _ => return Vec::new(),
+        }
+    }
+    let root = chunk_key.block(LocalPos(pos)).center();
+    let mut tree = TreeGentor {
+        tree: AppleWood::into_voxel(),
+        leaf: AppleLeaf::into_voxel(),
+        trunk_params: (root, h),
+        leafs_params: (root + Vec3::new(0.0, h as f32 - 1.0, 0.0), r, 0.0),
+    };
+    // 树覆盖到的区块 分别生成后和原来的数据比较
+    let min_chunk = ChunkPos::from_vec3(root - Vec3::new(r, 0.0, r));
+    let max_chunk = ChunkPos::from_vec3(root + Vec3::new(r, h as f32 + r, r));
+    let mut changes = Vec::new();
+    for x in min_chunk.0.x..=max_chunk.0.x {
+        for y in min_chunk.0.y..=max_chunk.0.y {
+            for z in min_chunk.0.z..=max_chunk.0.z {
+                let key = ChunkKey(IVec3::new(x, y, z), chunk_key.1);
+                let Some(before) = chunk_map.get(key) else {
+                    continue;
+                };
+                let mut after = before.clone();
+                tree.make_tree_for_chunk(&mut after, key);
+                for (index, (old, new)) in before.iter().zip(after.iter()).enumerate() {
+                    if old != new {
+                        changes.push((key, SampleShape::delinearize(index as u32), *new));
+                    }
+                }
+            }
+        }
+    }
+    changes
+}
+
+pub struct RandomTickPlugin;
+
+impl Plugin for RandomTickPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.init_resource::<RandomTickConfig>();
+        let mut registry = app
+            .world
+            .get_resource_or_insert_with(RandomTickRegistry::default);
+        registry.register(Grass::ID, grass_tick);
+        registry.register(Sown::ID, snow_tick);
+        registry.register(Sapling::ID, sapling_tick);
+        app.add_systems(Startup, setup_random_tick);
+        app.add_systems(Update, random_tick_system);
+    }
+}
+
+#[test]
+fn test_pick_random_blocks_deterministic() {
+    let keys = vec![
+        ChunkKey::new(IVec3::new(0, 0, 0)),
+        ChunkKey::new(IVec3::new(1, -2, 3)),
+    ];
+    let a = pick_random_blocks(&mut StdRng::seed_from_u64(7), &keys, 5);
+    let b = pick_random_blocks(&mut StdRng::seed_from_u64(7), &keys, 5);
+    let c = pick_random_blocks(&mut StdRng::seed_from_u64(8), &keys, 5);
+    assert_eq!(a.len(), 10);
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+}
+
+#[test]
+fn test_grass_tick() {
+    use crate::voxel_world::voxel::Stone;
+    let key = ChunkKey::new(IVec3::new(0, 0, 0));
+    let mut voxels = vec![Voxel::EMPTY; SampleShape::SIZE as usize];
+    for x in 0..CHUNK_SIZE_U32 {
+        for z in 0..CHUNK_SIZE_U32 {
+            voxels[SampleShape::linearize([x, 0, z]) as usize] = Soli::into_voxel();
+        }
+    }
+    voxels[SampleShape::linearize([8, 0, 8]) as usize] = Grass::into_voxel();
+    let mut chunk_map = ChunkMap::new();
+    chunk_map.write_chunk(key, voxels);
+    // 草会蔓延到旁边的土壤上
+    let mut rng = StdRng::seed_from_u64(1);
+    let spread: Vec<_> = (0..64)
+        .flat_map(|_| grass_tick(&chunk_map, key, [8, 0, 8], Grass::into_voxel(), &mut rng))
+        .collect();
+    assert!(!spread.is_empty());
+    assert!(spread
+        .iter()
+        .all(|(_, pos, voxel)| *voxel == Grass::into_voxel() && pos[1] == 0));
+    // 被挡住的草变回土壤
+    chunk_map.map_data.get_mut(&key).unwrap()[SampleShape::linearize([8, 1, 8]) as usize] =
+        Stone::into_voxel();
+    assert_eq!(
+        grass_tick(&chunk_map, key, [8, 0, 8], Grass::into_voxel(), &mut rng),
+        vec![(key, [8, 0, 8], Soli::into_voxel())]
+    );
+}
@@ -1,21 +1,25 @@
 use bevy::{
     prelude::{
         Assets, Commands, Entity, Event, EventReader, EventWriter, GlobalTransform, Mesh, Plugin,
-        PreUpdate, Res, ResMut, Resource, Vec3,
+        PreUpdate, Quat, Res, ResMut, Resource, Vec3,
     },
     tasks::{AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
-use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
+use bevy_rapier3d::prelude::{Collider, Group, RigidBody};
 
 use crate::{
     client::sp_mesh_display::get_sp_tfr,
     common::ServerClipSpheres,
     voxel_world::{
+        block_registry::{BlockModel, ModelBox, ModelCollider, ModelRotation, BLOCKS},
         chunk::{find_chunk_keys_array_by_sphere, generate_offset_array, ChunkKey},
         chunk_map::ChunkMap,
-        voxel::VoxelDirection,
-        voxel_mesh::{MeshMateData, VoxelMeshStorge, VOXEL_MESH_MAP},
+        dimension::dimension_collision_groups,
+        voxel::{Voxel, VoxelDirection},
+        voxel_mesh::{
+            vox_collider::VoxOccupancy, MeshMateData, ModelReloadEvent, VoxelMeshStorge, VOX_SCALE,
+        },
     },
     PY_DISTANCE,
 };
@@ -66,7 +70,49 @@ impl Default for SpPhysicsManager {
 // 处理生成物理引擎任务
 #[derive(Debug, Resource)]
 pub struct SpPhysicsTasks {
-    pub tasks: Vec<Task<(ChunkKey, u32, MeshMateData, VoxelDirection)>>,
+    pub tasks: Vec<Task<(ChunkKey, u32, MeshMateData, Voxel)>>,
+}
+
+// 模型体素生成的碰撞体 按照模型和方向缓存 读取失败的模型也记录下来 不会每帧重新读取
+#[derive(Default, Resource)]
+pub struct SpColliderCache {
+    pub colliders: HashMap<(String, VoxelDirection), Option<Collider>>,
+}
+
+impl SpColliderCache {
+    pub fn get(&mut self, model: &BlockModel, direction: VoxelDirection) -> Option<Collider> {
+        let direction = match model.rotation {
+            ModelRotation::Horizontal => direction,
+            ModelRotation::Fixed => VoxelDirection::Z,
+        };
+        self.colliders
+            .entry((model.vox.clone(), direction))
+            .or_insert_with(|| {
+                let cuboids = VoxOccupancy::load(&model.vox)?.cuboids(direction);
+                if cuboids.is_empty() {
+                    return None;
+                }
+                println!("模型{}生成了{}个长方体碰撞体", model.vox, cuboids.len());
+                Some(Collider::compound(
+                    cuboids
+                        .into_iter()
+                        .map(|(center, half_size)| {
+                            (
+                                center,
+                                Quat::IDENTITY,
+                                Collider::cuboid(half_size.x, half_size.y, half_size.z),
+                            )
+                        })
+                        .collect(),
+                ))
+            })
+            .clone()
+    }
+
+    // 模型文件修改之后清除缓存
+    pub fn remove_model(&mut self, vox: &String) {
+        self.colliders.retain(|(path, _), _| path != vox);
+    }
 }
 
 impl Default for SpPhysicsTasks {
@@ -84,6 +130,7 @@ impl Plugin for SpPhysicsPlugin {
         app.add_event::<DespawnSpEvent>();
         app.insert_resource(SpPhysicsManager::default());
         app.insert_resource(SpPhysicsTasks::default());
+        app.init_resource::<SpColliderCache>();
         // 处理生成和销毁
         app.add_systems(
             PreUpdate,
@@ -91,6 +138,7 @@ impl Plugin for SpPhysicsPlugin {
                 gen_sp_physics_tasks,
                 deal_gen_collider,
                 despawn_sp_event_gen,
+                reload_sp_collider,
                 deal_events,
             ),
         );
@@ -120,18 +168,17 @@ fn gen_sp_physics_tasks(
                 if let Some(voxels) = chunk_map.get(chunk_key) {
                     // 存在地图数据
                     for i in 0..voxels.len() {
-                        if VOXEL_MESH_MAP.contains_key(&voxels[i].id)
-                            && !sp_physics_manager.has_data(chunk_key, i)
+                        if voxels[i].has_model() && !sp_physics_manager.has_data(chunk_key, i)
                         // 原来没有数据
                         {
                             // 存在SP的类型的方块
-                            if let Some(meta_data) = voxel_mesh_storge.data.get(&voxels[i].id) {
-                                let ret = (
-                                    chunk_key.clone(),
-                                    i as u32,
-                                    meta_data.clone(),
-                                    voxels[i].direction,
-                                );
+                            if let Some(meta_data) = voxel_mesh_storge
+                                .data
+                                .get(&voxels[i].id)
+                                .filter(|meta_data| meta_data.collider)
+                            {
+                                let ret =
+                                    (chunk_key.clone(), i as u32, meta_data.clone(), voxels[i]);
                                 let task = pool.spawn(async move { ret });
                                 sp_physics_tasks.tasks.push(task);
                             }
@@ -148,33 +195,42 @@ fn deal_gen_collider(
     mut commands: Commands,
     mut sp_physics_manager: ResMut<SpPhysicsManager>,
     mut sp_physics_tasks: ResMut<SpPhysicsTasks>,
+    mut sp_collider_cache: ResMut<SpColliderCache>,
     mesh_assets: Res<Assets<Mesh>>,
 ) {
     let len = sp_physics_tasks.tasks.len().min(256);
     for ele in sp_physics_tasks.tasks.drain(..len) {
-        if let Some((chunk_key, index, mate_data, direction)) =
+        if let Some((chunk_key, index, mate_data, voxel)) =
             futures_lite::future::block_on(futures_lite::future::poll_once(ele))
         {
             if !sp_physics_manager.has_data(chunk_key, index as usize) {
-                // FIXME: 后续这里要其他的地方接管 碰撞体的生成
-                if let Some(mesh) = mesh_assets.get(&mate_data.vox_list[0].clone()) {
-                    if let Some(collider) = get_collider_by_mesh(mesh) {
-                        println!("这里生成碰撞体");
-                        let entity = commands
-                            .spawn((
-                                TerrainPhysics,
-                                get_sp_tfr(chunk_key.clone(), index.clone(), direction),
-                                GlobalTransform::default(),
-                            ))
-                            .insert(RigidBody::Fixed)
-                            .insert(collider)
-                            .insert(CollisionGroups::new(
-                                Group::GROUP_1,
-                                Group::GROUP_2 | Group::GROUP_3,
-                            ))
-                            .id();
-                        sp_physics_manager.insert(chunk_key, index as usize, entity);
+                let mut transform = get_sp_tfr(chunk_key.clone(), index.clone(), voxel);
+                let collider = match mate_data.model.collider {
+                    ModelCollider::Voxels => {
+                        // 已经按照方向旋转 并且使用方块的单位
+                        transform.rotation = Quat::IDENTITY;
+                        transform.scale = Vec3::ONE;
+                        sp_collider_cache.get(&mate_data.model, voxel.direction)
                     }
+                    ModelCollider::Mesh => mesh_assets
+                        .get(&mate_data.mesh)
+                        .and_then(get_collider_by_mesh),
+                    ModelCollider::Bbox => Some(get_collider_by_bbox(mate_data.model.bbox)),
+                };
+                // 模型还没有加载好时 下一次再生成
+                if let Some(collider) = collider {
+                    println!("这里生成碰撞体");
+                    let entity = commands
+                        .spawn((TerrainPhysics, transform, GlobalTransform::default()))
+                        .insert(RigidBody::Fixed)
+                        .insert(collider)
+                        .insert(dimension_collision_groups(
+                            chunk_key.1,
+                            Group::GROUP_1,
+                            Group::GROUP_2 | Group::GROUP_3,
+                        ))
+                        .id();
+                    sp_physics_manager.insert(chunk_key, index as usize, entity);
                 }
             }
         }
@@ -223,6 +279,37 @@ fn despawn_sp_event_gen(
     }
 }
 
+// 模型文件修改之后 删除用到这个模型的碰撞体 之后会按照新的模型重新生成
+fn reload_sp_collider(
+    chunk_map: Res<ChunkMap>,
+    sp_physics_manager: Res<SpPhysicsManager>,
+    mut sp_collider_cache: ResMut<SpColliderCache>,
+    mut model_reload_event: EventReader<ModelReloadEvent>,
+    mut event_writer: EventWriter<DespawnSpEvent>,
+) {
+    let ids: HashSet<u8> = model_reload_event.iter().map(|event| event.id).collect();
+    if ids.is_empty() {
+        return;
+    }
+    for id in ids.iter() {
+        if let Some(model) = BLOCKS.model(*id) {
+            sp_collider_cache.remove_model(&model.vox);
+        }
+    }
+    for (chunk_key, inner_map) in sp_physics_manager.entities.iter() {
+        if let Some(voxels) = chunk_map.get(*chunk_key) {
+            for index in inner_map.keys() {
+                if ids.contains(&voxels[*index].id) {
+                    event_writer.send(DespawnSpEvent {
+                        chunk_key: *chunk_key,
+                        index: *index,
+                    });
+                }
+            }
+        }
+    }
+}
+
 fn deal_events(
     mut commands: Commands,
     mut sp_physics_manager: ResMut<SpPhysicsManager>,
@@ -256,9 +343,51 @@ fn get_collider_by_mesh(mesh: &Mesh) -> Option<Collider> {
     {
         collider_vertices = collider_vertices
             .iter()
-            .map(|x| *x * Vec3::splat(1.0 / 32.0))
+            .map(|x| *x * Vec3::splat(VOX_SCALE))
             .collect();
     }
     let collider = Collider::trimesh(collider_vertices, collider_indices);
     Some(collider)
 }
+
+// 通过包围盒生成碰撞体 包围盒是方块内的坐标 碰撞体和模型一样使用模型体素的单位
+fn get_collider_by_bbox(bbox: ModelBox) -> Collider {
+    let min = Vec3::from(bbox.min);
+    let max = Vec3::from(bbox.max);
+    let half_size = (max - min) / 2.0;
+    // 相对方块中心的位置
+    let offset = (min + max) / 2.0 - Vec3::splat(0.5);
+    #[cfg(not(feature = "headless"))]
+    let (half_size, offset) = (half_size / VOX_SCALE, offset / VOX_SCALE);
+    Collider::compound(vec![(
+        offset,
+        Quat::IDENTITY,
+        Collider::cuboid(half_size.x, half_size.y, half_size.z),
+    )])
+}
+
+#[test]
+fn test_collider_by_bbox() {
+    use bevy_rapier3d::prelude::ColliderView;
+    // 方块下半部分 偏向 x 正方向
+    let bbox = ModelBox {
+        min: [0.5, 0.0, 0.25],
+        max: [1.0, 0.5, 0.75],
+    };
+    #[cfg(not(feature = "headless"))]
+    let scale = VOX_SCALE;
+    #[cfg(feature = "headless")]
+    let scale = 1.0;
+    let collider = get_collider_by_bbox(bbox);
+    let compound = collider.as_compound().unwrap();
+    let shapes: Vec<_> = compound.shapes().collect();
+    assert_eq!(shapes.len(), 1);
+    let (offset, _, shape) = &shapes[0];
+    let ColliderView::Cuboid(cuboid) = shape else {
+        panic!("包围盒碰撞体应该是长方体");
+    };
+    let expected_half_size = Vec3::new(0.25, 0.25, 0.25) / scale;
+    let expected_offset = Vec3::new(0.25, -0.25, 0.0) / scale;
+    assert!((cuboid.half_extents() - expected_half_size).length() < 1e-4);
+    assert!((*offset - expected_offset).length() < 1e-4);
+}
@@ -7,8 +7,12 @@ use crate::{
         rule::{StaffNumPair, StaffRules},
         StaffInfoStroge,
     },
-    tools::vec3_to_chunk_key_any_xyz,
-    voxel_world::player_state::PlayerOnTimeState,
+    voxel_world::{
+        chunk::ChunkKey,
+        dimension::DimensionId,
+        player_state::PlayerOnTimeState,
+        pos::{BlockPos, LocalPos},
+    },
 };
 
 use super::{
@@ -26,7 +30,7 @@ impl Plugin for ServerStaffRulePlugin {
 pub fn deal_with_staff_rule(
     mut server: ResMut<RenetServer>,
     lobby: ResMut<ServerLobby>,
-    mut query: Query<(Entity, &Transform, &mut PlayerOnTimeState)>,
+    mut query: Query<(Entity, &Transform, &mut PlayerOnTimeState, &DimensionId)>,
     staff_rules: Res<StaffRules>,
     mut fill_event: EventWriter<ObjectFillEvent>,
     staff_info_stroge: Res<StaffInfoStroge>,
@@ -40,7 +44,7 @@ pub fn deal_with_staff_rule(
             } = bincode::deserialize(&message).unwrap();
             if let Some(rule) = staff_rules.rules.get(&staff_rule_id) {
                 if let Some(entity) = lobby.players.get(&client_id) {
-                    if let Ok((_, trf, mut player_state)) = query.get_mut(*entity) {
+                    if let Ok((_, trf, mut player_state, dimension)) = query.get_mut(*entity) {
                         // 找到用户的Ontime 减少物品
                         for (index, staff_id, use_num) in need {
                             if player_state.0.use_staff(index, staff_id, use_num) == None {
@@ -56,7 +60,10 @@ pub fn deal_with_staff_rule(
                             if let Some(out_staff) = staff_info_stroge.get(staff_id) {
                                 for _ in 0..num_needed * times {
                                     let center = trf.translation;
-                                    let (chunk_key, xyz) = vec3_to_chunk_key_any_xyz(center);
+                                    let (chunk_key, LocalPos(xyz)) = ChunkKey::from_block(
+                                        BlockPos::from_vec3(center),
+                                        *dimension,
+                                    );
                                     // 生成新的掉落物
                                     fill_event.send(ObjectFillEvent {
                                         chunk_key: chunk_key,
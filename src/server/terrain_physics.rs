@@ -3,37 +3,75 @@ use std::collections::HashSet;
 use bevy::{
     prelude::{
         Commands, Component, Entity, GlobalTransform, IntoSystemConfigs, Last, Plugin, PreUpdate,
-        Query, Res, ResMut, Resource, SystemSet, Transform, Vec3,
+        Query, Res, ResMut, Resource, SystemSet, Transform, With,
     },
     tasks::{AsyncComputeTaskPool, Task},
     utils::HashMap,
 };
-use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group, RigidBody};
-use block_mesh::{greedy_quads, GreedyQuadsBuffer, RIGHT_HANDED_Y_UP_CONFIG};
-use ndshape::{ConstShape, ConstShape3u32};
+use bevy_rapier3d::prelude::{Collider, Group, RigidBody};
 
 use crate::{
     common::ServerClipSpheres,
     voxel_world::{
         chunk::{find_chunk_keys_array_by_sphere, generate_offset_array, ChunkKey},
         chunk_map::ChunkMap,
+        dimension::dimension_collision_groups,
+        pos::ChunkPos,
         voxel::Voxel,
     },
-    CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32, PY_DISTANCE,
+    PY_DISTANCE,
 };
 
+use super::chunk_collider::ChunkColliderShape;
+
 #[derive(Debug, Component)]
 pub struct TerrainPhysics;
 
 // 管理碰撞体的组件
-#[derive(Debug, Resource)]
+#[derive(Debug, Resource, Default)]
 pub struct ColliderManager {
+    // 没有实心方块的区块没有实体
     pub entities: HashMap<ChunkKey, Entity>,
+    // 已经生成过的区块 修改方块时在这上面增量更新
+    pub shapes: HashMap<ChunkKey, ChunkColliderShape>,
 }
 
 #[derive(Debug, Resource, Default)]
 pub struct ColliderTasksManager {
-    pub tasks: Vec<Task<(ChunkKey, Vec<Voxel>)>>,
+    pub tasks: Vec<(ChunkKey, Task<ChunkColliderShape>)>,
+}
+
+impl ColliderTasksManager {
+    // 在后台合并区块的长方体 同一个区块只有一个任务
+    pub fn build(&mut self, chunk_key: ChunkKey, voxels: Vec<Voxel>) {
+        if self.tasks.iter().any(|(key, _)| *key == chunk_key) {
+            return;
+        }
+        let pool = AsyncComputeTaskPool::get();
+        let task = pool.spawn(async move { ChunkColliderShape::new(&voxels) });
+        self.tasks.push((chunk_key, task));
+    }
+
+    // 任务完成之前方块改变了 丢掉用旧数据的任务 用新的数据重新生成
+    pub fn rebuild(&mut self, chunk_key: ChunkKey, voxels: Vec<Voxel>) {
+        self.tasks.retain(|(key, _)| *key != chunk_key);
+        self.build(chunk_key, voxels);
+    }
+}
+
+// 修改过方块 等待更新的层
+#[derive(Debug, Resource, Default)]
+pub struct ColliderUpdateTasksManager {
+    pub dirty: HashMap<ChunkKey, HashSet<u32>>,
+}
+
+impl ColliderUpdateTasksManager {
+    pub fn mark(&mut self, chunk_key: ChunkKey, positions: &[[u32; 3]]) {
+        let slabs = self.dirty.entry(chunk_key).or_default();
+        for pos in positions {
+            slabs.insert(ChunkColliderShape::slab_of(pos[1]));
+        }
+    }
 }
 
 pub fn server_update_collider_task_system(
@@ -42,7 +80,6 @@ pub fn server_update_collider_task_system(
     mut collider_tasks: ResMut<ColliderTasksManager>,
     server_clip_spheres: Res<ServerClipSpheres>,
 ) {
-    let pool = AsyncComputeTaskPool::get();
     for (_client_id, clip_spheres) in server_clip_spheres.clip_spheres.iter() {
         for chunk_key in find_chunk_keys_array_by_sphere(
             clip_spheres.new_sphere,
@@ -51,58 +88,68 @@ pub fn server_update_collider_task_system(
         .drain(..)
         {
             // 在没有创建过的情况下才进行
-            // FIXME: 这里在一开始的时候似乎就可以 过滤一些的
-            if !collider_manager.entities.contains_key(&chunk_key)
-                && chunk_map.map_data.contains_key(&chunk_key)
-            {
-                let voxel_with_neighbor = chunk_map.get_neighbors(chunk_key);
-                let task = pool.spawn(async move { (chunk_key, voxel_with_neighbor) });
-                collider_tasks.tasks.push(task);
+            if !collider_manager.shapes.contains_key(&chunk_key) {
+                if let Some(voxels) = chunk_map.get(chunk_key) {
+                    collider_tasks.build(chunk_key, voxels.clone());
+                }
             }
         }
     }
 }
 
-// 生成碰撞体
+fn spawn_terrain_collider(
+    commands: &mut Commands,
+    chunk_key: ChunkKey,
+    collider: Collider,
+) -> Entity {
+    commands
+        .spawn((
+            TerrainPhysics,
+            // 长方体使用区块内的方块坐标
+            Transform::from_translation(ChunkPos::from(chunk_key).min_block().0.as_vec3()),
+            GlobalTransform::default(),
+        ))
+        .insert(RigidBody::Fixed)
+        .insert(collider)
+        .insert(dimension_collision_groups(
+            chunk_key.1,
+            Group::GROUP_1,
+            Group::GROUP_2 | Group::GROUP_3,
+        ))
+        .id()
+}
+
+// 生成碰撞体 没有完成的任务留到下一帧
 pub fn spawn_collider(
     mut collider_tasks: ResMut<ColliderTasksManager>,
     mut collider_manager: ResMut<ColliderManager>,
     mut commands: Commands,
 ) {
-    let len = collider_tasks.tasks.len().min(256);
-    for ele in collider_tasks.tasks.drain(..len) {
-        if let Some((chunk_key, voxel_data)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            if !collider_manager.entities.contains_key(&chunk_key) {
-                if let Some(collider) = gen_collider(voxel_data) {
-                    let entity = commands
-                        .spawn((
-                            TerrainPhysics,
-                            Transform::from_xyz(
-                                (chunk_key.0.x * CHUNK_SIZE) as f32 - CHUNK_SIZE as f32 / 2.0 - 1.0,
-                                (chunk_key.0.y * CHUNK_SIZE) as f32 - CHUNK_SIZE as f32 / 2.0 - 1.0,
-                                (chunk_key.0.z * CHUNK_SIZE) as f32 - CHUNK_SIZE as f32 / 2.0 - 1.0,
-                            ),
-                            GlobalTransform::default(),
-                        ))
-                        .insert(RigidBody::Fixed)
-                        .insert(collider)
-                        .insert(CollisionGroups::new(
-                            Group::GROUP_1,
-                            Group::GROUP_2 | Group::GROUP_3,
-                        ))
-                        .id();
-                    collider_manager.entities.insert(chunk_key, entity);
-                }
+    let mut spawned = 0;
+    collider_tasks.tasks.retain_mut(|(chunk_key, task)| {
+        if spawned >= 256 {
+            return true;
+        }
+        let Some(shape) = futures_lite::future::block_on(futures_lite::future::poll_once(task))
+        else {
+            return true;
+        };
+        spawned += 1;
+        if !collider_manager.shapes.contains_key(chunk_key) {
+            if let Some(collider) = shape.collider() {
+                let entity = spawn_terrain_collider(&mut commands, *chunk_key, collider);
+                collider_manager.entities.insert(*chunk_key, entity);
             }
+            collider_manager.shapes.insert(*chunk_key, shape);
         }
-    }
+        false
+    });
 }
 
 pub fn despawn_collider(
     server_clip_spheres: Res<ServerClipSpheres>,
     mut collider_manager: ResMut<ColliderManager>,
+    mut collider_tasks: ResMut<ColliderTasksManager>,
     mut commands: Commands,
 ) {
     let neighbour_offest = generate_offset_array(PY_DISTANCE);
@@ -123,7 +170,12 @@ pub fn despawn_collider(
         }
     }
 
+    // 还没完成的任务也取消 不然完成之后会生成没人管理的碰撞体
+    collider_tasks
+        .tasks
+        .retain(|(chunk_key, _)| !chunks_to_remove.contains(chunk_key));
     for chunk_key in chunks_to_remove.into_iter() {
+        collider_manager.shapes.remove(&chunk_key);
         if let Some(entity) = collider_manager.entities.remove(&chunk_key) {
             commands.entity(entity).despawn();
         }
@@ -134,6 +186,7 @@ pub fn despawn_collider(
 pub enum ColliderSystem {
     ColliderTask,
     ColliderSpawn,
+    ColliderUpdate,
     ColliderDespawn,
 }
 
@@ -141,98 +194,62 @@ pub struct TerrainPhysicsPlugin;
 
 impl Plugin for TerrainPhysicsPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.insert_resource(ColliderManager {
-            entities: HashMap::default(),
-        })
-        .insert_resource(ColliderTasksManager::default())
-        .insert_resource(ColliderUpdateTasksManager::default())
-        .add_systems(
-            PreUpdate,
-            server_update_collider_task_system.in_set(ColliderSystem::ColliderTask),
-        )
-        .add_systems(
-            PreUpdate,
-            spawn_collider
-                .in_set(ColliderSystem::ColliderSpawn)
-                .after(ColliderSystem::ColliderTask),
-        )
-        .add_systems(PreUpdate, update_codiller)
-        .add_systems(
-            Last,
-            despawn_collider.in_set(ColliderSystem::ColliderDespawn),
-        );
-    }
-}
-
-/**
- * 通过包含邻居的体素数据 获取碰撞体
- */
-pub fn gen_collider(voxels: Vec<Voxel>) -> Option<Collider> {
-    type SampleShape =
-        ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
-    let mut buffer = GreedyQuadsBuffer::new(SampleShape::SIZE as usize);
-    let faces: [block_mesh::OrientedBlockFace; 6] = RIGHT_HANDED_Y_UP_CONFIG.faces;
-    greedy_quads(
-        &voxels,
-        &SampleShape {},
-        [0; 3],
-        [
-            (CHUNK_SIZE + 1) as u32,
-            (CHUNK_SIZE + 1) as u32,
-            (CHUNK_SIZE + 1) as u32,
-        ],
-        &faces,
-        &mut buffer,
-    );
-    let num_indices = buffer.quads.num_quads() * 6;
-    let num_vertices = buffer.quads.num_quads() * 4;
-    if num_indices == 0 {
-        return None;
-    }
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-
-    for (_, (group, face)) in buffer
-        .quads
-        .groups
-        .as_ref()
-        .iter()
-        .zip(faces.into_iter())
-        .enumerate()
-    {
-        for quad in group.iter() {
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
-            positions.extend_from_slice(&face.quad_mesh_positions(quad, 1.0));
-            normals.extend_from_slice(&face.quad_mesh_normals());
-        }
+        app.insert_resource(ColliderManager::default())
+            .insert_resource(ColliderTasksManager::default())
+            .insert_resource(ColliderUpdateTasksManager::default())
+            .add_systems(
+                PreUpdate,
+                server_update_collider_task_system.in_set(ColliderSystem::ColliderTask),
+            )
+            .add_systems(
+                PreUpdate,
+                spawn_collider
+                    .in_set(ColliderSystem::ColliderSpawn)
+                    .after(ColliderSystem::ColliderTask),
+            )
+            .add_systems(
+                PreUpdate,
+                update_codiller.in_set(ColliderSystem::ColliderUpdate),
+            )
+            .add_systems(
+                Last,
+                despawn_collider.in_set(ColliderSystem::ColliderDespawn),
+            );
     }
-    let collider_vertices: Vec<Vec3> = positions.iter().cloned().map(Vec3::from).collect();
-    let collider_indices: Vec<[u32; 3]> = indices.chunks(3).map(|i| [i[0], i[1], i[2]]).collect();
-    let collider = Collider::trimesh(collider_vertices, collider_indices);
-    Some(collider)
-}
-
-#[derive(Debug, Resource, Default)]
-pub struct ColliderUpdateTasksManager {
-    pub tasks: Vec<Task<(Entity, ChunkKey, Vec<Voxel>)>>,
 }
 
+// 只重新合并修改过的层 再替换复合碰撞体
 fn update_codiller(
     mut commands: Commands,
+    chunk_map: Res<ChunkMap>,
     mut collider_update_tasks_manager: ResMut<ColliderUpdateTasksManager>,
-    mut query: Query<(Entity, &mut Collider, &TerrainPhysics)>,
+    mut query: Query<&mut Collider, With<TerrainPhysics>>,
     mut collider_manager: ResMut<ColliderManager>,
 ) {
-    let len = collider_update_tasks_manager.tasks.len().min(256);
-    for ele in collider_update_tasks_manager.tasks.drain(..len) {
-        if let Some((entity, chunk_key, voxels)) =
-            futures_lite::future::block_on(futures_lite::future::poll_once(ele))
-        {
-            if let Ok((_, mut collider, _)) = query.get_mut(entity) {
-                if let Some(c) = gen_collider(voxels) {
-                    *collider = c;
-                } else if let Some(entity) = collider_manager.entities.remove(&chunk_key) {
+    for (chunk_key, slabs) in collider_update_tasks_manager.dirty.drain() {
+        let (Some(shape), Some(voxels)) = (
+            collider_manager.shapes.get_mut(&chunk_key),
+            chunk_map.get(chunk_key),
+        ) else {
+            continue;
+        };
+        for slab in slabs {
+            shape.update_slab(voxels, slab);
+        }
+        let new_collider = shape.collider();
+        let entity = collider_manager.entities.get(&chunk_key).copied();
+        match (new_collider, entity) {
+            (Some(new_collider), Some(entity)) => {
+                if let Ok(mut collider) = query.get_mut(entity) {
+                    *collider = new_collider;
+                }
+            }
+            (Some(new_collider), None) => {
+                let entity = spawn_terrain_collider(&mut commands, chunk_key, new_collider);
+                collider_manager.entities.insert(chunk_key, entity);
+            }
+            (None, _) => {
+                if let Some(entity) = collider_manager.entities.remove(&chunk_key) {
                     commands.entity(entity).despawn();
                 }
             }
@@ -1,16 +1,22 @@
 use bevy::{
     prelude::{
-        error, warn, App, AssetServer, Handle, Image, IntoSystemConfigs, Plugin, Res, ResMut,
-        Resource, Startup, SystemSet,
+        error, warn, App, AssetServer, Event, EventReader, Handle, Image, IntoSystemConfigs,
+        Plugin, PreUpdate, Res, ResMut, Resource, Startup, SystemSet,
     },
     utils::HashMap,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::voxel_world::voxel::Voxel;
+use crate::{
+    common::content_watcher::{ContentWatcherPlugin, STAFF_RON},
+    voxel_world::{
+        block_registry::{BlockDrop, BLOCKS},
+        voxel::Voxel,
+    },
+};
 
-use self::rule::StaffRulePlugin;
+use self::rule::{StaffRule, StaffRulePlugin, StaffRules};
 
 pub mod rule;
 
@@ -42,15 +48,42 @@ pub enum StaffType {
 pub struct StaffInfoStroge {
     pub data: HashMap<usize, Staff>,
     pub voxel_staff: HashMap<u8, Staff>,
-    // 灵活的 体素和物品掉落的关系
-    pub filled_map: HashMap<usize, FilledMeta>,
+    // 方块的掉落物 来自 blocks.ron 可以单独重新加载
+    pub block_drops: HashMap<u8, Vec<BlockDrop>>,
 }
 
 impl StaffInfoStroge {
-    fn register_filled(&mut self, fill_mate: FilledMeta) {
-        self.filled_map
-            .insert(fill_mate.voxel_id, fill_mate.clone());
+    pub fn empty() -> Self {
+        Self {
+            data: HashMap::default(),
+            voxel_staff: HashMap::default(),
+            block_drops: BLOCKS.drops(),
+        }
+    }
+
+    // 没有 asset_server 时(服务器) 不加载图标
+    pub fn from_configs(configs: StaffConfigs, asset_server: Option<&AssetServer>) -> Self {
+        let mut storge = Self::empty();
+        for mate in configs.configs {
+            storge.register(Staff {
+                id: mate.id,
+                name: mate.name,
+                icon: asset_server
+                    .map(|asset_server| asset_server.load(mate.icon_string))
+                    .unwrap_or_default(),
+                staff_type: mate.staff_type,
+            });
+        }
+        storge
+    }
+
+    // 重新加载物品定义 保留已经重新加载过的掉落物
+    pub fn replace_configs(&mut self, configs: StaffConfigs, asset_server: Option<&AssetServer>) {
+        let block_drops = std::mem::take(&mut self.block_drops);
+        *self = Self::from_configs(configs, asset_server);
+        self.block_drops = block_drops;
     }
+
     fn register(&mut self, staff: Staff) {
         if self.data.contains_key(&staff.id) {
             warn!("{} is already registered", staff.id);
@@ -71,16 +104,15 @@ impl StaffInfoStroge {
         self.voxel_staff.get(&voxel.id)
     }
 
-    // 通过体素获取掉落物
+    // 通过体素获取掉落物 掉落物配置在方块属性中
     pub fn voxel_to_staff_list(&self, voxel: Voxel) -> Option<Vec<Staff>> {
         let mut ret: Vec<Staff> = Vec::new();
-        let voxle_id = voxel.id;
-        if let Some(mate) = self.filled_map.get(&(voxle_id as usize)) {
-            for FilledPair {
+        if let Some(drops) = self.block_drops.get(&voxel.id) {
+            for BlockDrop {
                 possible,
                 staff_id,
                 times,
-            } in mate.filled_config.iter()
+            } in drops.iter()
             {
                 if let Some(staff) = self.get(*staff_id) {
                     for _ in 0..*times {
@@ -113,22 +145,56 @@ pub enum StaffSet {
     Init,
 }
 
+// 服务器推送的物品定义和合成规则 客户端收到之后替换本地的
+#[derive(Debug, Event)]
+pub enum StaffSyncEvent {
+    Staff(StaffConfigs),
+    Rules(Vec<StaffRule<u32>>),
+    Drops(HashMap<u8, Vec<BlockDrop>>),
+}
+
 pub struct StaffInfoPlugin;
 
 impl Plugin for StaffInfoPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(StaffRulePlugin);
-        app.insert_resource(StaffInfoStroge {
-            data: HashMap::default(),
-            voxel_staff: HashMap::default(),
-            filled_map: HashMap::default(),
-        });
+        if !app.is_plugin_added::<ContentWatcherPlugin>() {
+            app.add_plugins(ContentWatcherPlugin);
+        }
+        app.insert_resource(StaffInfoStroge::empty());
+        app.add_event::<StaffSyncEvent>();
         app.add_systems(Startup, setup.in_set(StaffSet::Init));
+        app.add_systems(PreUpdate, apply_staff_sync);
     }
 }
 
 fn setup(mut storge: ResMut<StaffInfoStroge>, asset_server: Res<AssetServer>) {
-    load_staff_configs(String::from("staff.ron"), &mut storge, Some(&asset_server));
+    load_staff_configs(STAFF_RON, &mut storge, Some(&asset_server));
+}
+
+// 整体替换 不会出现只更新了一半的情况
+fn apply_staff_sync(
+    mut events: EventReader<StaffSyncEvent>,
+    mut storge: ResMut<StaffInfoStroge>,
+    mut staff_rules: ResMut<StaffRules>,
+    asset_server: Res<AssetServer>,
+) {
+    for event in events.iter() {
+        match event {
+            StaffSyncEvent::Staff(configs) => {
+                storge.replace_configs(configs.clone(), Some(&asset_server));
+                println!("物品定义更新了 一共{}种物品", storge.data.len());
+            }
+            StaffSyncEvent::Rules(rules) => {
+                *staff_rules = StaffRules::from_list(rules.clone());
+                println!("合成规则更新了 一共{}条", staff_rules.rules.len());
+            }
+            StaffSyncEvent::Drops(drops) => {
+                storge.block_drops = drops.clone();
+                println!("方块掉落物更新了 一共{}种方块", drops.len());
+            }
+        }
+    }
 }
 
 pub struct ServerStaffInfoPlugin;
@@ -136,17 +202,16 @@ pub struct ServerStaffInfoPlugin;
 impl Plugin for ServerStaffInfoPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(StaffRulePlugin);
-        app.insert_resource(StaffInfoStroge {
-            data: HashMap::default(),
-            voxel_staff: HashMap::default(),
-            filled_map: HashMap::default(),
-        });
+        if !app.is_plugin_added::<ContentWatcherPlugin>() {
+            app.add_plugins(ContentWatcherPlugin);
+        }
+        app.insert_resource(StaffInfoStroge::empty());
         app.add_systems(Startup, server_setup.in_set(StaffSet::Init));
     }
 }
 
 fn server_setup(mut storge: ResMut<StaffInfoStroge>) {
-    load_staff_configs(String::from("staff.ron"), &mut storge, None);
+    load_staff_configs(STAFF_RON, &mut storge, None);
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -157,59 +222,30 @@ pub struct StaffMeta {
     staff_type: StaffType,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FilledMeta {
-    // 体素类型的id
-    voxel_id: usize,
-    filled_config: Vec<FilledPair>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FilledPair {
-    possible: f32,
-    staff_id: usize,
-    times: usize,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StaffConfigs {
     pub configs: Vec<StaffMeta>,
-    // 掉落物的特性
-    pub filled_configs: Vec<FilledMeta>,
+}
+
+impl StaffConfigs {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        ron::de::from_str(&content).map_err(|err| err.to_string())
+    }
 }
 
 fn load_staff_configs(
-    path: String,
+    path: &str,
     staff_info_stroge: &mut StaffInfoStroge,
     asset_server: Option<&AssetServer>,
 ) {
     // 加载文件到数据
-    match std::fs::File::open(path) {
-        Ok(file) => {
-            let res: StaffConfigs = ron::de::from_reader(file).unwrap();
-            for mate in res.configs {
-                if let Some(asset_server) = asset_server {
-                    staff_info_stroge.register(Staff {
-                        id: mate.id,
-                        name: mate.name,
-                        icon: asset_server.load(mate.icon_string),
-                        staff_type: mate.staff_type,
-                    });
-                } else {
-                    staff_info_stroge.register(Staff {
-                        id: mate.id,
-                        name: mate.name,
-                        icon: Handle::default(),
-                        staff_type: mate.staff_type,
-                    });
-                }
-            }
-            for mate in res.filled_configs {
-                staff_info_stroge.register_filled(mate);
-            }
+    match StaffConfigs::load(path) {
+        Ok(configs) => {
+            *staff_info_stroge = StaffInfoStroge::from_configs(configs, asset_server);
         }
-        Err(_) => {
-            error!("读取Staff配置数据失败");
+        Err(err) => {
+            error!("读取Staff配置数据失败{}", err);
         }
     }
 }
@@ -6,6 +6,8 @@ use bevy::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::common::content_watcher::STAFF_RULES_RON;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StaffNumPair {
     pub staff_id: usize,
@@ -30,6 +32,22 @@ pub struct StaffRules {
     pub rules: HashMap<u32, StaffRule<u32>>,
 }
 
+impl StaffRules {
+    pub fn from_list(list: Vec<StaffRule<u32>>) -> Self {
+        Self {
+            rules: list.into_iter().map(|rule| (rule.id, rule)).collect(),
+        }
+    }
+
+    // 读取失败时返回错误 不会替换原来的规则
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let list: Vec<StaffRule<u32>> =
+            ron::de::from_str(&content).map_err(|err| err.to_string())?;
+        Ok(Self::from_list(list))
+    }
+}
+
 // 加载这里的数据
 
 pub struct StaffRulePlugin;
@@ -44,16 +62,21 @@ impl Plugin for StaffRulePlugin {
 }
 
 fn setup(mut staff_rules: ResMut<StaffRules>) {
-    let path = "staff_rules.ron";
-    match std::fs::File::open(path) {
-        Ok(file) => {
-            let res: Vec<StaffRule<u32>> = ron::de::from_reader(file).unwrap();
-            for ele in res {
-                staff_rules.rules.insert(ele.id.clone(), ele.clone());
-            }
+    match StaffRules::load(STAFF_RULES_RON) {
+        Ok(rules) => {
+            *staff_rules = rules;
         }
-        Err(_) => {
-            error!("合成规则表获取失败");
+        Err(err) => {
+            error!("合成规则表获取失败{}", err);
         }
     }
 }
+
+#[test]
+fn test_staff_rules_load() {
+    let rules = StaffRules::load(STAFF_RULES_RON).unwrap();
+    assert!(rules.rules.values().all(|rule| !rule.output.is_empty()));
+    // 解析失败时返回错误 不会 panic
+    assert!(StaffRules::load("blocks.ron").is_err());
+    assert!(StaffRules::load("不存在.ron").is_err());
+}
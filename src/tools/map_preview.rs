@@ -0,0 +1,288 @@
+// 地图预览
+// 只使用cpu 通过地形生成算法采样一片区域 输出高度图 群落图 和地表方块颜色图
+
+use std::path::Path;
+
+use bevy::{prelude::IVec3, utils::HashMap};
+use image::{Rgb, RgbImage};
+use ndshape::{ConstShape, ConstShape2u32, ConstShape3u32};
+
+use crate::{
+    client::voxels::voxel_materail_config::MaterailConfiguration,
+    voxel_world::{
+        biomes::{biomes_noise, BiomesKind},
+        chunk::{generate_offset_array_with_y_0, ChunkKey},
+        map_generator::gen_chunk_data_by_seed,
+        pos::{ChunkPos, LocalPos},
+        voxel::Voxel,
+        world_height::WORLD_HEIGHT,
+    },
+    CHUNK_SIZE, CHUNK_SIZE_U32,
+};
+
+type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+type PanelShape = ConstShape2u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+
+// 一个位置的采样结果
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnSample {
+    // 最上面方块的高度
+    pub height: i32,
+    // 最上面的方块
+    pub top: Voxel,
+    pub biomes: BiomesKind,
+}
+
+// 体素id对应的颜色
+#[derive(Debug, Clone, Default)]
+pub struct VoxelPalette {
+    pub colors: HashMap<u8, [u8; 3]>,
+}
+
+impl VoxelPalette {
+    const MISSING: [u8; 3] = [255, 0, 255];
+
+    /**
+     * 通过体素材质配置生成调色板
+     * 使用方块顶面(法向量4)的贴图 没有时使用默认贴图 取贴图的平均颜色
+     */
+    pub fn from_material_config(config: &MaterailConfiguration, assets_dir: &Path) -> Self {
+        let mut colors = HashMap::new();
+        for (id, type_config) in config.voxels.iter() {
+            let voxel_config = type_config.normal.get(&4).unwrap_or(&type_config.default);
+            if voxel_config.path.is_empty() {
+                continue;
+            }
+            match image::open(assets_dir.join(&voxel_config.path)) {
+                Ok(image) => {
+                    colors.insert(*id, average_color(&image.to_rgba8()));
+                }
+                Err(err) => {
+                    println!("读取贴图失败{}: {:?}", voxel_config.path, err);
+                }
+            }
+        }
+        Self { colors }
+    }
+
+    pub fn get(&self, id: u8) -> [u8; 3] {
+        *self.colors.get(&id).unwrap_or(&Self::MISSING)
+    }
+}
+
+// 计算不透明像素的平均颜色
+fn average_color(image: &image::RgbaImage) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    let mut count = 0u64;
+    for pixel in image.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        sum[0] += r as u64;
+        sum[1] += g as u64;
+        sum[2] += b as u64;
+        count += 1;
+    }
+    if count == 0 {
+        return VoxelPalette::MISSING;
+    }
+    [
+        (sum[0] / count) as u8,
+        (sum[1] / count) as u8,
+        (sum[2] / count) as u8,
+    ]
+}
+
+// 群落图中的颜色
+pub fn biomes_color(biomes: BiomesKind) -> [u8; 3] {
+    match biomes {
+        BiomesKind::Basic => [96, 168, 72],
+        BiomesKind::Dry => [190, 160, 90],
+        BiomesKind::Snow => [235, 240, 245],
+        BiomesKind::Sand => [230, 205, 130],
+        BiomesKind::Blue => [40, 130, 120],
+    }
+}
+
+/**
+ * 采样一个区块列
+ * 从上往下生成区块 直到每个位置都找到了最上面的方块
+ */
+pub fn sample_column(seed: i32, column: IVec3) -> Vec<ColumnSample> {
+    let column_key = ChunkKey::new(IVec3::new(column.x, 0, column.z));
+    let biomes = biomes_noise(column_key, seed);
+    let mut samples: Vec<Option<ColumnSample>> = vec![None; PanelShape::SIZE as usize];
+    let mut found = 0;
+
+    for chunk_y in WORLD_HEIGHT.chunk_ys().rev() {
+        let chunk_key = ChunkKey::new(IVec3::new(column.x, chunk_y, column.z));
+        let (voxels, _) = gen_chunk_data_by_seed(seed, chunk_key);
+        for plane_index in 0..PanelShape::SIZE {
+            if samples[plane_index as usize].is_some() {
+                continue;
+            }
+            let [x, z] = PanelShape::delinearize(plane_index);
+            for y in (0..CHUNK_SIZE_U32).rev() {
+                let voxel = voxels[SampleShape::linearize([x, y, z]) as usize];
+                if voxel.id != Voxel::EMPTY.id {
+                    samples[plane_index as usize] = Some(ColumnSample {
+                        height: chunk_key.block(LocalPos([x, y, z])).0.y,
+                        top: voxel,
+                        biomes: BiomesKind::from_attr(biomes[plane_index as usize]),
+                    });
+                    found += 1;
+                    break;
+                }
+            }
+        }
+        if found == PanelShape::SIZE {
+            break;
+        }
+    }
+
+    samples
+        .into_iter()
+        .enumerate()
+        .map(|(plane_index, sample)| {
+            sample.unwrap_or(ColumnSample {
+                height: WORLD_HEIGHT.min_y(),
+                top: Voxel::EMPTY,
+                biomes: BiomesKind::from_attr(biomes[plane_index]),
+            })
+        })
+        .collect()
+}
+
+// 最下面的方块是0 最上面的方块是255
+fn height_value(height: i32) -> u8 {
+    let top = WORLD_HEIGHT.max_y() - 1;
+    let height = height.clamp(WORLD_HEIGHT.min_y(), top);
+    ((height - WORLD_HEIGHT.min_y()) * 255 / (top - WORLD_HEIGHT.min_y())) as u8
+}
+
+pub struct MapPreview {
+    // 最小的区块列
+    pub min_column: IVec3,
+    // 区块列的数量
+    pub size: u32,
+    pub columns: HashMap<IVec3, Vec<ColumnSample>>,
+}
+
+impl MapPreview {
+    /**
+     * 采样 以 center_x center_z 为中心 radius 为半径的正方形区域
+     * threads 个线程同时生成
+     */
+    pub fn sample(seed: i32, center_x: f32, center_z: f32, radius: f32, threads: usize) -> Self {
+        let mut center = ChunkPos::from_vec3(bevy::prelude::Vec3::new(center_x, 0., center_z)).0;
+        center.y = 0;
+        let chunk_distance = radius as i32 / CHUNK_SIZE;
+        let columns: Vec<IVec3> = generate_offset_array_with_y_0(chunk_distance)
+            .into_iter()
+            .map(|offset| center + offset)
+            .collect();
+        let total = columns.len();
+        let threads = threads.max(1);
+        let per_thread = (total + threads - 1) / threads;
+        println!("开始采样 {} 个区块列 使用 {} 个线程", total, threads);
+
+        let mut result = HashMap::new();
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = columns
+                .chunks(per_thread.max(1))
+                .map(|list| {
+                    scope.spawn(move || {
+                        list.iter()
+                            .map(|column| (*column, sample_column(seed, *column)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            for handle in handles {
+                for (column, samples) in handle.join().unwrap() {
+                    result.insert(column, samples);
+                }
+                println!("采样进度 {}/{}", result.len(), total);
+            }
+        });
+
+        Self {
+            min_column: center - IVec3::new(chunk_distance, 0, chunk_distance),
+            size: (chunk_distance * 2 + 1) as u32,
+            columns: result,
+        }
+    }
+
+    fn render(&self, mut color_fn: impl FnMut(&ColumnSample) -> [u8; 3]) -> RgbImage {
+        let pixels = self.size * CHUNK_SIZE_U32;
+        let mut image = RgbImage::new(pixels, pixels);
+        for (column, samples) in self.columns.iter() {
+            let base_x = ((column.x - self.min_column.x) * CHUNK_SIZE) as u32;
+            let base_z = ((column.z - self.min_column.z) * CHUNK_SIZE) as u32;
+            for (plane_index, sample) in samples.iter().enumerate() {
+                let [x, z] = PanelShape::delinearize(plane_index as u32);
+                image.put_pixel(base_x + x, base_z + z, Rgb(color_fn(sample)));
+            }
+        }
+        image
+    }
+
+    // 高度图 从最低到最高映射成灰度
+    pub fn heightmap(&self) -> RgbImage {
+        self.render(|sample| {
+            let value = height_value(sample.height);
+            [value, value, value]
+        })
+    }
+
+    // 群落图
+    pub fn biomes_map(&self) -> RgbImage {
+        self.render(|sample| biomes_color(sample.biomes))
+    }
+
+    // 地表方块颜色图
+    pub fn top_block_map(&self, palette: &VoxelPalette) -> RgbImage {
+        self.render(|sample| {
+            if sample.top.id == Voxel::EMPTY.id {
+                [0, 0, 0]
+            } else {
+                palette.get(sample.top.id)
+            }
+        })
+    }
+
+    // 输出全部图片到目录下
+    pub fn save_all(&self, out_dir: &Path, palette: &VoxelPalette) -> image::ImageResult<()> {
+        std::fs::create_dir_all(out_dir)?;
+        self.heightmap().save(out_dir.join("heightmap.png"))?;
+        self.biomes_map().save(out_dir.join("biomes.png"))?;
+        self.top_block_map(palette)
+            .save(out_dir.join("top_block.png"))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_average_color() {
+    use image::{Rgba, RgbaImage};
+    // 半透明以下的像素不参与计算
+    let mut image = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 127]));
+    image.put_pixel(0, 0, Rgba([100, 0, 40, 255]));
+    image.put_pixel(1, 0, Rgba([200, 50, 0, 128]));
+    assert_eq!(average_color(&image), [150, 25, 20]);
+    // 全部透明时使用缺失的颜色
+    let image = RgbaImage::from_pixel(2, 2, Rgba([10, 20, 30, 0]));
+    assert_eq!(average_color(&image), VoxelPalette::MISSING);
+}
+
+#[test]
+fn test_height_value() {
+    assert_eq!(height_value(WORLD_HEIGHT.min_y()), 0);
+    assert_eq!(height_value(WORLD_HEIGHT.max_y() - 1), 255);
+    // 超出范围的高度不会溢出
+    assert_eq!(height_value(WORLD_HEIGHT.min_y() - 10), 0);
+    assert_eq!(height_value(WORLD_HEIGHT.max_y() + 10), 255);
+    let middle = (WORLD_HEIGHT.min_y() + WORLD_HEIGHT.max_y()) / 2;
+    assert!(height_value(middle) > 0 && height_value(middle) < 255);
+}
@@ -1,21 +1,10 @@
 // 基础大陆
 
-use bevy::prelude::Vec3;
 use ndshape::ConstShape;
-use rand::Rng;
 
-use crate::{
-    tools::chunk_key_any_xyz_to_vec3,
-    voxel_world::{
-        chunk::ChunkKey,
-        voxel::{AppleLeaf, AppleWood, Grass, Soli, Sown, Stone, Voxel, VoxelMaterial},
-    },
-};
+use crate::voxel_world::voxel::{Grass, Soli, Sown, Stone, Voxel, VoxelMaterial};
 
-use super::{
-    find_out_chunk_keys, BiomesGenerator, SampleShape, TreeGentor, MOUNTAIN_LEVEL, SEE_LEVEL,
-    SNOW_LEVEL,
-};
+use super::{BiomesGenerator, SampleShape, MOUNTAIN_LEVEL, SEE_LEVEL, SNOW_LEVEL};
 
 // 基础大陆
 // 1. 雪顶
@@ -57,40 +46,4 @@ impl BiomesGenerator for BasicLandBiomes {
             }
         }
     }
-
-    fn make_tree_with_info(
-        &self,
-        chunk_key: crate::voxel_world::chunk::ChunkKey,
-        voxels: &mut Vec<Voxel>,
-        _chunk_index: u32,
-        _plane_index: u32,
-        height: f32,
-        xyz: [u32; 3],
-    ) -> Option<(Vec<ChunkKey>, TreeGentor)> {
-        if height >= MOUNTAIN_LEVEL {
-            return None;
-        }
-        let mut rng = rand::thread_rng();
-        let root_pos = chunk_key_any_xyz_to_vec3(chunk_key, xyz);
-
-        // 判断 是否需要给其他的模块处理？
-        let h = rng.gen_range(1..5);
-        let r = rng.gen_range(2.0..4.6);
-
-        let leaf_center = root_pos + Vec3::new(0.0, h as f32 - 1.0, 0.0);
-        // 这里 可以判断的又5个方向
-        let mut tree_gentor = TreeGentor {
-            tree: AppleWood::into_voxel(),
-            leaf: AppleLeaf::into_voxel(),
-            trunk_params: (root_pos, h),
-            leafs_params: (leaf_center, r, 0.0),
-        };
-        tree_gentor.make_tree_for_chunk(voxels, chunk_key.clone());
-
-        let vec_list = find_out_chunk_keys(xyz, chunk_key.clone(), h, r.ceil() as u32);
-        if vec_list.len() > 0 {
-            return Some((vec_list, tree_gentor));
-        }
-        None
-    }
 }
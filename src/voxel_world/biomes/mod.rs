@@ -9,10 +9,10 @@ use noise::{
     utils::NoiseMapBuilder,
     Worley,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
     server::{async_chunk::ChunkResultTasks, message_def::chunk_result::ChunkResult},
-    tools::chunk_key_any_xyz_to_vec3,
     CHUNK_SIZE, CHUNK_SIZE_U32,
 };
 
@@ -20,22 +20,31 @@ use self::{
     basic_land::BasicLandBiomes,
     blue_land::BuleLandBoimes,
     dry_land::DryLandBiomes,
+    plant::{PlantGentor, PLANT_REGISTRY},
     sand_land::SandLandBiomes,
     sdf::{sd_cut_sphere, trunk},
     snow_land::SnowLandBiomes,
+    structure::{StructureGentor, STRUCTURE_REGISTRY},
 };
 
 use super::{
-    chunk::ChunkKey, chunk_map::ChunkMap, compress::compress, map_database::DbSaveTasks,
-    voxel::Voxel,
+    chunk::ChunkKey,
+    chunk_map::ChunkMap,
+    compress::compress,
+    dimension::DimensionId,
+    map_database::DbSaveTasks,
+    pos::LocalPos,
+    voxel::{Voxel, VoxelMaterial, Water},
 };
 
 pub mod basic_land;
 pub mod blue_land;
 pub mod dry_land;
+pub mod plant;
 pub mod sand_land;
 pub mod sdf;
 pub mod snow_land;
+pub mod structure;
 
 pub type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
 pub type PanelShape = ConstShape2u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
@@ -46,7 +55,7 @@ pub fn biomes_generate(
     seed: i32,
     surface_index: Vec<u32>,
     voxels: &mut Vec<Voxel>,
-) -> Vec<(Vec<ChunkKey>, TreeGentor)> {
+) -> Vec<(Vec<ChunkKey>, OtherGentor)> {
     let mut ret = Vec::new();
     if surface_index.len() == 0 {
         return ret;
@@ -55,36 +64,111 @@ pub fn biomes_generate(
     let noise = biomes_noise(chunk_key, seed);
     // 这里产生一个 种树的噪声
     let tree_noise = tree_noise(chunk_key, seed);
+    // 结构要等地表全部生成后再写入 防止被覆盖
+    let mut structures: Vec<StructureGentor> = Vec::new();
 
     for index in surface_index {
         // 由噪声生产的特征值
-        let [x, _, z] = SampleShape::delinearize(index);
+        let [x, y, z] = SampleShape::delinearize(index);
         let index_2d = PanelShape::linearize([x, z]);
         let attr = noise[index_2d as usize];
         let generator = get_generator_by_attr(attr);
         generator.gen_land(chunk_key.clone(), voxels, index, index_2d);
-        // fixme: 这里要记录对于其他方块的影响
-        if tree_noise[index_2d as usize] > 0.99 {
-            if let Some(rs) = generator.make_tree(chunk_key.clone(), voxels, index, index_2d) {
-                ret.push(rs);
+        let surface = chunk_key.block(LocalPos([x, y, z])).0;
+        if let Some(structure) = STRUCTURE_REGISTRY.pick(seed, BiomesKind::from_attr(attr), surface)
+        {
+            structures.push(structure);
+            continue;
+        }
+        let height = (chunk_key.0.y * CHUNK_SIZE) as f32 + y as f32;
+        if let Some(plant) = PLANT_REGISTRY.pick(
+            seed,
+            BiomesKind::from_attr(attr),
+            surface.as_vec3() + Vec3::splat(0.5),
+            height,
+            tree_noise[index_2d as usize],
+        ) {
+            plant.make_plant_for_chunk(voxels, chunk_key);
+            let keys: Vec<ChunkKey> = plant
+                .chunk_keys()
+                .into_iter()
+                .filter(|key| *key != chunk_key)
+                .collect();
+            if keys.len() > 0 {
+                ret.push((keys, OtherGentor::Plant(plant)));
+            }
+            continue;
+        }
+        // 地表装饰 只放在当前区块内 区块顶部的地表就不放了
+        if y + 1 < CHUNK_SIZE_U32
+            && voxels[index as usize].id != Voxel::EMPTY.id
+            && voxels[index as usize].id != Water::ID
+        {
+            let up_index = SampleShape::linearize([x, y + 1, z]) as usize;
+            if voxels[up_index].id == Voxel::EMPTY.id {
+                if let Some(decoration) =
+                    PLANT_REGISTRY.pick_decoration(seed, BiomesKind::from_attr(attr), surface)
+                {
+                    voxels[up_index] = decoration;
+                }
             }
         }
     }
+
+    for structure in structures {
+        structure.make_structure_for_chunk(voxels, chunk_key);
+        let keys: Vec<ChunkKey> = structure
+            .chunk_keys()
+            .into_iter()
+            .filter(|key| *key != chunk_key)
+            .collect();
+        if keys.len() > 0 {
+            ret.push((keys, OtherGentor::Structure(structure)));
+        }
+    }
     ret
 }
 
+// 生物群落类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BiomesKind {
+    // 基础大陆
+    Basic,
+    // 干燥大陆
+    Dry,
+    // 雪地
+    Snow,
+    // 沙漠
+    Sand,
+    // 苍翠大陆
+    Blue,
+}
+
+impl BiomesKind {
+    // 通过群落噪声的特征值获取群落类型
+    pub fn from_attr(data: f32) -> Self {
+        if data < 0.1 {
+            BiomesKind::Basic
+        } else if data < 0.4 {
+            BiomesKind::Dry
+        } else if data < 0.6 {
+            BiomesKind::Snow
+        } else if data < 0.8 {
+            BiomesKind::Sand
+        } else {
+            BiomesKind::Blue
+        }
+    }
+}
+
 // 获取不同的生成器
 fn get_generator_by_attr(data: f32) -> Box<dyn BiomesGenerator> {
-    return if data < 0.1 {
-        BasicLandBiomes.into_boxed_generator()
-    } else if data < 0.4 {
-        DryLandBiomes.into_boxed_generator()
-    } else if data < 0.6 {
-        SnowLandBiomes.into_boxed_generator()
-    } else if data < 0.8 {
-        SandLandBiomes.into_boxed_generator()
-    } else {
-        BuleLandBoimes.into_boxed_generator()
+    return match BiomesKind::from_attr(data) {
+        BiomesKind::Basic => BasicLandBiomes.into_boxed_generator(),
+        BiomesKind::Dry => DryLandBiomes.into_boxed_generator(),
+        BiomesKind::Snow => SnowLandBiomes.into_boxed_generator(),
+        BiomesKind::Sand => SandLandBiomes.into_boxed_generator(),
+        BiomesKind::Blue => BuleLandBoimes.into_boxed_generator(),
     };
 }
 
@@ -156,39 +240,6 @@ pub trait BiomesGenerator: 'static + Sync + Send {
             [x, y, z],
         );
     }
-
-    fn make_tree(
-        &self,
-        chunk_key: ChunkKey,
-        voxels: &mut Vec<Voxel>,
-        chunk_index: u32,
-        plane_index: u32,
-    ) -> Option<(Vec<ChunkKey>, TreeGentor)> {
-        let base_y: f32 = (chunk_key.0.y * CHUNK_SIZE) as f32;
-        let [x, y, z] = SampleShape::delinearize(chunk_index);
-        let height = base_y + y as f32;
-        self.make_tree_with_info(
-            chunk_key,
-            voxels,
-            chunk_index,
-            plane_index,
-            height,
-            [x, y, z],
-        )
-    }
-
-    fn make_tree_with_info(
-        &self,
-        _chunk_key: ChunkKey,
-        _voxels: &mut Vec<Voxel>,
-        _chunk_index: u32,
-        _plane_index: u32,
-        _height: f32,
-        _xyz: [u32; 3],
-    ) -> Option<(Vec<ChunkKey>, TreeGentor)> {
-        // do nothing;
-        None
-    }
 }
 
 pub trait IntoBoxedTerrainGenerator: BiomesGenerator + Sized {
@@ -225,8 +276,7 @@ impl TreeGentor {
         );
 
         for index in 0..SampleShape::SIZE {
-            let inner_xyz = SampleShape::delinearize(index);
-            let check_pos = chunk_key_any_xyz_to_vec3(chunk_key, inner_xyz);
+            let check_pos = chunk_key.block(LocalPos::from_index(index)).center();
             if trunk_fn(check_pos.clone()) <= 0.0 {
                 voxels[index as usize] = self.tree.clone();
             } else if leaf_fn(check_pos.clone()) <= 0.0
@@ -238,6 +288,26 @@ impl TreeGentor {
     }
 }
 
+// 会延伸到其他区块的生成物
+#[derive(Debug, Clone)]
+pub enum OtherGentor {
+    Tree(TreeGentor),
+    Structure(StructureGentor),
+    Plant(PlantGentor),
+}
+
+impl OtherGentor {
+    pub fn make_for_chunk(&mut self, voxels: &mut Vec<Voxel>, chunk_key: ChunkKey) {
+        match self {
+            OtherGentor::Tree(tree_gentor) => tree_gentor.make_tree_for_chunk(voxels, chunk_key),
+            OtherGentor::Structure(structure) => {
+                structure.make_structure_for_chunk(voxels, chunk_key)
+            }
+            OtherGentor::Plant(plant) => plant.make_plant_for_chunk(voxels, chunk_key),
+        }
+    }
+}
+
 pub fn find_out_chunk_keys(xyz: [u32; 3], chunk_key: ChunkKey, h: u32, r: u32) -> Vec<ChunkKey> {
     let mut ret = Vec::new();
     let [x, y, z] = xyz;
@@ -283,17 +353,17 @@ pub fn find_out_chunk_keys(xyz: [u32; 3], chunk_key: ChunkKey, h: u32, r: u32) -
 
 #[derive(Resource)]
 pub struct OtherTreeTasksMap {
-    pub tree_map: HashMap<ChunkKey, Vec<TreeGentor>>,
+    pub tree_map: HashMap<ChunkKey, Vec<OtherGentor>>,
 }
 
 impl OtherTreeTasksMap {
-    pub fn insert(&mut self, list: Vec<(Vec<ChunkKey>, TreeGentor)>) {
-        for (keys, tree_gentor) in list.into_iter() {
+    pub fn insert(&mut self, list: Vec<(Vec<ChunkKey>, OtherGentor)>) {
+        for (keys, gentor) in list.into_iter() {
             for key in keys.iter() {
                 self.tree_map
                     .entry(*key)
                     .or_insert(Vec::new())
-                    .push(tree_gentor.clone());
+                    .push(gentor.clone());
             }
         }
     }
@@ -310,45 +380,57 @@ impl Plugin for OtherTreePlugin {
     }
 }
 
-fn deal_other_tree(
-    mut db_save_task: ResMut<DbSaveTasks>,
-    mut chunk_map: ResMut<ChunkMap>,
-    mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
-    mut tasks: ResMut<ChunkResultTasks>,
-) {
-    let pool = AsyncComputeTaskPool::get();
+// 把等待中的树和结构写入已经加载的区块 返回被修改过的区块
+pub fn apply_other_tree_tasks(
+    chunk_map: &mut ChunkMap,
+    other_tree_tasks_map: &mut OtherTreeTasksMap,
+) -> HashSet<ChunkKey> {
     let mut exit_keys: HashSet<ChunkKey> = HashSet::new();
 
     for (chunk_key, list) in other_tree_tasks_map.tree_map.iter_mut() {
         if let Some(voxels) = chunk_map.map_data.get_mut(&chunk_key.clone()) {
-            for tree_gentor in list {
-                tree_gentor.make_tree_for_chunk(voxels, chunk_key.clone());
+            for gentor in list {
+                // 生成器只关心坐标 和生成时一样使用主世界的区块
+                gentor.make_for_chunk(voxels, chunk_key.with_dimension(DimensionId::OVERWORLD));
                 exit_keys.insert(chunk_key.clone());
             }
         }
     }
 
+    for key in exit_keys.iter() {
+        other_tree_tasks_map.tree_map.remove(key);
+    }
+    exit_keys
+}
+
+fn deal_other_tree(
+    mut db_save_task: ResMut<DbSaveTasks>,
+    mut chunk_map: ResMut<ChunkMap>,
+    mut other_tree_tasks_map: ResMut<OtherTreeTasksMap>,
+    mut tasks: ResMut<ChunkResultTasks>,
+) {
+    let pool = AsyncComputeTaskPool::get();
+    let exit_keys = apply_other_tree_tasks(chunk_map.as_mut(), other_tree_tasks_map.as_mut());
+
     for key in exit_keys {
-        if let Some(_) = other_tree_tasks_map.tree_map.remove(&key) {
-            if let Some(data) = chunk_map.map_data.get(&key) {
-                let voxels = data.clone();
-                let (buffer, tree) = compress(voxels.clone());
-                let message = if buffer.len() == 0 {
-                    bincode::serialize(&ChunkResult::ChunkSame((key, voxels[0]))).unwrap()
-                } else {
-                    bincode::serialize(&ChunkResult::UpdateChunkData {
-                        key: key,
-                        data: (buffer, tree),
-                    })
-                    .unwrap()
-                };
-
-                let task = pool.spawn(async move { (0, message) });
-                tasks.tasks.push(task);
-
-                let task = pool.spawn(async move { (key.as_u8_array(), voxels.clone()) });
-                db_save_task.tasks.push(task);
-            }
+        if let Some(data) = chunk_map.map_data.get(&key) {
+            let voxels = data.clone();
+            let (buffer, tree) = compress(voxels.clone());
+            let message = if buffer.len() == 0 {
+                bincode::serialize(&ChunkResult::ChunkSame((key, voxels[0]))).unwrap()
+            } else {
+                bincode::serialize(&ChunkResult::UpdateChunkData {
+                    key: key,
+                    data: (buffer, tree),
+                })
+                .unwrap()
+            };
+
+            let task = pool.spawn(async move { (0, key.1, message) });
+            tasks.tasks.push(task);
+
+            let task = pool.spawn(async move { (key.as_u8_array(), voxels.clone()) });
+            db_save_task.tasks.push(task);
         }
     }
 }
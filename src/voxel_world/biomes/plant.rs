@@ -0,0 +1,404 @@
+// 植物
+// 植物的形状由多个 sdf 组合而成 在 plants.ron 中配置
+// 每个群落引用自己可以生长的植物
+
+use bevy::{
+    prelude::{IVec3, Vec3},
+    utils::HashMap,
+};
+use lazy_static::lazy_static;
+use ndshape::ConstShape;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::voxel_world::{
+    chunk::ChunkKey,
+    pos::{ChunkPos, LocalPos},
+    voxel::Voxel,
+};
+
+use super::{
+    sdf::{sd_box, sd_capsule, sd_cone, sd_cut_sphere, sd_ellipsoid, sd_sphere, trunk},
+    structure::position_hash,
+    BiomesKind, SampleShape,
+};
+
+pub const PLANTS_RON: &str = "plants.ron";
+
+lazy_static! {
+    pub static ref PLANT_REGISTRY: PlantRegistry = PlantRegistry::load(PLANTS_RON);
+}
+
+// 形状的位置相对于哪里
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum PlantAnchor {
+    // 根部 也就是地表方块
+    #[default]
+    Root,
+    // 树干的顶部
+    Top,
+}
+
+// 植物的形状 offset 等位置都是相对于 anchor 的偏移
+// 半径 高度等大小会乘以植物的缩放 偏移不会缩放
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PlantShape {
+    // 竖直的树干 高度由 trunk_height 决定
+    Trunk,
+    Sphere {
+        offset: (f32, f32, f32),
+        radius: f32,
+    },
+    // 被切开的球 cut 是切开的高度
+    CutSphere {
+        offset: (f32, f32, f32),
+        radius: f32,
+        cut: f32,
+    },
+    Ellipsoid {
+        offset: (f32, f32, f32),
+        radii: (f32, f32, f32),
+    },
+    // 尖端朝上的圆锥 offset 是底面中心
+    Cone {
+        offset: (f32, f32, f32),
+        height: f32,
+        radius: f32,
+    },
+    // 胶囊体 可以做树枝
+    Capsule {
+        from: (f32, f32, f32),
+        to: (f32, f32, f32),
+        radius: f32,
+    },
+    Box {
+        offset: (f32, f32, f32),
+        half: (f32, f32, f32),
+    },
+}
+
+type SdfFn = Box<dyn FnMut(Vec3) -> f32>;
+
+impl PlantShape {
+    // 生成 sdf 函数 以及包围盒
+    fn build(
+        &self,
+        anchor: Vec3,
+        root: Vec3,
+        trunk_height: u32,
+        scale: f32,
+    ) -> (SdfFn, Vec3, Vec3) {
+        let v = |(x, y, z): (f32, f32, f32)| anchor + Vec3::new(x, y, z);
+        match self {
+            PlantShape::Trunk => (
+                Box::new(trunk(root, trunk_height)),
+                root,
+                root + Vec3::new(0.0, trunk_height as f32, 0.0),
+            ),
+            PlantShape::Sphere { offset, radius } => {
+                let center = v(*offset);
+                let r = radius * scale;
+                (
+                    Box::new(sd_sphere(center, r)),
+                    center - Vec3::splat(r),
+                    center + Vec3::splat(r),
+                )
+            }
+            PlantShape::CutSphere {
+                offset,
+                radius,
+                cut,
+            } => {
+                let center = v(*offset);
+                let r = radius * scale;
+                (
+                    Box::new(sd_cut_sphere(center, r, cut * scale)),
+                    center - Vec3::splat(r),
+                    center + Vec3::splat(r),
+                )
+            }
+            PlantShape::Ellipsoid { offset, radii } => {
+                let center = v(*offset);
+                let radii = Vec3::new(radii.0, radii.1, radii.2) * scale;
+                (
+                    Box::new(sd_ellipsoid(center, radii)),
+                    center - radii,
+                    center + radii,
+                )
+            }
+            PlantShape::Cone {
+                offset,
+                height,
+                radius,
+            } => {
+                let base = v(*offset);
+                let h = height * scale;
+                let r = radius * scale;
+                (
+                    Box::new(sd_cone(base, h, r)),
+                    base - Vec3::new(r, 0.0, r),
+                    base + Vec3::new(r, h, r),
+                )
+            }
+            PlantShape::Capsule { from, to, radius } => {
+                let a = v(*from);
+                let b = v(*to);
+                let r = radius * scale;
+                (
+                    Box::new(sd_capsule(a, b, r)),
+                    a.min(b) - Vec3::splat(r),
+                    a.max(b) + Vec3::splat(r),
+                )
+            }
+            PlantShape::Box { offset, half } => {
+                let center = v(*offset);
+                let half = Vec3::new(half.0, half.1, half.2) * scale;
+                (Box::new(sd_box(center, half)), center - half, center + half)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlantPart {
+    pub shape: PlantShape,
+    pub voxel: Voxel,
+    #[serde(default)]
+    pub anchor: PlantAnchor,
+    // 是否覆盖已经存在的方块 树干一般需要覆盖
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+fn default_scale() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlantConfig {
+    // 按照顺序写入的形状
+    pub parts: Vec<PlantPart>,
+    // 树干高度的范围
+    #[serde(default)]
+    pub trunk_height: (u32, u32),
+    // 形状缩放的范围
+    #[serde(default = "default_scale")]
+    pub scale: (f32, f32),
+    // 可以生长的地表高度 和 SEE_LEVEL MOUNTAIN_LEVEL 使用相同的高度
+    #[serde(default)]
+    pub min_height: Option<f32>,
+    #[serde(default)]
+    pub max_height: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BiomesPlantConfig {
+    // 种树噪声超过这个值时才会生长植物
+    pub threshold: f32,
+    // 植物的名字 以及权重
+    pub plants: Vec<(String, u32)>,
+    // 地表装饰方块 以及每个地表方块上出现的概率
+    #[serde(default)]
+    pub decorations: Vec<(Voxel, f32)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PlantConfigs {
+    pub plants: HashMap<String, PlantConfig>,
+    pub biomes: HashMap<BiomesKind, BiomesPlantConfig>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlantRegistry {
+    pub names: Vec<String>,
+    pub plants: Vec<PlantConfig>,
+    // 群落 -> (阈值, (植物索引, 权重))
+    pub biomes: HashMap<BiomesKind, (f32, Vec<(usize, u32)>)>,
+    // 群落 -> (装饰方块, 概率)
+    pub decorations: HashMap<BiomesKind, Vec<(Voxel, f32)>>,
+}
+
+impl PlantRegistry {
+    pub fn load(path: &str) -> Self {
+        let configs: PlantConfigs = match std::fs::File::open(path) {
+            Ok(file) => match ron::de::from_reader(file) {
+                Ok(configs) => configs,
+                Err(err) => {
+                    println!("植物配置解析失败{:?}", err);
+                    PlantConfigs::default()
+                }
+            },
+            Err(_) => {
+                println!("没有找到植物配置文件{}", path);
+                PlantConfigs::default()
+            }
+        };
+        let mut registry = PlantRegistry::default();
+        let mut index_map: HashMap<String, usize> = HashMap::new();
+        for (name, config) in configs.plants.into_iter() {
+            index_map.insert(name.clone(), registry.plants.len());
+            registry.names.push(name);
+            registry.plants.push(config);
+        }
+        for (biomes, config) in configs.biomes.into_iter() {
+            let mut list = Vec::new();
+            for (name, weight) in config.plants.iter() {
+                match index_map.get(name) {
+                    Some(index) => list.push((*index, *weight)),
+                    None => println!("群落{:?}引用的植物[{}]不存在", biomes, name),
+                }
+            }
+            for (voxel, _) in config.decorations.iter() {
+                if !voxel.is_decoration() {
+                    println!("群落{:?}的装饰方块{}不是装饰类型", biomes, voxel.id);
+                }
+            }
+            registry.biomes.insert(biomes, (config.threshold, list));
+            registry.decorations.insert(biomes, config.decorations);
+        }
+        registry
+    }
+
+    /**
+     * 判断地表位置是否要生长植物
+     * root : 地表方块的中心
+     * height : 地表的高度
+     * noise : 种树噪声
+     */
+    pub fn pick(
+        &self,
+        seed: i32,
+        biomes: BiomesKind,
+        root: Vec3,
+        height: f32,
+        noise: f32,
+    ) -> Option<PlantGentor> {
+        let (threshold, list) = self.biomes.get(&biomes)?;
+        if noise <= *threshold || list.is_empty() {
+            return None;
+        }
+        let block = root.floor().as_ivec3();
+        let mut rng = StdRng::seed_from_u64(position_hash(seed, block.x, block.z, 0x504C414E54));
+        let total: u32 = list.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut value = rng.gen_range(0..total);
+        let mut plant = list[0].0;
+        for (index, weight) in list.iter() {
+            if value < *weight {
+                plant = *index;
+                break;
+            }
+            value -= weight;
+        }
+        let config = &self.plants[plant];
+        if config.min_height.map_or(false, |min| height < min)
+            || config.max_height.map_or(false, |max| height >= max)
+        {
+            return None;
+        }
+        let (min_h, max_h) = config.trunk_height;
+        let (min_scale, max_scale) = config.scale;
+        Some(PlantGentor {
+            plant,
+            root,
+            trunk_height: if max_h > min_h {
+                rng.gen_range(min_h..=max_h)
+            } else {
+                min_h
+            },
+            scale: if max_scale > min_scale {
+                rng.gen_range(min_scale..max_scale)
+            } else {
+                min_scale
+            },
+        })
+    }
+
+    /**
+     * 判断地表方块上是否放置装饰方块
+     * surface : 地表方块的位置
+     */
+    pub fn pick_decoration(&self, seed: i32, biomes: BiomesKind, surface: IVec3) -> Option<Voxel> {
+        let list = self.decorations.get(&biomes)?;
+        let hash = position_hash(seed, surface.x, surface.z, 0x4445434F);
+        let mut value = (hash >> 40) as f32 / (1u64 << 24) as f32;
+        for (voxel, chance) in list.iter() {
+            if value < *chance {
+                return Some(*voxel);
+            }
+            value -= chance;
+        }
+        None
+    }
+}
+
+// 一个确定了位置和大小的植物
+#[derive(Debug, Clone)]
+pub struct PlantGentor {
+    // 植物在注册表中的索引
+    pub plant: usize,
+    // 地表方块的中心
+    pub root: Vec3,
+    pub trunk_height: u32,
+    pub scale: f32,
+}
+
+impl PlantGentor {
+    fn build_parts(&self) -> Vec<(SdfFn, Vec3, Vec3, Voxel, bool)> {
+        let config = &PLANT_REGISTRY.plants[self.plant];
+        let top = self.root + Vec3::new(0.0, self.trunk_height as f32, 0.0);
+        config
+            .parts
+            .iter()
+            .map(|part| {
+                let anchor = match part.anchor {
+                    PlantAnchor::Root => self.root,
+                    PlantAnchor::Top => top,
+                };
+                let (sdf, min, max) =
+                    part.shape
+                        .build(anchor, self.root, self.trunk_height, self.scale);
+                (sdf, min, max, part.voxel, part.overwrite)
+            })
+            .collect()
+    }
+
+    // 植物覆盖到的全部区块
+    pub fn chunk_keys(&self) -> Vec<ChunkKey> {
+        let mut min = self.root;
+        let mut max = self.root;
+        for (_, part_min, part_max, _, _) in self.build_parts() {
+            min = min.min(part_min);
+            max = max.max(part_max);
+        }
+        let min_key = ChunkPos::from_vec3(min);
+        let max_key = ChunkPos::from_vec3(max);
+        let mut keys = Vec::new();
+        for x in min_key.0.x..=max_key.0.x {
+            for y in min_key.0.y..=max_key.0.y {
+                for z in min_key.0.z..=max_key.0.z {
+                    keys.push(ChunkKey::new(IVec3::new(x, y, z)));
+                }
+            }
+        }
+        keys
+    }
+
+    pub fn make_plant_for_chunk(&self, voxels: &mut Vec<Voxel>, chunk_key: ChunkKey) {
+        for (mut sdf, min, max, voxel, overwrite) in self.build_parts() {
+            for index in 0..SampleShape::SIZE {
+                let check_pos = chunk_key.block(LocalPos::from_index(index)).center();
+                if check_pos.cmplt(min.floor()).any() || check_pos.cmpgt(max.ceil()).any() {
+                    continue;
+                }
+                if sdf(check_pos) <= 0.0
+                    && (overwrite || voxels[index as usize].id == Voxel::EMPTY.id)
+                {
+                    voxels[index as usize] = voxel;
+                }
+            }
+        }
+    }
+}
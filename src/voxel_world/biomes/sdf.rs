@@ -45,3 +45,120 @@ pub fn sd_cut_sphere(center: Vec3, r: f32, h: f32) -> impl FnMut(Vec3) -> f32 {
         };
     }
 }
+
+/**
+ * 球体
+ */
+pub fn sd_sphere(center: Vec3, r: f32) -> impl FnMut(Vec3) -> f32 {
+    move |pos: Vec3| (pos - center).length() - r
+}
+
+/**
+ * 椭球体(近似距离)
+ * radii : 三个轴上的半径
+ */
+pub fn sd_ellipsoid(center: Vec3, radii: Vec3) -> impl FnMut(Vec3) -> f32 {
+    move |pos: Vec3| {
+        let p = pos - center;
+        let k0 = (p / radii).length();
+        let k1 = (p / (radii * radii)).length();
+        if k1 == 0.0 {
+            return -radii.min_element();
+        }
+        k0 * (k0 - 1.0) / k1
+    }
+}
+
+/**
+ * 胶囊体 可以用来做树枝
+ * a b : 两端的中心
+ */
+pub fn sd_capsule(a: Vec3, b: Vec3, r: f32) -> impl FnMut(Vec3) -> f32 {
+    move |pos: Vec3| {
+        let pa = pos - a;
+        let ba = b - a;
+        let h = if ba.length_squared() == 0.0 {
+            0.0
+        } else {
+            (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0)
+        };
+        (pa - ba * h).length() - r
+    }
+}
+
+/**
+ * 竖直的圆锥 尖端朝上
+ * base : 底面中心
+ * h : 高度
+ * r : 底面半径
+ */
+pub fn sd_cone(base: Vec3, h: f32, r: f32) -> impl FnMut(Vec3) -> f32 {
+    move |pos: Vec3| {
+        let p = pos - base;
+        let radius = r * (1.0 - (p.y / h).clamp(0.0, 1.0));
+        let side = p.xz().length() - radius;
+        side.max(-p.y).max(p.y - h)
+    }
+}
+
+/**
+ * 长方体
+ * half : 三个轴上的半长
+ */
+pub fn sd_box(center: Vec3, half: Vec3) -> impl FnMut(Vec3) -> f32 {
+    move |pos: Vec3| {
+        let q = (pos - center).abs() - half;
+        q.max(Vec3::ZERO).length() + q.max_element().min(0.0)
+    }
+}
+
+#[test]
+fn test_sdf_primitives() {
+    let center = Vec3::new(1.0, 2.0, 3.0);
+
+    let mut sphere = sd_sphere(center, 2.0);
+    assert_eq!(sphere(center), -2.0);
+    assert_eq!(sphere(center + Vec3::X * 2.0), 0.0);
+    assert_eq!(sphere(center + Vec3::Y * 5.0), 3.0);
+
+    // 轴上的距离是准确的
+    let mut ellipsoid = sd_ellipsoid(center, Vec3::new(3.0, 1.0, 2.0));
+    assert!(ellipsoid(center) < 0.0);
+    assert!(ellipsoid(center + Vec3::X * 2.5) < 0.0);
+    assert!((ellipsoid(center + Vec3::X * 3.0)).abs() < 1e-5);
+    assert!(ellipsoid(center + Vec3::Y * 1.5) > 0.0);
+
+    let b = center + Vec3::Y * 4.0;
+    let mut capsule = sd_capsule(center, b, 0.5);
+    assert_eq!(capsule(center + Vec3::Y * 2.0), -0.5);
+    assert_eq!(capsule(center + Vec3::new(1.5, 2.0, 0.0)), 1.0);
+    // 超出两端时是到端点的距离
+    assert_eq!(capsule(b + Vec3::Y * 1.5), 1.0);
+    // 两端重合时是球体
+    let mut point = sd_capsule(center, center, 1.0);
+    assert_eq!(point(center + Vec3::Z * 3.0), 2.0);
+
+    let mut cone = sd_cone(center, 4.0, 2.0);
+    assert!(cone(center + Vec3::Y) < 0.0);
+    // 越往上越细
+    assert!(cone(center + Vec3::new(1.2, 0.5, 0.0)) < 0.0);
+    assert!(cone(center + Vec3::new(1.2, 3.0, 0.0)) > 0.0);
+    assert!(cone(center - Vec3::Y) > 0.0);
+    assert!(cone(center + Vec3::Y * 5.0) > 0.0);
+
+    let mut cube = sd_box(center, Vec3::new(1.0, 2.0, 3.0));
+    assert_eq!(cube(center), -1.0);
+    assert_eq!(cube(center + Vec3::X * 3.0), 2.0);
+    assert_eq!(cube(center + Vec3::new(2.0, 3.0, 0.0)), 2.0_f32.sqrt());
+}
+
+#[test]
+fn test_trunk() {
+    let root = Vec3::new(0.5, 10.5, 0.5);
+    let mut trunk_fn = trunk(root, 4);
+    assert!(trunk_fn(root) <= 0.0);
+    assert!(trunk_fn(root + Vec3::Y * 4.0) <= 0.0);
+    assert!(trunk_fn(root + Vec3::Y * 5.0) > 0.0);
+    assert!(trunk_fn(root - Vec3::Y) > 0.0);
+    assert!(trunk_fn(root + Vec3::X) > 0.0);
+}
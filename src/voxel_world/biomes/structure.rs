@@ -0,0 +1,408 @@
+// 结构 预制体
+// 在生成地形时 在地表放置预先设计好的方块模板 (遗迹 小屋 水井等)
+// 模板可以来自 MagicaVoxel 的 .vox 文件 或者直接在 ron 中配置
+
+use bevy::{
+    prelude::IVec3,
+    utils::{HashMap, HashSet},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::voxel_world::{
+    chunk::ChunkKey,
+    dimension::DimensionId,
+    pos::BlockPos,
+    voxel::{Voxel, VoxelDirection, VOXEL_DIRECTION_VEC},
+    voxel_mesh::vox_to_y_up,
+};
+
+use super::BiomesKind;
+
+pub const STRUCTURES_RON: &str = "structures.ron";
+
+lazy_static! {
+    pub static ref STRUCTURE_REGISTRY: StructureRegistry = StructureRegistry::load(STRUCTURES_RON);
+}
+
+// 模板的来源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StructureSource {
+    // MagicaVoxel 文件 路径相对于 assets 目录
+    // palette 是颜色索引到体素的映射 索引等于 MagicaVoxel 中的颜色编号减一
+    // 没有配置的颜色会被忽略
+    Vox {
+        path: String,
+        palette: HashMap<u8, Voxel>,
+    },
+    // 直接配置方块 (x, y, z) 是相对于模板最小角的位置
+    // 可以配置空方块 用来挖空地形
+    Blocks(Vec<((i32, i32, i32), Voxel)>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureConfig {
+    pub name: String,
+    pub source: StructureSource,
+    // 可以出现的群落
+    pub biomes: Vec<BiomesKind>,
+    // 每个地表位置出现的概率
+    pub chance: f32,
+    // 相对于地表的高度偏移 负数时会陷入地下
+    #[serde(default)]
+    pub y_offset: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StructureConfigs {
+    pub structures: Vec<StructureConfig>,
+}
+
+// 加载好的模板
+#[derive(Debug, Clone)]
+pub struct StructureTemplate {
+    pub name: String,
+    pub size: IVec3,
+    pub blocks: Vec<(IVec3, Voxel)>,
+    pub biomes: Vec<BiomesKind>,
+    pub chance: f32,
+    pub y_offset: i32,
+}
+
+impl StructureTemplate {
+    pub fn from_config(config: &StructureConfig) -> Option<Self> {
+        let blocks = match &config.source {
+            StructureSource::Vox { path, palette } => load_vox_blocks(path, palette)?,
+            StructureSource::Blocks(list) => list
+                .iter()
+                .map(|((x, y, z), voxel)| (IVec3::new(*x, *y, *z), *voxel))
+                .collect(),
+        };
+        if blocks.is_empty() {
+            println!("结构[{}]没有任何方块", config.name);
+            return None;
+        }
+        let mut size = IVec3::ZERO;
+        for (pos, _) in blocks.iter() {
+            size = size.max(*pos + IVec3::ONE);
+        }
+        Some(Self {
+            name: config.name.clone(),
+            size,
+            blocks,
+            biomes: config.biomes.clone(),
+            chance: config.chance,
+            y_offset: config.y_offset,
+        })
+    }
+}
+
+// 读取 vox 文件中的第一个模型
+fn load_vox_blocks(path: &String, palette: &HashMap<u8, Voxel>) -> Option<Vec<(IVec3, Voxel)>> {
+    let data = match dot_vox::load(format!("assets/{}", path).as_str()) {
+        Ok(data) => data,
+        Err(err) => {
+            println!("读取结构文件失败{}: {}", path, err);
+            return None;
+        }
+    };
+    let (blocks, missing) = vox_model_blocks(data.models.first()?, palette);
+    if !missing.is_empty() {
+        println!("结构文件{}中的颜色没有配置体素: {:?}", path, missing);
+    }
+    Some(blocks)
+}
+
+// 模型中的方块 坐标转换和模型的mesh一致 同时返回没有配置体素的颜色
+fn vox_model_blocks(
+    model: &dot_vox::Model,
+    palette: &HashMap<u8, Voxel>,
+) -> (Vec<(IVec3, Voxel)>, HashSet<u8>) {
+    let mut missing: HashSet<u8> = HashSet::new();
+    let mut blocks = Vec::new();
+    for voxel in model.voxels.iter() {
+        match palette.get(&voxel.i) {
+            Some(target) => blocks.push((vox_to_y_up(voxel).as_ivec3(), *target)),
+            None => {
+                missing.insert(voxel.i);
+            }
+        }
+    }
+    (blocks, missing)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StructureRegistry {
+    pub templates: Vec<StructureTemplate>,
+}
+
+impl StructureRegistry {
+    pub fn load(path: &str) -> Self {
+        let configs: StructureConfigs = match std::fs::File::open(path) {
+            Ok(file) => match ron::de::from_reader(file) {
+                Ok(configs) => configs,
+                Err(err) => {
+                    println!("结构配置解析失败{:?}", err);
+                    StructureConfigs::default()
+                }
+            },
+            Err(_) => {
+                println!("没有找到结构配置文件{}", path);
+                StructureConfigs::default()
+            }
+        };
+        Self {
+            templates: configs
+                .structures
+                .iter()
+                .filter_map(StructureTemplate::from_config)
+                .collect(),
+        }
+    }
+
+    /**
+     * 判断地表位置是否要放置结构
+     * 结果只由种子和位置决定 同一个位置每次生成的结果都相同
+     */
+    pub fn pick(&self, seed: i32, biomes: BiomesKind, surface: IVec3) -> Option<StructureGentor> {
+        for (index, template) in self.templates.iter().enumerate() {
+            if !template.biomes.contains(&biomes) {
+                continue;
+            }
+            let hash = position_hash(seed, surface.x, surface.z, index as u64);
+            // 低24位作为概率 高位作为方向
+            let value = (hash & 0xFFFFFF) as f32 / 0x1000000 as f32;
+            if value < template.chance {
+                return Some(StructureGentor {
+                    template: index,
+                    origin: surface + IVec3::new(0, 1 + template.y_offset, 0),
+                    direction: VOXEL_DIRECTION_VEC[(hash >> 32) as usize % 4],
+                });
+            }
+        }
+        None
+    }
+}
+
+// 把种子和坐标混合成一个随机数
+pub fn position_hash(seed: i32, x: i32, z: i32, salt: u64) -> u64 {
+    let mut value = (seed as u32 as u64) ^ ((x as u32 as u64) << 32) ^ ((z as u32 as u64) << 16);
+    value = value.wrapping_add(salt.wrapping_mul(0x9E3779B97F4A7C15));
+    // splitmix64
+    value = value.wrapping_add(0x9E3779B97F4A7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+    value ^ (value >> 31)
+}
+
+// 一个放置好的结构
+#[derive(Debug, Clone)]
+pub struct StructureGentor {
+    // 模板在注册表中的索引
+    pub template: usize,
+    // 模板中心底部的方块坐标
+    pub origin: IVec3,
+    pub direction: VoxelDirection,
+}
+
+impl StructureGentor {
+    // 旋转后的全部方块的世界坐标
+    pub fn blocks(&self) -> Vec<(IVec3, Voxel)> {
+        self.rotate_template(&STRUCTURE_REGISTRY.templates[self.template])
+    }
+
+    fn rotate_template(&self, template: &StructureTemplate) -> Vec<(IVec3, Voxel)> {
+        let pivot = IVec3::new(template.size.x / 2, 0, template.size.z / 2);
+        let steps = self.direction.rotate_steps();
+        template
+            .blocks
+            .iter()
+            .map(|(pos, voxel)| {
+                let mut offset = *pos - pivot;
+                // 和 VoxelDirection::to_quat 的旋转方向一致
+                for _ in 0..steps {
+                    offset = IVec3::new(offset.z, offset.y, -offset.x);
+                }
+                (self.origin + offset, voxel.rotate_by(self.direction))
+            })
+            .collect()
+    }
+
+    // 结构覆盖到的全部区块
+    pub fn chunk_keys(&self) -> Vec<ChunkKey> {
+        let mut keys: HashSet<ChunkKey> = HashSet::new();
+        for (pos, _) in self.blocks() {
+            keys.insert(ChunkKey::from_block(BlockPos(pos), DimensionId::OVERWORLD).0);
+        }
+        keys.into_iter().collect()
+    }
+
+    pub fn make_structure_for_chunk(&self, voxels: &mut Vec<Voxel>, chunk_key: ChunkKey) {
+        write_blocks_in_chunk(self.blocks(), voxels, chunk_key);
+    }
+}
+
+// 只写入落在这个区块里的方块
+fn write_blocks_in_chunk(
+    blocks: Vec<(IVec3, Voxel)>,
+    voxels: &mut Vec<Voxel>,
+    chunk_key: ChunkKey,
+) {
+    for (pos, voxel) in blocks {
+        let (key, local) = ChunkKey::from_block(BlockPos(pos), chunk_key.1);
+        if key == chunk_key {
+            voxels[local.index()] = voxel;
+        }
+    }
+}
+
+#[test]
+fn test_vox_model_blocks() {
+    use crate::voxel_world::{
+        voxel::{Stone, VoxelMaterial},
+        voxel_mesh::vox_collider::VoxOccupancy,
+    };
+    use ndshape::{RuntimeShape, Shape};
+    // 沿着 y 轴不对称的模型 颜色 1 没有配置
+    let model = dot_vox::Model {
+        size: dot_vox::Size { x: 2, y: 3, z: 2 },
+        voxels: vec![
+            dot_vox::Voxel {
+                x: 0,
+                y: 0,
+                z: 0,
+                i: 0,
+            },
+            dot_vox::Voxel {
+                x: 1,
+                y: 2,
+                z: 1,
+                i: 0,
+            },
+            dot_vox::Voxel {
+                x: 1,
+                y: 1,
+                z: 0,
+                i: 1,
+            },
+        ],
+    };
+    let mut palette = HashMap::new();
+    palette.insert(0, Stone::into_voxel());
+    let (blocks, missing) = vox_model_blocks(&model, &palette);
+    assert_eq!(missing, HashSet::from_iter([1]));
+    // 放下的结构和模型的碰撞体 mesh 方向一致
+    let occupancy = VoxOccupancy::from_model(&model);
+    let shape = RuntimeShape::<u32, 3>::new(occupancy.size.to_array());
+    for (pos, _) in blocks.iter() {
+        assert!(occupancy.filled[shape.linearize(pos.as_uvec3().to_array()) as usize]);
+    }
+    assert_eq!(
+        blocks.iter().map(|(pos, _)| *pos).collect::<Vec<_>>(),
+        vec![IVec3::new(0, 0, 0), IVec3::new(1, 1, 2)]
+    );
+}
+
+#[cfg(test)]
+fn l_shape_template() -> StructureTemplate {
+    use crate::voxel_world::voxel::{Stone, VoxelMaterial};
+    // 不对称的 L 形 沿 x 三格 沿 z 两格 顶上一格
+    let blocks: Vec<(IVec3, Voxel)> = [(0, 0, 0), (1, 0, 0), (2, 0, 0), (0, 0, 1), (2, 1, 0)]
+        .into_iter()
+        .map(|(x, y, z)| (IVec3::new(x, y, z), Stone::into_voxel()))
+        .collect();
+    StructureTemplate {
+        name: "l_shape".to_string(),
+        size: IVec3::new(3, 2, 2),
+        blocks,
+        biomes: vec![BiomesKind::Basic],
+        chance: 0.5,
+        y_offset: 0,
+    }
+}
+
+#[test]
+fn test_pick_deterministic() {
+    let registry = StructureRegistry {
+        templates: vec![l_shape_template()],
+    };
+    let mut picked = 0;
+    for x in -20..20 {
+        for z in -20..20 {
+            let surface = IVec3::new(x, 10, z);
+            let first = registry.pick(7, BiomesKind::Basic, surface);
+            let second = registry.pick(7, BiomesKind::Basic, surface);
+            match (first, second) {
+                (Some(first), Some(second)) => {
+                    assert_eq!(first.template, second.template);
+                    assert_eq!(first.origin, second.origin);
+                    assert_eq!(first.direction, second.direction);
+                    assert_eq!(first.origin, surface + IVec3::Y);
+                    picked += 1;
+                }
+                (None, None) => {}
+                _ => panic!("同一个位置生成的结果不同 {:?}", surface),
+            }
+            // 不在配置的群落里不会出现
+            assert!(registry.pick(7, BiomesKind::Sand, surface).is_none());
+        }
+    }
+    // 概率是一半 不会全部出现或者全部不出现
+    assert!(picked > 0 && picked < 1600);
+}
+
+#[test]
+fn test_blocks_rotation() {
+    let template = l_shape_template();
+    let pivot = IVec3::new(template.size.x / 2, 0, template.size.z / 2);
+    let origin = IVec3::new(3, 20, -5);
+    for direction in VOXEL_DIRECTION_VEC {
+        let gentor = StructureGentor {
+            template: 0,
+            origin,
+            direction,
+        };
+        let blocks = gentor.rotate_template(&template);
+        assert_eq!(blocks.len(), template.blocks.len());
+        for ((pos, voxel), (template_pos, _)) in blocks.iter().zip(template.blocks.iter()) {
+            // 和模型用的旋转结果一致
+            let expected = direction.to_quat() * (*template_pos - pivot).as_vec3();
+            assert_eq!(*pos - origin, expected.round().as_ivec3());
+            assert_eq!(voxel.direction, direction);
+        }
+    }
+}
+
+#[test]
+fn test_make_structure_for_chunk() {
+    use crate::CHUNK_SIZE;
+    let template = l_shape_template();
+    // 放在区块 0 和区块 x=1 的交界处 (区块 0 覆盖 -8..8)
+    let gentor = StructureGentor {
+        template: 0,
+        origin: IVec3::new(7, 0, 0),
+        direction: VoxelDirection::Z,
+    };
+    let blocks = gentor.rotate_template(&template);
+    let chunk_key = ChunkKey(IVec3::ZERO, DimensionId::OVERWORLD);
+    let mut voxels = vec![Voxel::EMPTY; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize];
+    write_blocks_in_chunk(blocks.clone(), &mut voxels, chunk_key);
+
+    let inside: Vec<_> = blocks
+        .iter()
+        .filter(|(pos, _)| {
+            ChunkKey::from_block(BlockPos(*pos), DimensionId::OVERWORLD).0 == chunk_key
+        })
+        .collect();
+    assert!(!inside.is_empty() && inside.len() < blocks.len());
+    for (pos, voxel) in inside.iter() {
+        let (_, local) = ChunkKey::from_block(BlockPos(*pos), DimensionId::OVERWORLD);
+        assert_eq!(voxels[local.index()], *voxel);
+    }
+    // 其他位置都没有被写入
+    let written = voxels
+        .iter()
+        .filter(|voxel| **voxel != Voxel::EMPTY)
+        .count();
+    assert_eq!(written, inside.len());
+}
@@ -0,0 +1,246 @@
+// 方块属性注册表
+// 每种方块的类型 硬度 能否破坏 碰撞体 模型 贴图和掉落物都定义在 blocks.ron 中
+// 服务器和客户端都从这里读取 不再在代码里写死
+
+use bevy::utils::HashMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+pub const BLOCKS_RON: &str = "blocks.ron";
+
+lazy_static! {
+    pub static ref BLOCKS: BlockRegistry = BlockRegistry::load(BLOCKS_RON);
+}
+
+// 方块的物理类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BlockKind {
+    #[default]
+    Air,
+    // 实心方块 会挡住后面的面和光
+    Solid,
+    // 液体 单独生成mesh
+    Liquid,
+    // 半透明的方块 单独生成mesh并且混合显示
+    Transparent,
+    // 镂空的方块 和实心方块一起生成mesh 贴图透明的部分直接丢弃 比如树叶
+    Cutout,
+    // 装饰方块 渲染成交叉的面片
+    Decoration,
+}
+
+// 碰撞体的形状
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColliderShape {
+    #[default]
+    None,
+    // 占满整个格子 合并到地形的碰撞体中
+    Cube,
+    // 使用特殊模型生成的碰撞体
+    Model,
+}
+
+// 特殊模型的碰撞体怎么生成
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ModelCollider {
+    // 模型占用的体素合并成的长方体 和看到的形状一样
+    #[default]
+    Voxels,
+    // 使用模型的三角面
+    Mesh,
+    // 使用包围盒 比三角面简单很多
+    Bbox,
+}
+
+// 特殊模型的朝向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ModelRotation {
+    // 跟随方块的方向水平旋转
+    #[default]
+    Horizontal,
+    // 始终保持模型原来的方向
+    Fixed,
+}
+
+// 包围盒 方块内的坐标 0.0..=1.0
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Default for ModelBox {
+    fn default() -> Self {
+        Self {
+            min: [0.0; 3],
+            max: [1.0; 3],
+        }
+    }
+}
+
+// 特殊模型(.vox) 有模型的方块不生成地形mesh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockModel {
+    // 模型文件 路径相对于 assets
+    pub vox: String,
+    #[serde(default)]
+    pub collider: ModelCollider,
+    #[serde(default)]
+    pub bbox: ModelBox,
+    #[serde(default)]
+    pub rotation: ModelRotation,
+}
+
+// 贴图 路径相对于 assets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockTextures {
+    pub default: String,
+    // 各个法向量下的贴图
+    #[serde(default)]
+    pub normal: HashMap<u8, String>,
+}
+
+// 掉落物 每次有 possible 的概率掉落一个 一共尝试 times 次
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockDrop {
+    pub possible: f32,
+    pub staff_id: usize,
+    pub times: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockProperty {
+    pub id: u8,
+    pub name: String,
+    pub ch_name: String,
+    pub kind: BlockKind,
+    // 硬度 空手破坏需要的秒数
+    #[serde(default)]
+    pub hardness: f32,
+    #[serde(default = "default_breakable")]
+    pub breakable: bool,
+    #[serde(default)]
+    pub collider: ColliderShape,
+    #[serde(default)]
+    pub model: Option<BlockModel>,
+    #[serde(default)]
+    pub textures: Option<BlockTextures>,
+    // 没有配置时掉落方块本身对应的物品
+    #[serde(default)]
+    pub drops: Option<Vec<BlockDrop>>,
+}
+
+fn default_breakable() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlockConfig {
+    pub blocks: Vec<BlockProperty>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockRegistry {
+    pub blocks: HashMap<u8, BlockProperty>,
+}
+
+impl BlockRegistry {
+    pub fn load(path: &str) -> Self {
+        match Self::read(path) {
+            Ok(registry) => registry,
+            Err(err) => {
+                println!("方块配置加载失败{}", err);
+                BlockRegistry::default()
+            }
+        }
+    }
+
+    // 读取失败时返回错误 重新加载时使用
+    pub fn read(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let config: BlockConfig = ron::de::from_str(&content).map_err(|err| err.to_string())?;
+        Ok(Self::from_config(config))
+    }
+
+    pub fn from_config(config: BlockConfig) -> Self {
+        let mut blocks = HashMap::default();
+        for block in config.blocks {
+            if blocks.contains_key(&block.id) {
+                println!("方块{}重复定义了", block.id);
+            }
+            blocks.insert(block.id, block);
+        }
+        println!("加载了{}种方块", blocks.len());
+        Self { blocks }
+    }
+
+    pub fn get(&self, id: u8) -> Option<&BlockProperty> {
+        self.blocks.get(&id)
+    }
+
+    // 没有定义的方块当作实心方块
+    pub fn kind(&self, id: u8) -> BlockKind {
+        match self.get(id) {
+            Some(block) => block.kind,
+            None if id == 0 => BlockKind::Air,
+            None => BlockKind::Solid,
+        }
+    }
+
+    pub fn collider(&self, id: u8) -> ColliderShape {
+        match self.get(id) {
+            Some(block) => block.collider,
+            None if id == 0 => ColliderShape::None,
+            None => ColliderShape::Cube,
+        }
+    }
+
+    pub fn breakable(&self, id: u8) -> bool {
+        self.get(id).map(|block| block.breakable).unwrap_or(true)
+    }
+
+    pub fn model(&self, id: u8) -> Option<&BlockModel> {
+        self.get(id).and_then(|block| block.model.as_ref())
+    }
+
+    // 配置了掉落物的方块 修改之后可以热加载
+    pub fn drops(&self) -> HashMap<u8, Vec<BlockDrop>> {
+        self.blocks
+            .values()
+            .filter_map(|block| Some((block.id, block.drops.clone()?)))
+            .collect()
+    }
+
+    // 有特殊模型的方块
+    pub fn models(&self) -> impl Iterator<Item = &BlockProperty> {
+        self.blocks.values().filter(|block| block.model.is_some())
+    }
+}
+
+#[test]
+fn test_blocks_ron() {
+    use super::voxel::{
+        AppleLeaf, BasicStone, Glass, Ice, Stone, TallGrass, VoxelMaterial, Water, WorkCube,
+    };
+    let registry = BlockRegistry::load(BLOCKS_RON);
+    // 每一个体素id都要有定义
+    for id in 0..=Ice::ID {
+        assert!(registry.get(id).is_some(), "方块{}没有定义", id);
+    }
+    assert_eq!(registry.kind(Stone::ID), BlockKind::Solid);
+    assert_eq!(registry.kind(Water::ID), BlockKind::Liquid);
+    assert_eq!(registry.kind(Glass::ID), BlockKind::Transparent);
+    assert_eq!(registry.kind(AppleLeaf::ID), BlockKind::Cutout);
+    assert_eq!(registry.kind(TallGrass::ID), BlockKind::Decoration);
+    assert_eq!(registry.collider(Water::ID), ColliderShape::None);
+    assert_eq!(registry.collider(WorkCube::ID), ColliderShape::Model);
+    let model = registry.model(WorkCube::ID).unwrap();
+    assert!(model.vox.ends_with(".vox"));
+    assert_eq!(model.rotation, ModelRotation::Horizontal);
+    assert!(!registry.breakable(BasicStone::ID));
+    assert!(registry.breakable(Stone::ID));
+    // 只有配置了掉落物的方块
+    let drops = registry.drops();
+    assert_eq!(drops[&AppleLeaf::ID].len(), 3);
+    assert!(!drops.contains_key(&Stone::ID));
+}
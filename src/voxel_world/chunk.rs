@@ -4,31 +4,62 @@ use std::{
 };
 
 use bevy::{
-    prelude::{IVec3, Resource, Vec3},
+    prelude::{IVec3, Resource},
     reflect::Reflect,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{common::Sphere3, CHUNK_SIZE};
 
+use super::{
+    dimension::DimensionId,
+    pos::{BlockPos, ChunkPos, LocalPos},
+    world_height::WORLD_HEIGHT,
+};
+
+// 区块的位置和所在的维度
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
-pub struct ChunkKey(pub IVec3);
+pub struct ChunkKey(pub IVec3, pub DimensionId);
 
 impl ChunkKey {
+    // 主世界的区块 客户端同时只在一个维度中 只使用主世界的区块
+    pub fn new(ivec3: IVec3) -> ChunkKey {
+        ChunkKey(ivec3, DimensionId::OVERWORLD)
+    }
+
+    pub fn with_dimension(&self, dimension: DimensionId) -> ChunkKey {
+        ChunkKey(self.0, dimension)
+    }
+
     pub fn to_y_zore(&self) -> ChunkKey {
         let mut ivec3 = self.0.clone();
         ivec3.y = 0;
-        ChunkKey(ivec3)
+        ChunkKey(ivec3, self.1)
     }
 
     pub fn add_ivec3(&self, key: IVec3) -> ChunkKey {
         let ivec3 = self.0.clone();
-        ChunkKey(ivec3 + key)
+        ChunkKey(ivec3 + key, self.1)
+    }
+
+    // 方块所在的区块和区块内的位置
+    pub fn from_block(block: BlockPos, dimension: DimensionId) -> (ChunkKey, LocalPos) {
+        let (chunk, local) = block.to_chunk_local();
+        (chunk.key(dimension), local)
+    }
+
+    // 区块内的位置在世界中的方块
+    pub fn block(&self, local: LocalPos) -> BlockPos {
+        BlockPos::from_chunk_local(ChunkPos::from(*self), local)
     }
 
     pub fn as_u8_array(&self) -> [u8; 8] {
         let mut hasher = DefaultHasher::new();
         self.0.hash(&mut hasher);
+        // 主世界保持原来的键 已经保存的地图还可以读取
+        if self.1 != DimensionId::OVERWORLD {
+            self.1.hash(&mut hasher);
+        }
         let hash_value = hasher.finish();
         hash_value.to_ne_bytes() // unsafe { std::mem::transmute(hash_value) };
     }
@@ -85,19 +116,19 @@ pub fn generate_offset_resource_min_1(radius: f32) -> NeighbourOffset {
 }
 
 pub fn find_chunk_keys_array_by_sphere_y_0(sphere: Sphere3, offsets: Vec<IVec3>) -> Vec<ChunkKey> {
-    let mut center_chunk_point = get_chunk_key_i3_by_vec3(sphere.center);
+    let mut center_chunk_point = ChunkPos::from_vec3(sphere.center).0;
     center_chunk_point.y = 0;
     offsets
         .iter()
-        .map(|&ele| ChunkKey(center_chunk_point + ele))
+        .map(|&ele| ChunkKey(center_chunk_point + ele, sphere.dimension))
         .collect()
 }
 
 pub fn find_chunk_keys_array_by_sphere(sphere: Sphere3, offsets: Vec<IVec3>) -> Vec<ChunkKey> {
-    let center_chunk_point = get_chunk_key_i3_by_vec3(sphere.center);
+    let center_chunk_point = ChunkPos::from_vec3(sphere.center).0;
     offsets
         .iter()
-        .map(|&ele| ChunkKey(center_chunk_point + ele))
+        .map(|&ele| ChunkKey(center_chunk_point + ele, sphere.dimension))
         .collect()
 }
 
@@ -107,10 +138,10 @@ pub fn find_chunk_keys_by_sphere_to_full_height(
     offsets: Vec<IVec3>,
     mut rt: impl FnMut(ChunkKey),
 ) {
-    let mut center_chunk_point = get_chunk_key_i3_by_vec3(sphere.center);
+    let mut center_chunk_point = ChunkPos::from_vec3(sphere.center).0;
     center_chunk_point.y = 0;
     for &ele in offsets.iter() {
-        for y_offset in -7..=8 {
+        for y_offset in WORLD_HEIGHT.chunk_ys() {
             rt(ChunkKey(
                 center_chunk_point
                     + ele
@@ -119,27 +150,26 @@ pub fn find_chunk_keys_by_sphere_to_full_height(
                         y: y_offset,
                         z: 0,
                     },
+                sphere.dimension,
             ))
         }
     }
 }
 
-// 新版获取当前的 chunk_Key
-pub fn get_chunk_key_i3_by_vec3(point: Vec3) -> IVec3 {
-    IVec3 {
-        x: get_chunk_key_axis(point.x),
-        y: get_chunk_key_axis(point.y),
-        z: get_chunk_key_axis(point.z),
-    }
-}
-
-fn get_chunk_key_axis(x: f32) -> i32 {
-    let x_dot = x * 10.;
-    if x > 8. {
-        return ((x_dot - (CHUNK_SIZE as f32 / 2.0) * 10.) as i32 / (CHUNK_SIZE * 10)) + 1;
-    }
-    if x < -8. {
-        return ((x_dot + (CHUNK_SIZE as f32 / 2.0) * 10.) as i32 / (CHUNK_SIZE * 10)) - 1;
+#[test]
+fn test_chunk_key_from_block() {
+    for key in [
+        IVec3::new(0, 0, 0),
+        IVec3::new(-1, 3, 2),
+        IVec3::new(5, -7, -4),
+    ] {
+        for xyz in [[0, 0, 0], [15, 15, 15], [7, 8, 0]] {
+            let chunk_key = ChunkKey(key, DimensionId(2));
+            let block = chunk_key.block(LocalPos(xyz));
+            assert_eq!(
+                ChunkKey::from_block(block, DimensionId(2)),
+                (chunk_key, LocalPos(xyz))
+            );
+        }
     }
-    0
 }
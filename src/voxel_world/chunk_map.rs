@@ -3,21 +3,71 @@ use bevy::{
     reflect::Reflect,
     utils::HashMap,
 };
-use ndshape::{ConstShape, ConstShape3u32};
+use ndshape::{ConstShape, ConstShape3u32, Shape};
 
-use crate::{CHUNK_SIZE, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32};
+use crate::{CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32};
 
-use super::{chunk::ChunkKey, voxel::Voxel};
+use super::{
+    chunk::ChunkKey,
+    dimension::DimensionId,
+    light::light_region_shape,
+    pos::{BlockPos, LocalPos},
+    voxel::Voxel,
+    world_height::WORLD_HEIGHT,
+};
 
 #[derive(Debug, Clone, Default, Resource, Reflect)]
 pub struct ChunkMap {
     pub map_data: HashMap<ChunkKey, Vec<Voxel>>,
+    // 每个区块的光照 只有客户端使用 见 light.rs
+    pub light_data: HashMap<ChunkKey, Vec<u8>>,
 }
 
 impl ChunkMap {
     pub fn new() -> Self {
         let data_map = HashMap::<ChunkKey, Vec<Voxel>>::new();
-        Self { map_data: data_map }
+        Self {
+            map_data: data_map,
+            light_data: HashMap::new(),
+        }
+    }
+
+    // 获取某个位置的光照
+    pub fn get_light(&self, chunk_key: ChunkKey, xyz: [u32; 3]) -> Option<u8> {
+        type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+        let index = DataShape::linearize(xyz) as usize;
+        self.light_data.get(&chunk_key).map(|light| light[index])
+    }
+
+    // 计算光照时使用 中心列以及周围八列的全部y轴数据 没有加载的区块当作空气
+    pub fn get_light_region(&self, chunk_key: ChunkKey) -> Vec<Voxel> {
+        type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+        let region_shape = light_region_shape();
+        let mut result = vec![Voxel::EMPTY; region_shape.size() as usize];
+        for (layer, chunk_y) in WORLD_HEIGHT.chunk_ys().enumerate() {
+            for dx in 0..3 {
+                for dz in 0..3 {
+                    let key = ChunkKey(
+                        IVec3::new(chunk_key.0.x + dx - 1, chunk_y, chunk_key.0.z + dz - 1),
+                        chunk_key.1,
+                    );
+                    let Some(voxels) = self.get(key) else {
+                        continue;
+                    };
+                    let base = [
+                        dx as u32 * CHUNK_SIZE_U32,
+                        layer as u32 * CHUNK_SIZE_U32,
+                        dz as u32 * CHUNK_SIZE_U32,
+                    ];
+                    for (i, voxel) in voxels.iter().enumerate() {
+                        let [x, y, z] = DataShape::delinearize(i as u32);
+                        let index = region_shape.linearize([base[0] + x, base[1] + y, base[2] + z]);
+                        result[index as usize] = *voxel;
+                    }
+                }
+            }
+        }
+        result
     }
 
     // 获取某个位置的方块
@@ -30,33 +80,51 @@ impl ChunkMap {
         None
     }
 
-    // 寻找y轴上最进的数据
-    pub fn find_closest_block_y(
+    // 获取世界中某个方块
+    pub fn get_block_at(&self, dimension: DimensionId, block: BlockPos) -> Option<Voxel> {
+        let (chunk_key, LocalPos(xyz)) = ChunkKey::from_block(block, dimension);
+        self.get_block(chunk_key, xyz)
+    }
+
+    // 这个位置可以放下物体 没有加载的区块不算
+    fn is_free(&self, dimension: DimensionId, block: BlockPos, height: i32) -> bool {
+        (0..height).all(|dy| {
+            self.get_block_at(dimension, block.offset(IVec3::Y * dy))
+                .map_or(false, |voxel| !voxel.has_collider())
+        })
+    }
+
+    /**
+     * 寻找离 block 最近的空位 从空位开始往上 height 格都没有碰撞体
+     * 先在 max_distance 范围内按照距离由近到远查找 距离相同时优先往上
+     * 范围内都找不到时 再沿着这一列一直往上找
+     */
+    pub fn find_free_space(
         &self,
-        chunk_key: ChunkKey,
-        xyz: [u32; 3],
-        id: u8,
-    ) -> Option<(ChunkKey, [u32; 3])> {
-        type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-        for chunk_y in chunk_key.0.y..=128 / CHUNK_SIZE {
-            let start = if chunk_y == chunk_key.0.y { xyz[1] } else { 0 };
-            let new_chunk_key = ChunkKey(IVec3 {
-                x: chunk_key.0.x,
-                y: chunk_y,
-                z: chunk_key.0.z,
-            });
-            if let Some(chunk_data) = self.get(new_chunk_key) {
-                for y in start..CHUNK_SIZE_U32 {
-                    let pos = [xyz[0], y, xyz[2]];
-                    let index = DataShape::linearize(pos);
-                    let data = chunk_data[index as usize];
-                    if data.id == id {
-                        return Some((new_chunk_key, pos));
-                    }
+        dimension: DimensionId,
+        block: BlockPos,
+        height: i32,
+        max_distance: i32,
+    ) -> Option<BlockPos> {
+        let range = -max_distance..=max_distance;
+        let mut offsets = Vec::new();
+        for x in range.clone() {
+            for y in range.clone() {
+                for z in range.clone() {
+                    offsets.push(IVec3::new(x, y, z));
                 }
             }
         }
-        None
+        offsets.sort_by_key(|offset| (offset.length_squared(), -offset.y));
+        if let Some(offset) = offsets
+            .into_iter()
+            .find(|offset| self.is_free(dimension, block.offset(*offset), height))
+        {
+            return Some(block.offset(offset));
+        }
+        (block.0.y + max_distance + 1..WORLD_HEIGHT.max_y())
+            .map(|y| BlockPos::new(block.0.x, y, block.0.z))
+            .find(|pos| self.is_free(dimension, *pos, height))
     }
 
     pub fn chunk_for_mesh_ready(&self, chunk_key: ChunkKey) -> bool {
@@ -67,9 +135,8 @@ impl ChunkMap {
         let n_self = &IVec3::new(0, 0, 0);
 
         let offsets = [px, nx, pz, nz, n_self];
-        let last_index = -128 / CHUNK_SIZE + 1;
 
-        for y_offset in last_index..=128 / CHUNK_SIZE {
+        for y_offset in WORLD_HEIGHT.chunk_ys() {
             for offset in offsets.iter() {
                 let mut new_key = chunk_key;
                 new_key.0.y = y_offset;
@@ -97,182 +164,63 @@ impl ChunkMap {
         }
     }
 
-    // 获取全部y轴的数据
-    pub fn get_with_neighbor_full_y(&self, chunk_key: ChunkKey) -> Vec<Voxel> {
-        let mut result = Vec::new();
-        type SampleShape = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, 256, CHUNK_SIZE_ADD_2_U32>;
-        type DataShape = ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32, CHUNK_SIZE_ADD_2_U32>;
-        let mut map: HashMap<i32, Vec<Voxel>> = HashMap::new();
-
-        let last_inex = -128 / CHUNK_SIZE + 1;
-
-        for y_offset in last_inex..=128 / CHUNK_SIZE {
-            let mut new_key = chunk_key;
-            new_key.0.y = y_offset;
-            let layer_data = self.get_layer_neighbors(new_key);
-            map.insert(y_offset, layer_data);
-        }
-
-        for i in 0..SampleShape::SIZE {
-            let [x, y, z] = SampleShape::delinearize(i);
-            let layer = y / CHUNK_SIZE_U32;
-            let layer_index: i32 = (layer as i32) + last_inex;
-            let data = map.get(&{ layer_index });
-            let index = DataShape::linearize([x, y % CHUNK_SIZE_U32, z]);
-            result.push(Self::get_by_index(data, index));
-        }
-
-        result
-    }
-
+    // 区块和外面一圈邻居的数据 棱和角上的邻居也有 生成mesh时计算环境光遮蔽要用到
     pub fn get_neighbors(&self, chunk_key: ChunkKey) -> Vec<Voxel> {
-        let voxels = self.get(chunk_key);
-
         type SampleShape =
             ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
         type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
 
-        let px = &IVec3::new(1, 0, 0);
-        let nx = &IVec3::new(-1, 0, 0);
-        let pz = &IVec3::new(0, 0, 1);
-        let nz = &IVec3::new(0, 0, -1);
-        let py = &IVec3::new(0, 1, 0);
-        let ny = &IVec3::new(0, -1, 0);
-
-        let offsets = vec![px, nx, pz, nz, py, ny];
-        let mut map: HashMap<IVec3, Vec<Voxel>> = HashMap::new();
-        for ele in offsets {
-            let new_key = ChunkKey(chunk_key.0 + *ele);
-            if let Some(v) = self.get(new_key) {
-                map.insert(*ele, v.clone());
-            };
-        }
-        let mut result = Vec::new();
-        for i in 0..SampleShape::SIZE {
-            let [x, y, z] = SampleShape::delinearize(i);
-            if x != 0
-                && x != CHUNK_SIZE_U32 + 1
-                && z != 0
-                && z != CHUNK_SIZE_U32 + 1
-                && y == CHUNK_SIZE_U32 + 1
-            {
-                // y轴
-                let index = DataShape::linearize([x - 1, 0, z - 1]);
-                let v = map.get(py);
-                result.push(Self::get_by_index(v, index));
-            } else if x != 0
-                && x != CHUNK_SIZE_U32 + 1
-                && z != 0
-                && z != CHUNK_SIZE_U32 + 1
-                && y == 0
-            {
-                let index = DataShape::linearize([x - 1, CHUNK_SIZE_U32 - 1, z - 1]);
-                let v: Option<&Vec<Voxel>> = map.get(ny);
-                result.push(Self::get_by_index(v, index));
-            } else if y != 0
-                && y != CHUNK_SIZE_U32 + 1
-                && z != 0
-                && z != CHUNK_SIZE_U32 + 1
-                && x == CHUNK_SIZE_U32 + 1
-            {
-                // y轴
-                let index = DataShape::linearize([0, y - 1, z - 1]);
-                let v: Option<&Vec<Voxel>> = map.get(px);
-                result.push(Self::get_by_index(v, index));
-            } else if y != 0
-                && y != CHUNK_SIZE_U32 + 1
-                && z != 0
-                && z != CHUNK_SIZE_U32 + 1
-                && x == 0
-            {
-                let index = DataShape::linearize([CHUNK_SIZE_U32 - 1, y - 1, z - 1]);
-                let v = map.get(nx);
-                result.push(Self::get_by_index(v, index));
-            } else if x != 0
-                && x != CHUNK_SIZE_U32 + 1
-                && y != 0
-                && y != CHUNK_SIZE_U32 + 1
-                && z == CHUNK_SIZE_U32 + 1
-            {
-                // z轴
-                let index = DataShape::linearize([x - 1, y - 1, 0]);
-                let v = map.get(pz);
-                result.push(Self::get_by_index(v, index));
-            } else if x != 0
-                && x != CHUNK_SIZE_U32 + 1
-                && y != 0
-                && y != CHUNK_SIZE_U32 + 1
-                && z == 0
-            {
-                let index = DataShape::linearize([x - 1, y - 1, CHUNK_SIZE_U32 - 1]);
-                let v = map.get(nz);
-                result.push(Self::get_by_index(v, index));
-            } else if x > 0
-                && x < CHUNK_SIZE_U32 + 1
-                && y > 0
-                && y < CHUNK_SIZE_U32 + 1
-                && z > 0
-                && z < CHUNK_SIZE_U32 + 1
-            {
-                let index = DataShape::linearize([x - 1, y - 1, z - 1]);
-                result.push(Self::get_by_index(voxels, index));
+        // 每个方向上 0 是前一个区块的最后一格 CHUNK_SIZE_U32 + 1 是后一个区块的第一格
+        let split = |v: u32| {
+            if v == 0 {
+                (-1, CHUNK_SIZE_U32 - 1)
+            } else if v == CHUNK_SIZE_U32 + 1 {
+                (1, 0)
             } else {
-                result.push(Voxel::EMPTY);
+                (0, v - 1)
             }
-        }
-        result
-    }
-
-    // 生成mesh时使用生成一层
-    fn get_layer_neighbors(&self, chunk_key: ChunkKey) -> Vec<Voxel> {
-        let voxels = self.get(chunk_key);
-
-        type SampleShape =
-            ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32, CHUNK_SIZE_ADD_2_U32>;
-        type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
-
-        let px = &IVec3::new(1, 0, 0);
-        let nx = &IVec3::new(-1, 0, 0);
-        let pz = &IVec3::new(0, 0, 1);
-        let nz = &IVec3::new(0, 0, -1);
-
-        let offsets = vec![px, nx, pz, nz];
-        let mut map: HashMap<IVec3, Vec<Voxel>> = HashMap::new();
-        for ele in offsets {
-            let new_key = ChunkKey(chunk_key.0 + *ele);
-            if let Some(v) = self.get(new_key) {
-                map.insert(*ele, v.clone());
-            };
-        }
-        let mut result = Vec::new();
+        };
+        // 周围27个区块 用到时才查找
+        let mut chunks: [Option<Option<&Vec<Voxel>>>; 27] = [None; 27];
+        let mut result = Vec::with_capacity(SampleShape::SIZE as usize);
         for i in 0..SampleShape::SIZE {
-            let [x, y, z] = SampleShape::delinearize(i);
-            if z != 0 && z != CHUNK_SIZE_U32 + 1 && x == CHUNK_SIZE_U32 + 1 {
-                // y轴
-                let index = DataShape::linearize([0, y, z - 1]);
-                let v: Option<&Vec<Voxel>> = map.get(px);
-                result.push(Self::get_by_index(v, index));
-            } else if z != 0 && z != CHUNK_SIZE_U32 + 1 && x == 0 {
-                let index = DataShape::linearize([CHUNK_SIZE_U32 - 1, y, z - 1]);
-                let v = map.get(nx);
-                result.push(Self::get_by_index(v, index));
-            } else if x != 0 && x != CHUNK_SIZE_U32 + 1 && z == CHUNK_SIZE_U32 + 1 {
-                // z轴
-                let index = DataShape::linearize([x - 1, y, 0]);
-                let v = map.get(pz);
-                result.push(Self::get_by_index(v, index));
-            } else if x != 0 && x != CHUNK_SIZE_U32 + 1 && z == 0 {
-                let index = DataShape::linearize([x - 1, y, CHUNK_SIZE_U32 - 1]);
-                let v = map.get(nz);
-                result.push(Self::get_by_index(v, index));
-            } else if x > 0 && x < CHUNK_SIZE_U32 + 1 && z > 0 && z < CHUNK_SIZE_U32 + 1 {
-                let index = DataShape::linearize([x - 1, y, z - 1]);
-                result.push(Self::get_by_index(voxels, index));
-            } else {
-                result.push(Voxel::EMPTY);
-            }
+            let [(ox, x), (oy, y), (oz, z)] = SampleShape::delinearize(i).map(split);
+            let offset = IVec3::new(ox, oy, oz);
+            let voxels = *chunks[((ox + 1) * 9 + (oy + 1) * 3 + oz + 1) as usize]
+                .get_or_insert_with(|| self.get(chunk_key.add_ivec3(offset)));
+            result.push(Self::get_by_index(voxels, DataShape::linearize([x, y, z])));
         }
-
         result
     }
 }
+
+#[test]
+fn test_find_free_space() {
+    use super::voxel::{Stone, VoxelMaterial};
+    let dimension = DimensionId::OVERWORLD;
+    let mut chunk_map = ChunkMap::new();
+    // 下面一半是石头 上面是空气
+    let voxels = (0..CHUNK_SIZE_U32.pow(3))
+        .map(|index| match LocalPos::from_index(index).0[1] < 8 {
+            true => Stone::into_voxel(),
+            false => Voxel::EMPTY,
+        })
+        .collect();
+    chunk_map.write_chunk(ChunkKey(IVec3::ZERO, dimension), voxels);
+    // 区块 0 的 y 是 -8..8 石头的表面在 y = 0
+    let stuck = BlockPos::new(2, -2, 3);
+    assert_eq!(
+        chunk_map.find_free_space(dimension, stuck, 2, 3),
+        Some(BlockPos::new(2, 0, 3))
+    );
+    // 已经是空位时不移动
+    let free = BlockPos::new(2, 4, 3);
+    assert_eq!(chunk_map.find_free_space(dimension, free, 2, 3), Some(free));
+    // 范围内没有空位时沿着这一列往上 上面的区块没有加载
+    assert_eq!(
+        chunk_map.find_free_space(dimension, BlockPos::new(2, -7, 3), 2, 3),
+        Some(BlockPos::new(2, 0, 3))
+    );
+    // 放不下的物体找不到空位
+    assert_eq!(chunk_map.find_free_space(dimension, free, 10, 3), None);
+}
@@ -0,0 +1,237 @@
+// 维度
+// 一个服务器可以有多个维度(世界) 每个维度有自己的名字 地图生成预设和种子
+// 区块的 ChunkKey 带着维度 所以不同维度的区块数据 数据库存储和碰撞体都是分开的
+// 地图生成只关心坐标 生成时使用主世界的 ChunkKey 生成完成后再换回原来的维度
+
+use bevy::{prelude::Component, reflect::Reflect};
+use bevy_rapier3d::prelude::{CollisionGroups, Group};
+use lazy_static::lazy_static;
+use ndshape::{ConstShape, ConstShape3u32};
+use serde::{Deserialize, Serialize};
+
+use crate::{CHUNK_SIZE, CHUNK_SIZE_U32};
+
+use super::{
+    biomes::OtherGentor, chunk::ChunkKey, map_database::MAP_SEED,
+    map_generator::gen_chunk_data_by_seed, voxel::Voxel, world_height::WORLD_HEIGHT,
+};
+
+pub const DIMENSIONS_RON: &str = "dimensions.ron";
+
+// 每个维度占用三个碰撞分组(地形 掉落物 玩家) 32个分组最多放下10个维度
+pub const MAX_DIMENSIONS: u8 = 10;
+
+lazy_static! {
+    pub static ref DIMENSIONS: Vec<DimensionConfig> = DimensionsConfig::load(DIMENSIONS_RON);
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect, Component,
+)]
+pub struct DimensionId(pub u8);
+
+impl DimensionId {
+    // 主世界 原来的地图数据都属于这个维度
+    pub const OVERWORLD: DimensionId = DimensionId(0);
+}
+
+// 地图生成的预设
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WorldPreset {
+    // 群系生成
+    Normal,
+    // 超平坦 从最下面一个区块的底部开始往上每一层的体素id
+    Flat(Vec<u8>),
+    // 什么都没有
+    Void,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionConfig {
+    pub id: u8,
+    pub name: String,
+    pub preset: WorldPreset,
+    pub seed: i32,
+    // 进入这个维度时的位置
+    pub spawn: [f32; 3],
+}
+
+impl DimensionConfig {
+    pub fn dimension(&self) -> DimensionId {
+        DimensionId(self.id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DimensionsConfig {
+    pub dimensions: Vec<DimensionConfig>,
+}
+
+impl Default for DimensionsConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: vec![DimensionConfig {
+                id: DimensionId::OVERWORLD.0,
+                name: String::from("overworld"),
+                preset: WorldPreset::Normal,
+                seed: MAP_SEED,
+                spawn: [0., 60., 0.],
+            }],
+        }
+    }
+}
+
+impl DimensionsConfig {
+    /**
+     * 读取维度配置 去掉id超出范围和重复的维度
+     * 没有主世界时补上默认的主世界
+     */
+    pub fn load(path: &str) -> Vec<DimensionConfig> {
+        let config: DimensionsConfig = match std::fs::File::open(path) {
+            Ok(file) => match ron::de::from_reader(file) {
+                Ok(config) => config,
+                Err(err) => {
+                    println!("维度配置解析失败{:?}", err);
+                    DimensionsConfig::default()
+                }
+            },
+            Err(_) => {
+                println!("没有找到维度配置文件{}", path);
+                DimensionsConfig::default()
+            }
+        };
+        let mut dimensions: Vec<DimensionConfig> = Vec::new();
+        for dimension in config.dimensions {
+            if dimension.id >= MAX_DIMENSIONS {
+                println!("维度{}的id超出范围{}", dimension.name, dimension.id);
+                continue;
+            }
+            if dimensions
+                .iter()
+                .any(|d| d.id == dimension.id || d.name == dimension.name)
+            {
+                println!("维度{}的id或者名字重复了", dimension.name);
+                continue;
+            }
+            dimensions.push(dimension);
+        }
+        if !dimensions.iter().any(|d| d.id == DimensionId::OVERWORLD.0) {
+            dimensions.insert(0, DimensionsConfig::default().dimensions.remove(0));
+        }
+        dimensions
+    }
+}
+
+pub fn get_dimension(dimension: DimensionId) -> Option<&'static DimensionConfig> {
+    DIMENSIONS.iter().find(|d| d.id == dimension.0)
+}
+
+pub fn find_dimension_by_name(name: &str) -> Option<&'static DimensionConfig> {
+    DIMENSIONS.iter().find(|d| d.name == name)
+}
+
+/**
+ * 按照维度的预设生成区块
+ * 返回的其他区块上的树和结构 ChunkKey 也在同一个维度
+ */
+pub fn gen_dimension_chunk(chunk_key: ChunkKey) -> (Vec<Voxel>, Vec<(Vec<ChunkKey>, OtherGentor)>) {
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    let Some(config) = get_dimension(chunk_key.1) else {
+        println!("没有找到维度{:?}", chunk_key.1);
+        return (vec![Voxel::EMPTY; SampleShape::SIZE as usize], Vec::new());
+    };
+    match &config.preset {
+        WorldPreset::Normal => {
+            let (voxels, mut others) = gen_chunk_data_by_seed(
+                config.seed,
+                chunk_key.with_dimension(DimensionId::OVERWORLD),
+            );
+            for (keys, _) in others.iter_mut() {
+                for key in keys.iter_mut() {
+                    *key = key.with_dimension(chunk_key.1);
+                }
+            }
+            (voxels, others)
+        }
+        WorldPreset::Flat(layers) => (gen_flat_chunk(chunk_key, layers), Vec::new()),
+        WorldPreset::Void => (vec![Voxel::EMPTY; SampleShape::SIZE as usize], Vec::new()),
+    }
+}
+
+// 超平坦世界第一层的高度 也就是最下面一个区块的底部
+pub fn flat_bottom_y() -> i32 {
+    WORLD_HEIGHT.min_chunk_y * CHUNK_SIZE
+}
+
+// 超平坦的区块 layers 从 flat_bottom_y 开始往上
+pub fn gen_flat_chunk(chunk_key: ChunkKey, layers: &[u8]) -> Vec<Voxel> {
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    let base_y = chunk_key.0.y * CHUNK_SIZE;
+    let bottom_y = flat_bottom_y();
+    (0..SampleShape::SIZE)
+        .map(|i| {
+            let [_, y, _] = SampleShape::delinearize(i);
+            let layer = base_y + y as i32 - bottom_y;
+            match usize::try_from(layer).ok().and_then(|l| layers.get(l)) {
+                Some(id) => Voxel {
+                    id: *id,
+                    ..Voxel::EMPTY
+                },
+                None => Voxel::EMPTY,
+            }
+        })
+        .collect()
+}
+
+/**
+ * 把碰撞分组移动到维度自己的分组上
+ * 主世界就是原来的分组 不同维度的物体不会发生碰撞
+ */
+pub fn dimension_collision_groups(
+    dimension: DimensionId,
+    memberships: Group,
+    filters: Group,
+) -> CollisionGroups {
+    let shift = dimension.0.min(MAX_DIMENSIONS - 1) as u32 * 3;
+    CollisionGroups::new(
+        Group::from_bits_truncate(memberships.bits() << shift),
+        Group::from_bits_truncate(filters.bits() << shift),
+    )
+}
+
+#[test]
+fn test_gen_flat_chunk() {
+    use super::voxel::{BasicStone, Grass, Soli, Stone, VoxelMaterial};
+    use bevy::prelude::IVec3;
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    let layers = [BasicStone::ID, Stone::ID, Soli::ID, Grass::ID];
+    let bottom = ChunkKey(IVec3::new(0, WORLD_HEIGHT.min_chunk_y, 0), DimensionId(1));
+    let voxels = gen_flat_chunk(bottom, &layers);
+    for (y, id) in layers.iter().enumerate() {
+        assert_eq!(
+            voxels[SampleShape::linearize([3, y as u32, 5]) as usize].id,
+            *id
+        );
+    }
+    assert_eq!(
+        voxels[SampleShape::linearize([3, layers.len() as u32, 5]) as usize],
+        Voxel::EMPTY
+    );
+    // 上面的区块是空的
+    let voxels = gen_flat_chunk(bottom.add_ivec3(IVec3::Y), &layers);
+    assert!(voxels.iter().all(|v| *v == Voxel::EMPTY));
+}
+
+#[test]
+fn test_dimension_collision_groups() {
+    let overworld = dimension_collision_groups(
+        DimensionId::OVERWORLD,
+        Group::GROUP_3,
+        Group::GROUP_1 | Group::GROUP_3,
+    );
+    assert_eq!(overworld.memberships, Group::GROUP_3);
+    assert_eq!(overworld.filters, Group::GROUP_1 | Group::GROUP_3);
+    let other = dimension_collision_groups(DimensionId(1), Group::GROUP_1, Group::GROUP_2);
+    assert_eq!(other.memberships, Group::GROUP_4);
+    assert_eq!(other.filters, Group::GROUP_5);
+}
@@ -0,0 +1,323 @@
+// 体素光照
+// 每个位置有天空光和方块光 取值 0..=MAX_LIGHT 存在一个字节中 高4位是天空光 低4位是方块光
+// 天空光从最上面直接往下照 再向四周扩散 方块光从发光的方块向四周扩散 每经过一格减一
+// 区块加载和修改时按照周围 3x3 列的区域重新计算 这样光可以跨过区块的边界
+
+use std::collections::VecDeque;
+
+use bevy::utils::HashMap;
+use block_mesh::{Voxel as MeshVoxel, VoxelVisibility};
+use lazy_static::lazy_static;
+use ndshape::{ConstShape, ConstShape3u32, RuntimeShape, Shape};
+use serde::{Deserialize, Serialize};
+
+use crate::{CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_U32};
+
+use super::{chunk::ChunkKey, voxel::Voxel, world_height::WORLD_HEIGHT};
+
+pub const LIGHTS_RON: &str = "lights.ron";
+
+pub const MAX_LIGHT: u8 = 15;
+// 没有光照数据时使用 只有满的天空光
+pub const FULL_SKY_LIGHT: u8 = MAX_LIGHT << 4;
+
+// 计算光照的区域 中心列以及周围的八列
+pub const LIGHT_REGION_SIZE: u32 = CHUNK_SIZE_U32 * 3;
+
+// 光照区域的形状 高度是整个世界的高度
+pub fn light_region_shape() -> RuntimeShape<u32, 3> {
+    WORLD_HEIGHT.column_shape(LIGHT_REGION_SIZE)
+}
+
+lazy_static! {
+    pub static ref LIGHT_EMITTERS: HashMap<u8, u8> = LightConfig::load(LIGHTS_RON).emitters;
+}
+
+// 光照配置
+// emitters: 发光的方块 体素id -> 亮度(最大 MAX_LIGHT)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LightConfig {
+    pub emitters: HashMap<u8, u8>,
+}
+
+impl LightConfig {
+    pub fn load(path: &str) -> Self {
+        match std::fs::File::open(path) {
+            Ok(file) => match ron::de::from_reader(file) {
+                Ok(config) => config,
+                Err(err) => {
+                    println!("光照配置解析失败{:?}", err);
+                    LightConfig::default()
+                }
+            },
+            Err(_) => {
+                println!("没有找到光照配置文件{}", path);
+                LightConfig::default()
+            }
+        }
+    }
+}
+
+// 一列区块的光照结果
+pub struct ColumnLight {
+    // 每个区块生成mesh时使用 和 ChunkMap::get_neighbors 的形状一样 顺序和 chunks 一致
+    pub padded: Vec<Vec<u8>>,
+    // 中心列每个区块的光照
+    pub chunks: Vec<(ChunkKey, Vec<u8>)>,
+}
+
+impl ColumnLight {
+    // 某一层区块生成mesh用的光照
+    pub fn section_padded(&self, chunk_y: i32) -> Option<&Vec<u8>> {
+        if !WORLD_HEIGHT.contains_chunk_y(chunk_y) {
+            return None;
+        }
+        self.padded
+            .get((chunk_y - WORLD_HEIGHT.min_chunk_y) as usize)
+    }
+}
+
+pub fn sky_light(light: u8) -> u8 {
+    light >> 4
+}
+
+pub fn block_light(light: u8) -> u8 {
+    light & 0x0F
+}
+
+// 方块自身的亮度
+pub fn emit_level(voxel: Voxel) -> u8 {
+    LIGHT_EMITTERS
+        .get(&voxel.id)
+        .copied()
+        .unwrap_or(0)
+        .min(MAX_LIGHT)
+}
+
+fn is_opaque(voxel: Voxel) -> bool {
+    matches!(voxel.get_visibility(), VoxelVisibility::Opaque)
+}
+
+// 光经过一格时减少的亮度 水里衰减得更快
+fn light_cost(voxel: Voxel) -> u8 {
+    if voxel.is_water() {
+        2
+    } else {
+        1
+    }
+}
+
+fn for_each_neighbor<S: Shape<3, Coord = u32>>(shape: &S, index: u32, mut f: impl FnMut(usize)) {
+    let [sx, sy, sz] = shape.as_array();
+    let [x, y, z] = shape.delinearize(index);
+    if x > 0 {
+        f(shape.linearize([x - 1, y, z]) as usize);
+    }
+    if x + 1 < sx {
+        f(shape.linearize([x + 1, y, z]) as usize);
+    }
+    if y > 0 {
+        f(shape.linearize([x, y - 1, z]) as usize);
+    }
+    if y + 1 < sy {
+        f(shape.linearize([x, y + 1, z]) as usize);
+    }
+    if z > 0 {
+        f(shape.linearize([x, y, z - 1]) as usize);
+    }
+    if z + 1 < sz {
+        f(shape.linearize([x, y, z + 1]) as usize);
+    }
+}
+
+// 从队列中的位置向四周扩散 shift 为4时处理天空光 为0时处理方块光
+fn spread<S: Shape<3, Coord = u32>>(
+    voxels: &[Voxel],
+    light: &mut [u8],
+    shape: &S,
+    queue: &mut VecDeque<u32>,
+    shift: u8,
+) {
+    while let Some(index) = queue.pop_front() {
+        let level = (light[index as usize] >> shift) & 0x0F;
+        if level <= 1 {
+            continue;
+        }
+        for_each_neighbor(shape, index, |n| {
+            let voxel = voxels[n];
+            if is_opaque(voxel) {
+                return;
+            }
+            let next = level.saturating_sub(light_cost(voxel));
+            if next > (light[n] >> shift) & 0x0F {
+                light[n] = (light[n] & !(0x0F << shift)) | (next << shift);
+                queue.push_back(n as u32);
+            }
+        });
+    }
+}
+
+/**
+ * 计算区域内每个位置的光照
+ * 区域的最上面当作露天
+ */
+pub fn compute_light<S: Shape<3, Coord = u32>>(voxels: &[Voxel], shape: &S) -> Vec<u8> {
+    let [sx, sy, sz] = shape.as_array();
+    let mut light = vec![0u8; voxels.len()];
+    // 1. 天空光直接往下照 直到碰到不透明的方块
+    for x in 0..sx {
+        for z in 0..sz {
+            let mut level = MAX_LIGHT;
+            for y in (0..sy).rev() {
+                let index = shape.linearize([x, y, z]) as usize;
+                let voxel = voxels[index];
+                if is_opaque(voxel) {
+                    break;
+                }
+                if voxel.is_water() {
+                    level -= 1;
+                }
+                if level == 0 {
+                    break;
+                }
+                light[index] = level << 4;
+            }
+        }
+    }
+    // 2. 只需要从旁边更暗的位置开始扩散天空光
+    let mut queue = VecDeque::new();
+    for index in 0..voxels.len() as u32 {
+        let sky = sky_light(light[index as usize]);
+        if sky <= 1 {
+            continue;
+        }
+        let mut darker = false;
+        for_each_neighbor(shape, index, |n| {
+            darker |= !is_opaque(voxels[n]) && sky_light(light[n]) + 1 < sky;
+        });
+        if darker {
+            queue.push_back(index);
+        }
+    }
+    spread(voxels, &mut light, shape, &mut queue, 4);
+    // 3. 方块光
+    for (index, voxel) in voxels.iter().enumerate() {
+        let emit = emit_level(*voxel);
+        if emit > 0 {
+            light[index] |= emit;
+            queue.push_back(index as u32);
+        }
+    }
+    spread(voxels, &mut light, shape, &mut queue, 0);
+    light
+}
+
+/**
+ * 把 3x3 列区域的光照 拆成生成mesh用的数据和中心列每个区块的数据
+ */
+pub fn split_region_light(chunk_key: ChunkKey, region_light: &[u8]) -> ColumnLight {
+    type DataShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    type PaddedShape =
+        ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
+    let region_shape = light_region_shape();
+    let height = WORLD_HEIGHT.height() as i32;
+    // 上下多一层 世界下面是黑的 上面是天空
+    let padded = (0..WORLD_HEIGHT.chunk_count())
+        .map(|layer| {
+            (0..PaddedShape::SIZE)
+                .map(|i| {
+                    let [x, y, z] = PaddedShape::delinearize(i);
+                    let region_y = (layer * CHUNK_SIZE_U32 + y) as i32 - 1;
+                    if region_y < 0 {
+                        0
+                    } else if region_y >= height {
+                        FULL_SKY_LIGHT
+                    } else {
+                        region_light[region_shape.linearize([
+                            x + CHUNK_SIZE_U32 - 1,
+                            region_y as u32,
+                            z + CHUNK_SIZE_U32 - 1,
+                        ]) as usize]
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let chunks = WORLD_HEIGHT
+        .chunk_ys()
+        .enumerate()
+        .map(|(layer, chunk_y)| {
+            let mut key = chunk_key;
+            key.0.y = chunk_y;
+            let data = (0..DataShape::SIZE)
+                .map(|i| {
+                    let [x, y, z] = DataShape::delinearize(i);
+                    region_light[region_shape.linearize([
+                        x + CHUNK_SIZE_U32,
+                        y + layer as u32 * CHUNK_SIZE_U32,
+                        z + CHUNK_SIZE_U32,
+                    ]) as usize]
+                })
+                .collect();
+            (key, data)
+        })
+        .collect();
+    ColumnLight { padded, chunks }
+}
+
+// 计算一列区块的光照 region 来自 ChunkMap::get_light_region
+pub fn compute_column_light(chunk_key: ChunkKey, region: &[Voxel]) -> ColumnLight {
+    let light = compute_light(region, &light_region_shape());
+    split_region_light(chunk_key, &light)
+}
+
+#[test]
+fn test_compute_light() {
+    use super::voxel::{Stone, VoxelMaterial};
+    type TestShape = ConstShape3u32<5, 6, 5>;
+    let stone = Stone::into_voxel();
+    let mut voxels = vec![Voxel::EMPTY; TestShape::SIZE as usize];
+    // y = 3 是一层石头 中间留一个洞
+    for x in 0..5 {
+        for z in 0..5 {
+            if x != 2 || z != 2 {
+                voxels[TestShape::linearize([x, 3, z]) as usize] = stone;
+            }
+        }
+    }
+    let light = compute_light(&voxels, &TestShape {});
+    let at = |p: [u32; 3]| light[TestShape::linearize(p) as usize];
+    // 上面露天
+    assert_eq!(sky_light(at([0, 5, 0])), MAX_LIGHT);
+    // 洞的正下方直接照到
+    assert_eq!(sky_light(at([2, 2, 2])), MAX_LIGHT);
+    // 旁边的位置扩散过去 每格减一
+    assert_eq!(sky_light(at([1, 2, 2])), MAX_LIGHT - 1);
+    assert_eq!(sky_light(at([0, 2, 0])), MAX_LIGHT - 4);
+    // 石头里面没有光
+    assert_eq!(at([0, 3, 0]), 0);
+    // 没有发光的方块
+    assert!(light.iter().all(|l| block_light(*l) == 0));
+}
+
+#[test]
+fn test_section_padded() {
+    type PaddedShape =
+        ConstShape3u32<CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32, CHUNK_SIZE_ADD_2_U32>;
+    let region = vec![Voxel::EMPTY; light_region_shape().size() as usize];
+    let light = compute_column_light(ChunkKey::new(bevy::prelude::IVec3::ZERO), &region);
+    assert_eq!(light.padded.len(), WORLD_HEIGHT.chunk_count() as usize);
+    let bottom = light.section_padded(WORLD_HEIGHT.min_chunk_y).unwrap();
+    // 最下面一层的下面没有光 区块里面是露天的
+    assert_eq!(bottom[PaddedShape::linearize([5, 0, 5]) as usize], 0);
+    assert_eq!(
+        bottom[PaddedShape::linearize([5, 1, 5]) as usize],
+        FULL_SKY_LIGHT
+    );
+    let top = light.section_padded(WORLD_HEIGHT.max_chunk_y).unwrap();
+    assert_eq!(
+        top[PaddedShape::linearize([5, CHUNK_SIZE_U32 + 1, 5]) as usize],
+        FULL_SKY_LIGHT
+    );
+    assert!(light.section_padded(WORLD_HEIGHT.max_chunk_y + 1).is_none());
+}
@@ -0,0 +1,131 @@
+// 远处地形的细节层次(LOD)
+// 完整精度的视野外面 一列区块按照距离降采样成 2 4 8 倍大的格子
+// 服务器只发送降采样之后的数据 客户端直接用它生成低精度的mesh
+
+use bevy::utils::HashMap;
+use block_mesh::{Voxel as MeshVoxel, VoxelVisibility};
+use ndshape::{ConstShape, ConstShape3u32, RuntimeShape, Shape};
+
+use crate::CHUNK_SIZE_U32;
+
+use super::{voxel::Voxel, world_height::WORLD_HEIGHT};
+
+// 每一级LOD的倍数
+pub const LOD_FACTORS: [u32; 3] = [2, 4, 8];
+// 每一级LOD最远的区块距离 和完整精度的视野一样按正方形计算
+pub const LOD_CHUNK_DISTANCES: [i32; 3] = [12, 16, 24];
+
+/**
+ * 这个距离的列使用的LOD倍数
+ * full_distance 以内使用完整精度 最后一级之外不显示
+ */
+pub fn lod_factor(chunk_distance: i32, full_distance: i32) -> Option<u32> {
+    if chunk_distance <= full_distance {
+        return None;
+    }
+    LOD_FACTORS
+        .iter()
+        .zip(LOD_CHUNK_DISTANCES)
+        .find(|(_, distance)| chunk_distance <= *distance)
+        .map(|(factor, _)| *factor)
+}
+
+pub fn max_lod_distance() -> i32 {
+    LOD_CHUNK_DISTANCES[LOD_CHUNK_DISTANCES.len() - 1]
+}
+
+// 一列降采样之后的形状
+pub fn lod_shape(factor: u32) -> RuntimeShape<u32, 3> {
+    column_lod_shape(WORLD_HEIGHT.chunk_count(), factor)
+}
+
+fn column_lod_shape(chunk_count: u32, factor: u32) -> RuntimeShape<u32, 3> {
+    RuntimeShape::<u32, 3>::new([
+        CHUNK_SIZE_U32 / factor,
+        chunk_count * CHUNK_SIZE_U32 / factor,
+        CHUNK_SIZE_U32 / factor,
+    ])
+}
+
+/**
+ * 把一列区块降采样 chunks 是从下到上的每个区块
+ * 格子里至少一半是实心方块时才是实心的 类型取格子里最上面一层实心方块中最多的 这样地表还是草地
+ * 水 装饰方块 半透明方块和特殊mesh的方块都当作空气 远处只显示地形
+ */
+pub fn downsample_column(chunks: &[Vec<Voxel>], factor: u32) -> Vec<Voxel> {
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    let shape = column_lod_shape(chunks.len() as u32, factor);
+    let get = |x: u32, y: u32, z: u32| {
+        let chunk = &chunks[(y / CHUNK_SIZE_U32) as usize];
+        chunk[SampleShape::linearize([x, y % CHUNK_SIZE_U32, z]) as usize]
+    };
+    let mut result = vec![Voxel::EMPTY; shape.size() as usize];
+    for (index, cell) in result.iter_mut().enumerate() {
+        let [cx, cy, cz] = shape.delinearize(index as u32).map(|v| v * factor);
+        let mut solid = 0;
+        let mut top: HashMap<Voxel, u32> = HashMap::new();
+        for y in (cy..cy + factor).rev() {
+            // 只统计最上面有实心方块的一层
+            let top_layer = top.is_empty();
+            for x in cx..cx + factor {
+                for z in cz..cz + factor {
+                    let voxel = get(x, y, z);
+                    if let VoxelVisibility::Opaque = voxel.get_visibility() {
+                        solid += 1;
+                        if top_layer {
+                            *top.entry(voxel).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+        }
+        if solid * 2 >= factor * factor * factor {
+            if let Some((voxel, _)) = top
+                .into_iter()
+                .max_by_key(|(voxel, count)| (*count, voxel.id))
+            {
+                *cell = voxel;
+            }
+        }
+    }
+    result
+}
+
+#[test]
+fn test_lod_factor() {
+    assert_eq!(lod_factor(3, 8), None);
+    assert_eq!(lod_factor(8, 8), None);
+    assert_eq!(lod_factor(9, 8), Some(2));
+    assert_eq!(lod_factor(16, 8), Some(4));
+    assert_eq!(lod_factor(17, 8), Some(8));
+    assert_eq!(lod_factor(25, 8), None);
+}
+
+#[test]
+fn test_downsample_column() {
+    use super::voxel::{Grass, Soli, Stone, VoxelMaterial, Water};
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    // 下面的区块是石头 上面的区块下面3层是泥土 一层草地 再往上是水
+    let bottom = vec![Stone::into_voxel(); SampleShape::SIZE as usize];
+    let mut top = vec![Voxel::EMPTY; SampleShape::SIZE as usize];
+    for (index, voxel) in top.iter_mut().enumerate() {
+        let [_, y, _] = SampleShape::delinearize(index as u32);
+        *voxel = match y {
+            0..=2 => Soli::into_voxel(),
+            3 => Grass::into_voxel(),
+            4..=5 => Water::into_voxel(),
+            _ => Voxel::EMPTY,
+        };
+    }
+    let shape = column_lod_shape(2, 4);
+    let lod = downsample_column(&[bottom, top], 4);
+    assert_eq!(lod.len(), shape.size() as usize);
+    let at = |y: u32| lod[shape.linearize([1, y, 2]) as usize];
+    assert_eq!(at(0), Stone::into_voxel());
+    assert_eq!(at(3), Stone::into_voxel());
+    // 草地在最上面
+    assert_eq!(at(4), Grass::into_voxel());
+    // 水当作空气
+    assert_eq!(at(5), Voxel::EMPTY);
+    assert_eq!(at(7), Voxel::EMPTY);
+}
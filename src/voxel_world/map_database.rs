@@ -7,9 +7,14 @@ use bevy::{
 use ndshape::{ConstShape, ConstShape3u32};
 use sled::Db;
 
-use crate::{voxel_world::map_generator::gen_chunk_data_by_seed, CHUNK_SIZE_U32, CLIENT_MAP_GEN};
+use crate::{CHUNK_SIZE_U32, CLIENT_MAP_GEN};
 
-use super::{biomes::OtherTreeTasksMap, chunk::ChunkKey, voxel::Voxel};
+use super::{
+    biomes::OtherTreeTasksMap, chunk::ChunkKey, dimension::gen_dimension_chunk, voxel::Voxel,
+};
+
+// 主世界默认的种子 其他维度的种子见 dimensions.ron
+pub const MAP_SEED: i32 = 1512354854;
 
 #[derive(Resource)]
 pub struct MapDataBase {
@@ -39,9 +44,9 @@ impl MapDataBase {
         match self.db.get(key) {
             Ok(rs) => match if CLIENT_MAP_GEN { None } else { rs } {
                 Some(data) => bincode::deserialize(&data).unwrap(),
-                // 这里在没有获取到的情况下使用算法的值
+                // 这里在没有获取到的情况下使用维度的预设生成
                 None => {
-                    let (new_voxels, other_trees) = gen_chunk_data_by_seed(1512354854, chunk_key);
+                    let (new_voxels, other_trees) = gen_dimension_chunk(chunk_key);
                     let new_voxels_clone = new_voxels.clone();
                     let task = pool.spawn(async move { (key, new_voxels_clone) });
                     db_tasks.tasks.push(task);
@@ -57,11 +62,47 @@ impl MapDataBase {
     }
 }
 
+// 只读取区块数据 没有时使用维度的预设生成但是不保存 给远处的LOD使用
+pub fn peek_chunk(db: &Db, chunk_key: ChunkKey) -> Vec<Voxel> {
+    type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+    match db.get(chunk_key.as_u8_array()) {
+        Ok(Some(data)) if !CLIENT_MAP_GEN => bincode::deserialize(&data).unwrap(),
+        Ok(_) => gen_dimension_chunk(chunk_key).0,
+        Err(e) => {
+            println!("wrong, to get Map {:?}", e);
+            vec![Voxel::EMPTY; SampleShape::SIZE as usize]
+        }
+    }
+}
+
 #[derive(Debug, Resource)]
 pub struct DbSaveTasks {
     pub tasks: Vec<Task<([u8; 8], Vec<Voxel>)>>,
 }
 
+impl DbSaveTasks {
+    // 等待全部任务完成并写入数据库 返回写入的数量
+    pub fn flush(&mut self, db: &MapDataBase) -> usize {
+        let len = self.tasks.len();
+        for ele in self.tasks.drain(..) {
+            let (key, data) = futures_lite::future::block_on(ele);
+            save_chunk_data(db, key, &data);
+        }
+        len
+    }
+}
+
+fn save_chunk_data(db: &MapDataBase, key: [u8; 8], data: &Vec<Voxel>) {
+    match db.db.insert(key, bincode::serialize(data).unwrap()) {
+        Ok(_) => {
+            // println!("数据保存成功");
+        }
+        Err(err) => {
+            println!("数据保存问题{:?}", err);
+        }
+    }
+}
+
 pub fn save_db_task_system(mut db_save_task: ResMut<DbSaveTasks>, db: ResMut<MapDataBase>) {
     // 一次最多处理6个
     let len = db_save_task.tasks.len().min(6);
@@ -69,14 +110,7 @@ pub fn save_db_task_system(mut db_save_task: ResMut<DbSaveTasks>, db: ResMut<Map
         if let Some((key, data)) =
             futures_lite::future::block_on(futures_lite::future::poll_once(ele))
         {
-            match db.db.insert(key, bincode::serialize(&data).unwrap()) {
-                Ok(_) => {
-                    // println!("数据保存成功");
-                }
-                Err(err) => {
-                    println!("数据保存问题{:?}", err);
-                }
-            }
+            save_chunk_data(&db, key, &data);
         }
     }
 }
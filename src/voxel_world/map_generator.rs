@@ -12,12 +12,12 @@ use crate::{
     CHUNK_SIZE, CHUNK_SIZE_U32,
 };
 
-use super::{biomes::TreeGentor, chunk::ChunkKey, voxel::Voxel};
+use super::{biomes::OtherGentor, chunk::ChunkKey, voxel::Voxel};
 
 pub fn gen_chunk_data_by_seed(
     seed: i32,
     chunk_key: ChunkKey,
-) -> (Vec<Voxel>, Vec<(Vec<ChunkKey>, TreeGentor)>) {
+) -> (Vec<Voxel>, Vec<(Vec<ChunkKey>, OtherGentor)>) {
     // let base_x = (chunk_key.0.x * CHUNK_SIZE) as f32;
     let base_y: f32 = (chunk_key.0.y * CHUNK_SIZE) as f32;
     // let base_z = (chunk_key.0.z * CHUNK_SIZE) as f32;
@@ -81,7 +81,7 @@ pub fn gen_chunk_data_by_seed(
     }
 
     // 处理不同群落
-    let others: Vec<(Vec<ChunkKey>, crate::voxel_world::biomes::TreeGentor)> =
+    let others: Vec<(Vec<ChunkKey>, OtherGentor)> =
         biomes_generate(chunk_key, seed, suface_index, &mut voxels);
 
     //生成 沙子
@@ -96,6 +96,7 @@ pub fn gen_chunk_data_by_seed(
                 || (z != 0 && check_water(voxels.clone(), [x, y, z - 1])))
                 && voxels[i as usize].id != Water::ID
                 && voxels[i as usize].id != Voxel::EMPTY.id
+                && !voxels[i as usize].is_decoration()
             {
                 voxels[i as usize] = Sand::into_voxel()
             }
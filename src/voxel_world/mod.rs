@@ -1,9 +1,15 @@
 pub mod biomes;
+pub mod block_registry;
 pub mod chunk;
 pub mod chunk_map;
 pub mod compress;
+pub mod dimension;
+pub mod light;
+pub mod lod;
 pub mod map_database;
 pub mod map_generator;
 pub mod player_state;
+pub mod pos;
 pub mod voxel;
-pub mod voxel_mesh;
\ No newline at end of file
+pub mod voxel_mesh;
+pub mod world_height;
@@ -3,12 +3,39 @@ use serde::{Deserialize, Serialize};
 
 use crate::MAX_STAFF_FIXED;
 
-use super::map_database::MapDataBase;
+use super::{dimension::DimensionId, map_database::MapDataBase};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct PlayerState {
     pub position: [f32; 3],
     pub toolbar: [(Option<usize>, usize); 10],
+    // 所在的维度
+    pub dimension: DimensionId,
+}
+
+// 加入维度之前保存的玩家数据
+#[derive(Debug, Serialize, Deserialize)]
+struct LegacyPlayerState {
+    position: [f32; 3],
+    toolbar: [(Option<usize>, usize); 10],
+}
+
+// 解析保存的玩家数据 以前的数据没有维度 当作在主世界
+fn decode_player_state(data: &[u8]) -> Option<PlayerState> {
+    if let Ok(state) = bincode::deserialize(data) {
+        return Some(state);
+    }
+    match bincode::deserialize::<LegacyPlayerState>(data) {
+        Ok(legacy) => Some(PlayerState {
+            position: legacy.position,
+            toolbar: legacy.toolbar,
+            dimension: DimensionId::OVERWORLD,
+        }),
+        Err(err) => {
+            println!("玩家数据解析失败{:?}", err);
+            None
+        }
+    }
 }
 
 impl PlayerState {
@@ -81,7 +108,7 @@ impl StoragePlayerState for MapDataBase {
         let key_str = format!("U:{}", username);
         let key = key_str.as_bytes();
         match self.db.get(key) {
-            Ok(rs) => rs.map(|data| bincode::deserialize(&data).unwrap()),
+            Ok(rs) => rs.and_then(|data| decode_player_state(&data)),
             Err(_) => {
                 println!("获取玩家状态时报错");
                 None
@@ -92,3 +119,28 @@ impl StoragePlayerState for MapDataBase {
 
 #[derive(Debug, Component, Clone)]
 pub struct PlayerOnTimeState(pub PlayerState);
+
+#[test]
+fn test_decode_legacy_player_state() {
+    let mut toolbar = [(None, 0); 10];
+    toolbar[2] = (Some(5), 12);
+    let legacy = bincode::serialize(&LegacyPlayerState {
+        position: [1., 2., 3.],
+        toolbar,
+    })
+    .unwrap();
+    let state = decode_player_state(&legacy).unwrap();
+    assert_eq!(state.position, [1., 2., 3.]);
+    assert_eq!(state.toolbar[2], (Some(5), 12));
+    assert_eq!(state.dimension, DimensionId::OVERWORLD);
+
+    let data = bincode::serialize(&PlayerState {
+        dimension: DimensionId(2),
+        ..state
+    })
+    .unwrap();
+    assert_eq!(
+        decode_player_state(&data).unwrap().dimension,
+        DimensionId(2)
+    );
+}
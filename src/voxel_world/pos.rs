@@ -0,0 +1,191 @@
+// 整数的方块坐标
+// 世界中的方块 BlockPos(b) 占据 [b, b + 1) 它的中心点是 b + 0.5
+// 区块 ChunkPos(k) 包含的方块是 [k * 16 - 8, k * 16 + 8) 区块内的位置 LocalPos 是 0..16
+// 所有的转换都只用整数计算 不会在区块的边界和负坐标上出现差一的问题
+
+use bevy::prelude::{IVec3, Vec3};
+use ndshape::{ConstShape, ConstShape3u32};
+use serde::{Deserialize, Serialize};
+
+use crate::{CHUNK_SIZE, CHUNK_SIZE_U32};
+
+use super::{chunk::ChunkKey, dimension::DimensionId};
+
+// 区块 0 的第一个方块
+pub const CHUNK_ORIGIN_OFFSET: i32 = CHUNK_SIZE / 2;
+
+type SampleShape = ConstShape3u32<CHUNK_SIZE_U32, CHUNK_SIZE_U32, CHUNK_SIZE_U32>;
+
+// 世界中的方块位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockPos(pub IVec3);
+
+// 区块的位置 不带维度 带维度的是 ChunkKey
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChunkPos(pub IVec3);
+
+// 区块内的位置 每个分量都在 0..CHUNK_SIZE 中
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocalPos(pub [u32; 3]);
+
+impl BlockPos {
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self(IVec3::new(x, y, z))
+    }
+
+    // 点所在的方块 方块的边界属于坐标更大的方块
+    pub fn from_vec3(pos: Vec3) -> Self {
+        Self(pos.floor().as_ivec3())
+    }
+
+    pub fn from_chunk_local(chunk: ChunkPos, local: LocalPos) -> Self {
+        Self(chunk.min_block().0 + local.as_ivec3())
+    }
+
+    // 方块的中心点
+    pub fn center(&self) -> Vec3 {
+        self.0.as_vec3() + Vec3::splat(0.5)
+    }
+
+    pub fn offset(&self, offset: IVec3) -> Self {
+        Self(self.0 + offset)
+    }
+
+    pub fn chunk(&self) -> ChunkPos {
+        let p = self.0 + IVec3::splat(CHUNK_ORIGIN_OFFSET);
+        ChunkPos(IVec3::new(
+            p.x.div_euclid(CHUNK_SIZE),
+            p.y.div_euclid(CHUNK_SIZE),
+            p.z.div_euclid(CHUNK_SIZE),
+        ))
+    }
+
+    pub fn local(&self) -> LocalPos {
+        let p = self.0 + IVec3::splat(CHUNK_ORIGIN_OFFSET);
+        LocalPos([
+            p.x.rem_euclid(CHUNK_SIZE) as u32,
+            p.y.rem_euclid(CHUNK_SIZE) as u32,
+            p.z.rem_euclid(CHUNK_SIZE) as u32,
+        ])
+    }
+
+    pub fn to_chunk_local(&self) -> (ChunkPos, LocalPos) {
+        (self.chunk(), self.local())
+    }
+}
+
+impl ChunkPos {
+    // 点所在的区块
+    pub fn from_vec3(pos: Vec3) -> Self {
+        BlockPos::from_vec3(pos).chunk()
+    }
+
+    // 区块中坐标最小的方块
+    pub fn min_block(&self) -> BlockPos {
+        BlockPos(self.0 * CHUNK_SIZE - IVec3::splat(CHUNK_ORIGIN_OFFSET))
+    }
+
+    pub fn key(&self, dimension: DimensionId) -> ChunkKey {
+        ChunkKey(self.0, dimension)
+    }
+}
+
+impl From<ChunkKey> for ChunkPos {
+    fn from(chunk_key: ChunkKey) -> Self {
+        ChunkPos(chunk_key.0)
+    }
+}
+
+impl LocalPos {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Self([x, y, z])
+    }
+
+    // 区块数据中的下标
+    pub fn from_index(index: u32) -> Self {
+        Self(SampleShape::delinearize(index))
+    }
+
+    pub fn index(&self) -> usize {
+        SampleShape::linearize(self.0) as usize
+    }
+
+    pub fn as_ivec3(&self) -> IVec3 {
+        IVec3::new(self.0[0] as i32, self.0[1] as i32, self.0[2] as i32)
+    }
+}
+
+#[test]
+fn test_chunk_borders() {
+    // 区块 0 是 [-8, 8)
+    assert_eq!(
+        BlockPos::new(-8, 7, 0).to_chunk_local(),
+        (ChunkPos(IVec3::ZERO), LocalPos::new(0, 15, 8))
+    );
+    assert_eq!(BlockPos::new(8, 0, 0).chunk(), ChunkPos(IVec3::X));
+    assert_eq!(
+        BlockPos::new(-9, 0, 0).to_chunk_local(),
+        (ChunkPos(IVec3::new(-1, 0, 0)), LocalPos::new(15, 8, 8))
+    );
+    assert_eq!(
+        BlockPos::new(-24, 0, 0).chunk(),
+        ChunkPos(IVec3::new(-1, 0, 0))
+    );
+    assert_eq!(
+        BlockPos::new(-25, 0, 0).chunk(),
+        ChunkPos(IVec3::new(-2, 0, 0))
+    );
+    // 边界上的点属于坐标更大的方块
+    assert_eq!(
+        ChunkPos::from_vec3(Vec3::new(8.0, -8.0, -8.001)),
+        ChunkPos(IVec3::new(1, 0, -1))
+    );
+    assert_eq!(
+        BlockPos::from_vec3(Vec3::new(-0.2, 3.2, -1.0)),
+        BlockPos::new(-1, 3, -1)
+    );
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn prop_block_chunk_local_roundtrip(
+        x in -100_000i32..100_000,
+        y in -512i32..512,
+        z in -100_000i32..100_000,
+    ) {
+        let block = BlockPos::new(x, y, z);
+        let (chunk, local) = block.to_chunk_local();
+        proptest::prop_assert!(local.0.iter().all(|v| *v < CHUNK_SIZE_U32));
+        proptest::prop_assert_eq!(BlockPos::from_chunk_local(chunk, local), block);
+        proptest::prop_assert_eq!(LocalPos::from_index(local.index() as u32), local);
+    }
+
+    #[test]
+    fn prop_center_in_same_block(
+        x in -100_000i32..100_000,
+        y in -512i32..512,
+        z in -100_000i32..100_000,
+    ) {
+        let block = BlockPos::new(x, y, z);
+        proptest::prop_assert_eq!(BlockPos::from_vec3(block.center()), block);
+        proptest::prop_assert_eq!(BlockPos::from_vec3(block.0.as_vec3()), block);
+        proptest::prop_assert_eq!(ChunkPos::from_vec3(block.center()), block.chunk());
+    }
+
+    #[test]
+    fn prop_offset_moves_chunk(
+        x in -100_000i32..100_000,
+        z in -100_000i32..100_000,
+        dx in -40i32..40,
+    ) {
+        let block = BlockPos::new(x, 0, z);
+        let moved = block.offset(IVec3::new(dx, 0, 0));
+        let chunk_dx = moved.chunk().0.x - block.chunk().0.x;
+        // 区块之间的距离和方块之间的距离一致
+        let expected = moved.chunk().min_block().0.x - block.chunk().min_block().0.x;
+        proptest::prop_assert_eq!(chunk_dx * CHUNK_SIZE, expected);
+        proptest::prop_assert!(moved.chunk().min_block().0.x <= moved.0.x);
+        proptest::prop_assert!(moved.0.x < moved.chunk().min_block().0.x + CHUNK_SIZE);
+    }
+}
@@ -4,7 +4,7 @@ use bevy::{prelude::Quat, reflect::Reflect};
 use block_mesh::{MergeVoxel, Voxel as MeshVoxel, VoxelVisibility};
 use serde::{Deserialize, Serialize};
 
-use super::voxel_mesh::VOXEL_MESH_MAP;
+use super::block_registry::{BlockKind, BlockModel, BlockProperty, ColliderShape, BLOCKS};
 
 /**
  * 体素类型
@@ -51,6 +51,16 @@ impl VoxelDirection {
             VoxelDirection::NX => Quat::from_rotation_y(3.0 * PI / 2.0),
         }
     }
+
+    // 相对于Z方向 绕y轴旋转90度的次数
+    pub fn rotate_steps(&self) -> u32 {
+        match self {
+            VoxelDirection::Z => 0,
+            VoxelDirection::X => 1,
+            VoxelDirection::NZ => 2,
+            VoxelDirection::NX => 3,
+        }
+    }
 }
 
 pub const VOXEL_DIRECTION_VEC: [VoxelDirection; 4] = [
@@ -120,19 +130,124 @@ impl Voxel {
             },
         }
     }
+
+    // 方块属性 定义在 blocks.ron 中
+    pub fn property(&self) -> Option<&'static BlockProperty> {
+        BLOCKS.get(self.id)
+    }
+
+    pub fn kind(&self) -> BlockKind {
+        BLOCKS.kind(self.id)
+    }
+
+    // 是否是装饰方块
+    pub fn is_decoration(&self) -> bool {
+        self.kind() == BlockKind::Decoration
+    }
+
+    // 是否是半透明的方块
+    pub fn is_translucent(&self) -> bool {
+        self.kind() == BlockKind::Transparent
+    }
+
+    // 是否是镂空的方块
+    pub fn is_cutout(&self) -> bool {
+        self.kind() == BlockKind::Cutout
+    }
+
+    // 特殊模型的配置
+    pub fn model(&self) -> Option<&'static BlockModel> {
+        BLOCKS.model(self.id)
+    }
+
+    // 是否有特殊模型
+    pub fn has_model(&self) -> bool {
+        self.model().is_some()
+    }
+
+    // 是否可以被破坏
+    pub fn is_breakable(&self) -> bool {
+        BLOCKS.breakable(self.id)
+    }
+
+    pub fn collider_shape(&self) -> ColliderShape {
+        BLOCKS.collider(self.id)
+    }
+
+    // 是否有碰撞体 没有碰撞体的方块可以穿过
+    pub fn has_collider(&self) -> bool {
+        self.collider_shape() != ColliderShape::None
+    }
+
+    // 是否是树叶 没有连接到原木时会枯萎
+    pub fn is_leaf(&self) -> bool {
+        LEAF_IDS.contains(&self.id)
+    }
+
+    // 是否是原木 树叶要连接到原木上
+    pub fn is_log(&self) -> bool {
+        LOG_IDS.contains(&self.id)
+    }
+
+    // 下面空了以后是否会掉下来
+    pub fn has_gravity(&self) -> bool {
+        GRAVITY_IDS.contains(&self.id)
+    }
+
+    // 是否是水 包括水源和流动的水
+    pub fn is_water(&self) -> bool {
+        self.water_level() > 0
+    }
+
+    // 水位 水源是 WATER_SOURCE_LEVEL 流动的水是 1..WATER_SOURCE_LEVEL 不是水为0
+    pub fn water_level(&self) -> u8 {
+        if self.id == Water::ID {
+            WATER_SOURCE_LEVEL
+        } else if self.id >= FlowingWater::ID && self.id < FlowingWater::ID + WATER_SOURCE_LEVEL - 1
+        {
+            self.id - FlowingWater::ID + 1
+        } else {
+            0
+        }
+    }
+
+    // 指定水位的流动的水
+    pub fn flowing_water(level: u8) -> Self {
+        if level >= WATER_SOURCE_LEVEL {
+            return Water::into_voxel();
+        }
+        if level == 0 {
+            return Voxel::EMPTY;
+        }
+        Self {
+            id: FlowingWater::ID + level - 1,
+            direction: VoxelDirection::Z,
+        }
+    }
+
+    // 把体素的方向按照 direction 再旋转一次
+    pub fn rotate_by(&self, direction: VoxelDirection) -> Self {
+        let mut voxel = *self;
+        for _ in 0..direction.rotate_steps() {
+            voxel = voxel.next_direction();
+        }
+        voxel
+    }
 }
 
 impl MeshVoxel for Voxel {
     fn get_visibility(&self) -> VoxelVisibility {
-        // 这里控制显示问题
-        if VOXEL_MESH_MAP.contains_key(&self.id) {
+        // 特殊模型单独生成mesh
+        if self.has_model() {
             return VoxelVisibility::Empty;
         }
-        // 这里过滤掉水
-        if self.id > 0 && self.id != 5 {
-            return VoxelVisibility::Opaque;
+        match self.kind() {
+            BlockKind::Solid => VoxelVisibility::Opaque,
+            // 镂空的方块可以看到后面 不会挡住旁边方块的面
+            BlockKind::Transparent | BlockKind::Cutout => VoxelVisibility::Translucent,
+            // 水和装饰方块单独生成mesh
+            BlockKind::Air | BlockKind::Liquid | BlockKind::Decoration => VoxelVisibility::Empty,
         }
-        VoxelVisibility::Empty
     }
 }
 
@@ -193,3 +308,26 @@ voxel_material!(AppleWood, 苹果树原木, 10);
 voxel_material!(AppleLeaf, 苹果树叶子, 11);
 voxel_material!(TestCube, 测试方块, 12);
 voxel_material!(WorkCube, 工作方块, 13);
+voxel_material!(PineLeaf, 松针, 14);
+voxel_material!(Cactus, 仙人掌, 15);
+voxel_material!(SnowCabbage, 雪白菜, 16);
+voxel_material!(TallGrass, 草丛, 17);
+voxel_material!(Flower, 花, 18);
+voxel_material!(DeadBush, 枯草, 19);
+
+// 流动的水 水位1到7 依次占用 20..=26
+voxel_material!(FlowingWater, 流水, 20);
+voxel_material!(Lamp, 灯, 27);
+voxel_material!(Sapling, 树苗, 28);
+voxel_material!(Glass, 玻璃, 29);
+voxel_material!(Ice, 冰, 30);
+
+// 树叶和原木
+pub const LEAF_IDS: [u8; 2] = [AppleLeaf::ID, PineLeaf::ID];
+pub const LOG_IDS: [u8; 1] = [AppleWood::ID];
+
+// 受重力影响的方块 下面空了会掉下来
+pub const GRAVITY_IDS: [u8; 2] = [Sand::ID, Sown::ID];
+
+// 水源的水位 流动的水最高是 WATER_SOURCE_LEVEL - 1
+pub const WATER_SOURCE_LEVEL: u8 = 8;
@@ -1,42 +1,51 @@
 // 特别的体素类型的展示
 // 这个地方要给server和client使用
+// 有哪些特殊模型 以及它们的碰撞体和朝向 都在 blocks.ron 的 model 中配置
 
 use bevy::{
-    prelude::{AssetServer, Handle, Image, Mesh, Plugin, Res, ResMut, Resource, Startup},
+    prelude::{
+        AssetEvent, AssetServer, Event, EventReader, EventWriter, Handle, Mesh, Plugin, Quat, Res,
+        ResMut, Resource, Startup, UVec3, Update,
+    },
     utils::HashMap,
 };
 use bevy_vox_mesh::VoxMeshPlugin;
-use lazy_static::lazy_static;
 
-use crate::voxel_world::voxel::{VoxelMaterial, WorkCube};
+use crate::voxel_world::block_registry::{BlockModel, ColliderShape, ModelRotation, BLOCKS};
+
+use super::voxel::VoxelDirection;
+
+pub mod vox_collider;
+
+// .vox 模型中一个体素的大小 一个方块的边长是32个模型体素
+pub const VOX_SCALE: f32 = 1.0 / 32.0;
 
 #[derive(Debug, Clone)]
-pub struct VoxelMeshConfig {
+pub struct MeshMateData {
+    pub mesh: Handle<Mesh>,
     // 是否生成碰撞体
     pub collider: bool,
-    // 模型列表
-    pub vox_list: Vec<String>,
-    // 图片列表
-    pub image_list: Vec<String>,
+    pub model: BlockModel,
 }
 
-lazy_static! {
-    pub static ref VOXEL_MESH_MAP: HashMap<u8, VoxelMeshConfig> = {
-        let mut map = HashMap::new();
-        // 这里加上数据
-        map.insert(WorkCube::into_voxel().id,VoxelMeshConfig{
-            collider: true,
-            vox_list: vec![String::from("vox/工作台.vox")],
-            image_list: Vec::new(),
-        });
-        map
-    };
+// MagicaVoxel 中 z 轴朝上 转换成 y 轴朝上
+// 和 bevy_vox_mesh 生成mesh时一样 直接交换 y 和 z 不做镜像
+// 碰撞体和结构都使用这里的转换 才能和看到的模型一致
+pub fn vox_to_y_up(voxel: &dot_vox::Voxel) -> UVec3 {
+    UVec3::new(voxel.x as u32, voxel.z as u32, voxel.y as u32)
 }
 
-#[derive(Debug, Clone)]
-pub struct MeshMateData {
-    pub vox_list: Vec<Handle<Mesh>>,
-    pub image_list: Vec<Handle<Image>>,
+// y 轴朝上的模型大小
+pub fn vox_size(model: &dot_vox::Model) -> UVec3 {
+    UVec3::new(model.size.x, model.size.z, model.size.y)
+}
+
+// 模型的旋转 固定方向的模型不跟随方块的方向
+pub fn model_rotation(model: &BlockModel, direction: VoxelDirection) -> Quat {
+    match model.rotation {
+        ModelRotation::Horizontal => direction.to_quat(),
+        ModelRotation::Fixed => Quat::IDENTITY,
+    }
 }
 
 #[derive(Debug, Clone, Resource)]
@@ -44,6 +53,12 @@ pub struct VoxelMeshStorge {
     pub data: HashMap<u8, MeshMateData>,
 }
 
+// 模型文件被修改之后重新加载了 id 是用到这个模型的方块
+#[derive(Debug, Event)]
+pub struct ModelReloadEvent {
+    pub id: u8,
+}
+
 // 加载数据
 pub struct VoxelMeshPlugin;
 
@@ -53,27 +68,61 @@ impl Plugin for VoxelMeshPlugin {
         app.insert_resource(VoxelMeshStorge {
             data: HashMap::new(),
         });
+        app.add_event::<ModelReloadEvent>();
         app.add_systems(Startup, init_mesh_resource);
+        app.add_systems(Update, model_reload_system);
     }
 }
 
 fn init_mesh_resource(assets: Res<AssetServer>, mut voxel_mesh_storge: ResMut<VoxelMeshStorge>) {
-    for (key, config) in VOXEL_MESH_MAP.iter() {
-        let mut vox_list: Vec<Handle<Mesh>> = Vec::new();
-        for vox_str in config.vox_list.iter() {
-            println!("加载了模型: {}", vox_str);
-            vox_list.push(assets.load(vox_str));
+    for block in BLOCKS.models() {
+        if let Some(model) = &block.model {
+            println!("加载了模型: {}", model.vox);
+            voxel_mesh_storge.data.insert(
+                block.id,
+                MeshMateData {
+                    mesh: assets.load(model.vox.as_str()),
+                    collider: block.collider == ColliderShape::Model,
+                    model: model.clone(),
+                },
+            );
         }
-        let mut image_list: Vec<Handle<Image>> = Vec::new();
-        for image_str in config.image_list.iter() {
-            image_list.push(assets.load(image_str));
+    }
+}
+
+// 模型文件修改后 AssetServer 会重新加载 这里通知用到它的地方
+fn model_reload_system(
+    mut asset_events: EventReader<AssetEvent<Mesh>>,
+    voxel_mesh_storge: Res<VoxelMeshStorge>,
+    mut event_writer: EventWriter<ModelReloadEvent>,
+) {
+    for event in asset_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            for (id, data) in voxel_mesh_storge.data.iter() {
+                if data.mesh == *handle {
+                    println!("模型重新加载了: {}", data.model.vox);
+                    event_writer.send(ModelReloadEvent { id: *id });
+                }
+            }
         }
-        voxel_mesh_storge.data.insert(
-            *key,
-            MeshMateData {
-                vox_list: vox_list,
-                image_list: image_list,
-            },
-        );
+    }
+}
+
+#[test]
+fn test_model_rotation() {
+    use crate::voxel_world::voxel::VOXEL_DIRECTION_VEC;
+    let mut model = BlockModel {
+        vox: "vox/工作台.vox".to_string(),
+        collider: Default::default(),
+        bbox: Default::default(),
+        rotation: ModelRotation::Fixed,
+    };
+    // 固定方向的模型不管方块朝哪里都不旋转
+    for direction in VOXEL_DIRECTION_VEC {
+        assert_eq!(model_rotation(&model, direction), Quat::IDENTITY);
+    }
+    model.rotation = ModelRotation::Horizontal;
+    for direction in VOXEL_DIRECTION_VEC {
+        assert_eq!(model_rotation(&model, direction), direction.to_quat());
     }
 }
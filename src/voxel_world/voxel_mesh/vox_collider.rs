@@ -0,0 +1,195 @@
+// 通过 .vox 模型的体素占用生成碰撞体
+// 占用的格子合并成尽量少的长方体 再按照方块的方向旋转
+// 坐标和 bevy_vox_mesh 生成的mesh一样 z轴朝上转换成y轴朝上 以模型中心为原点
+
+use bevy::prelude::{UVec3, Vec3};
+use ndshape::{RuntimeShape, Shape};
+
+use crate::voxel_world::voxel::VoxelDirection;
+
+use super::{vox_size, vox_to_y_up, VOX_SCALE};
+
+// 模型的占用 y轴朝上
+#[derive(Debug, Clone)]
+pub struct VoxOccupancy {
+    pub size: UVec3,
+    pub filled: Vec<bool>,
+}
+
+// 合并之后的长方体 格子坐标 max 不包含
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxCuboid {
+    pub min: UVec3,
+    pub max: UVec3,
+}
+
+impl VoxOccupancy {
+    // 读取 vox 文件中的第一个模型
+    pub fn load(path: &str) -> Option<Self> {
+        let data = match dot_vox::load(format!("assets/{}", path).as_str()) {
+            Ok(data) => data,
+            Err(err) => {
+                println!("读取模型文件失败{}: {}", path, err);
+                return None;
+            }
+        };
+        Some(Self::from_model(data.models.first()?))
+    }
+
+    pub fn from_model(model: &dot_vox::Model) -> Self {
+        let size = vox_size(model);
+        let shape = RuntimeShape::<u32, 3>::new(size.to_array());
+        let mut filled = vec![false; shape.size() as usize];
+        for voxel in model.voxels.iter() {
+            let index = shape.linearize(vox_to_y_up(voxel).to_array());
+            filled[index as usize] = true;
+        }
+        Self { size, filled }
+    }
+
+    pub fn merge_cuboids(&self) -> Vec<VoxCuboid> {
+        merge_cuboids(self.size, &self.filled)
+    }
+
+    /**
+     * 方块坐标下的长方体 (中心 半边长) 中心相对于方块的中心
+     * 已经按照方向旋转 碰撞体不需要再旋转和缩放
+     */
+    pub fn cuboids(&self, direction: VoxelDirection) -> Vec<(Vec3, Vec3)> {
+        let rotation = direction.to_quat();
+        let half_model = self.size.as_vec3() / 2.0;
+        self.merge_cuboids()
+            .into_iter()
+            .map(|cuboid| {
+                let min = cuboid.min.as_vec3();
+                let max = cuboid.max.as_vec3();
+                let center = ((min + max) / 2.0 - half_model) * VOX_SCALE;
+                let half_size = (max - min) / 2.0 * VOX_SCALE;
+                (rotation * center, (rotation * half_size).abs())
+            })
+            .collect()
+    }
+}
+
+/**
+ * 把占用的格子贪心地合并成长方体 filled 按照 ndshape 的顺序排列
+ * 先沿x轴 再沿z轴 最后沿y轴扩展 每个格子只属于一个长方体
+ */
+pub fn merge_cuboids(size: UVec3, filled: &[bool]) -> Vec<VoxCuboid> {
+    let shape = RuntimeShape::<u32, 3>::new(size.to_array());
+    let [sx, sy, sz] = size.to_array();
+    let mut used = vec![false; filled.len()];
+    let free = |used: &Vec<bool>, x: u32, y: u32, z: u32| {
+        let index = shape.linearize([x, y, z]) as usize;
+        filled[index] && !used[index]
+    };
+    let mut cuboids = Vec::new();
+    for y in 0..sy {
+        for z in 0..sz {
+            for x in 0..sx {
+                if !free(&used, x, y, z) {
+                    continue;
+                }
+                let mut max_x = x + 1;
+                while max_x < sx && free(&used, max_x, y, z) {
+                    max_x += 1;
+                }
+                let mut max_z = z + 1;
+                while max_z < sz && (x..max_x).all(|x| free(&used, x, y, max_z)) {
+                    max_z += 1;
+                }
+                let mut max_y = y + 1;
+                while max_y < sy && (z..max_z).all(|z| (x..max_x).all(|x| free(&used, x, max_y, z)))
+                {
+                    max_y += 1;
+                }
+                for cy in y..max_y {
+                    for cz in z..max_z {
+                        for cx in x..max_x {
+                            used[shape.linearize([cx, cy, cz]) as usize] = true;
+                        }
+                    }
+                }
+                cuboids.push(VoxCuboid {
+                    min: UVec3::new(x, y, z),
+                    max: UVec3::new(max_x, max_y, max_z),
+                });
+            }
+        }
+    }
+    cuboids
+}
+
+#[test]
+fn test_merge_cuboids() {
+    // 4x4x4 的模型 下面一层是满的 上面是一根柱子
+    let size = UVec3::new(4, 4, 4);
+    let shape = RuntimeShape::<u32, 3>::new(size.to_array());
+    let mut filled = vec![false; shape.size() as usize];
+    for (index, cell) in filled.iter_mut().enumerate() {
+        let [x, y, z] = shape.delinearize(index as u32);
+        *cell = y == 0 || (x == 0 && z == 3);
+    }
+    let occupancy = VoxOccupancy { size, filled };
+    let cuboids = occupancy.merge_cuboids();
+    assert_eq!(
+        cuboids,
+        vec![
+            VoxCuboid {
+                min: UVec3::new(0, 0, 0),
+                max: UVec3::new(4, 1, 4),
+            },
+            VoxCuboid {
+                min: UVec3::new(0, 1, 3),
+                max: UVec3::new(1, 4, 4),
+            },
+        ]
+    );
+
+    // 旋转90度之后 x和z的长度交换
+    let rotated = occupancy.cuboids(VoxelDirection::X);
+    let (center, half_size) = rotated[1];
+    let expected = Vec3::new(0.5, 1.5, 0.5) * VOX_SCALE;
+    assert!((half_size - expected).length() < 1e-5);
+    let (center_z, _) = occupancy.cuboids(VoxelDirection::Z)[1];
+    assert!((center - VoxelDirection::X.to_quat() * center_z).length() < 1e-5);
+}
+
+#[test]
+fn test_vox_axis() {
+    use dot_vox::{Model, Size, Voxel};
+    // MagicaVoxel 中 z 轴朝上 沿着 y 轴不对称的 L 形
+    let model = Model {
+        size: Size { x: 2, y: 3, z: 1 },
+        voxels: vec![
+            Voxel {
+                x: 0,
+                y: 0,
+                z: 0,
+                i: 0,
+            },
+            Voxel {
+                x: 1,
+                y: 0,
+                z: 0,
+                i: 0,
+            },
+            Voxel {
+                x: 0,
+                y: 2,
+                z: 0,
+                i: 0,
+            },
+        ],
+    };
+    let occupancy = VoxOccupancy::from_model(&model);
+    assert_eq!(occupancy.size, UVec3::new(2, 1, 3));
+    let shape = RuntimeShape::<u32, 3>::new(occupancy.size.to_array());
+    let filled = |p: [u32; 3]| occupancy.filled[shape.linearize(p) as usize];
+    // y 和 z 交换 没有镜像
+    assert!(filled([0, 0, 0]));
+    assert!(filled([1, 0, 0]));
+    assert!(filled([0, 0, 2]));
+    assert!(!filled([1, 0, 2]));
+    assert_eq!(occupancy.filled.iter().filter(|cell| **cell).count(), 3);
+}
@@ -0,0 +1,134 @@
+// 世界高度
+// 世界在 y 方向上只有有限的区块 最低和最高的方块高度写在 world.ron 中
+// 高度会按照区块的边界对齐 一列区块从 min_chunk_y 到 max_chunk_y
+// 区块的查询 加载 光照和生成mesh时的整列数据都使用这里的高度
+
+use std::ops::RangeInclusive;
+
+use bevy::prelude::IVec3;
+use lazy_static::lazy_static;
+use ndshape::RuntimeShape;
+use serde::{Deserialize, Serialize};
+
+use crate::{CHUNK_SIZE, CHUNK_SIZE_U32};
+
+use super::pos::{BlockPos, ChunkPos};
+
+pub const WORLD_RON: &str = "world.ron";
+
+lazy_static! {
+    pub static ref WORLD_HEIGHT: WorldHeight = WorldConfig::load(WORLD_RON).height();
+}
+
+// 世界配置
+// min_y: 最低的方块高度 max_y: 最高的方块高度(不包含)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldConfig {
+    pub min_y: i32,
+    pub max_y: i32,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        // 原来固定的 -7..=8 层区块
+        Self {
+            min_y: -120,
+            max_y: 136,
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn load(path: &str) -> Self {
+        match std::fs::File::open(path) {
+            Ok(file) => match ron::de::from_reader(file) {
+                Ok(config) => config,
+                Err(err) => {
+                    println!("世界配置解析失败{:?}", err);
+                    WorldConfig::default()
+                }
+            },
+            Err(_) => {
+                println!("没有找到世界配置文件{}", path);
+                WorldConfig::default()
+            }
+        }
+    }
+
+    /**
+     * 换算成区块的范围
+     * 包含 min_y 和 max_y - 1 的区块都在世界中
+     */
+    pub fn height(&self) -> WorldHeight {
+        if self.max_y <= self.min_y {
+            println!("世界高度{}..{}是空的 使用默认高度", self.min_y, self.max_y);
+            return WorldConfig::default().height();
+        }
+        WorldHeight {
+            min_chunk_y: BlockPos::new(0, self.min_y, 0).chunk().0.y,
+            max_chunk_y: BlockPos::new(0, self.max_y - 1, 0).chunk().0.y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldHeight {
+    pub min_chunk_y: i32,
+    pub max_chunk_y: i32,
+}
+
+impl WorldHeight {
+    // 一列区块的 y
+    pub fn chunk_ys(&self) -> RangeInclusive<i32> {
+        self.min_chunk_y..=self.max_chunk_y
+    }
+
+    pub fn chunk_count(&self) -> u32 {
+        (self.max_chunk_y - self.min_chunk_y + 1) as u32
+    }
+
+    // 一列的方块数量
+    pub fn height(&self) -> u32 {
+        self.chunk_count() * CHUNK_SIZE_U32
+    }
+
+    // 最下面的方块
+    pub fn min_y(&self) -> i32 {
+        ChunkPos(IVec3::new(0, self.min_chunk_y, 0)).min_block().0.y
+    }
+
+    // 最上面的方块之上
+    pub fn max_y(&self) -> i32 {
+        self.min_y() + self.chunk_count() as i32 * CHUNK_SIZE
+    }
+
+    pub fn contains_chunk_y(&self, chunk_y: i32) -> bool {
+        self.chunk_ys().contains(&chunk_y)
+    }
+
+    // 整列数据的形状 x 和 z 方向的大小是 xz
+    pub fn column_shape(&self, xz: u32) -> RuntimeShape<u32, 3> {
+        RuntimeShape::<u32, 3>::new([xz, self.height(), xz])
+    }
+}
+
+#[test]
+fn test_world_height() {
+    let height = WorldConfig::default().height();
+    assert_eq!(height.chunk_ys(), -7..=8);
+    assert_eq!(height.height(), 256);
+    assert_eq!(height.min_y(), -120);
+    assert_eq!(height.max_y(), 136);
+    // 没有对齐的高度会扩展到整个区块
+    let height = WorldConfig {
+        min_y: -64,
+        max_y: 9,
+    }
+    .height();
+    assert_eq!(height.chunk_ys(), -4..=1);
+    assert_eq!(height.min_y(), -72);
+    assert_eq!(height.max_y(), 24);
+    // 空的高度使用默认
+    let height = WorldConfig { min_y: 0, max_y: 0 }.height();
+    assert_eq!(height, WorldConfig::default().height());
+}